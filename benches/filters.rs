@@ -49,6 +49,16 @@ pub fn filter_linear_custom(c: &mut Criterion) {
         b.iter(|| run_filter(&img, LinearMean::default()));
     });
 
+    group.bench_function("filter linear gaussian 21x21 img 1000x1000", |b| {
+        let size = FilterWindowSize::new(21, 21);
+        b.iter(|| run_filter(&img, LinearGaussian::new(size, ExtendValue::Closest)));
+    });
+
+    group.bench_function("filter linear mean 21x21 img 1000x1000", |b| {
+        let size = FilterWindowSize::new(21, 21);
+        b.iter(|| run_filter(&img, LinearMean::new(size, ExtendValue::Closest)));
+    });
+
     group.bench_function("filter CannyEdgeDetection img 1000x1000", |b| {
         b.iter(|| run_filter(&img, CannyEdgeDetection::default()));
     });
@@ -61,6 +71,11 @@ pub fn filter_linear_custom(c: &mut Criterion) {
         b.iter(|| run_filter(&img, MedianFilter::default()));
     });
 
+    group.bench_function("filter MedianFilter 21x21 img 1000x1000", |b| {
+        let size = FilterWindowSize::new(21, 21);
+        b.iter(|| run_filter(&img, MedianFilter::new(size, ExtendValue::Closest)));
+    });
+
     group.bench_function("filter CutBrightness img 1000x1000", |b| {
         b.iter(|| run_filter(&img, CutBrightness::default()));
     });
@@ -0,0 +1,93 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitDepth {
+    #[default]
+    U8,
+    U16,
+    F32,
+}
+
+impl BitDepth {
+    // float values are kept in [0; 1]
+    pub fn max_value(&self) -> f64 {
+        match self {
+            BitDepth::U8 => u8::MAX as f64,
+            BitDepth::U16 => u16::MAX as f64,
+            BitDepth::F32 => 1_f64,
+        }
+    }
+
+    // count of histogram bins covering [0; max_value]
+    pub fn levels_count(&self) -> usize {
+        match self {
+            BitDepth::U8 => u8::MAX as usize + 1,
+            BitDepth::U16 | BitDepth::F32 => u16::MAX as usize + 1,
+        }
+    }
+
+    pub fn level_of(&self, val: f64) -> usize {
+        let max_level = self.levels_count() - 1;
+        let level = val / self.max_value() * max_level as f64;
+        (level as usize).min(max_level)
+    }
+
+    pub fn value_of_level(&self, level: usize) -> f64 {
+        level as f64 * self.max_value() / (self.levels_count() - 1) as f64
+    }
+
+    pub fn to_u8(&self, val: f64) -> u8 {
+        (val * u8::MAX as f64 / self.max_value()) as u8
+    }
+
+    pub fn to_u16(&self, val: f64) -> u16 {
+        (val * u16::MAX as f64 / self.max_value()) as u16
+    }
+
+    pub fn convert_value(&self, val: f64, to: BitDepth) -> f64 {
+        let converted = val * to.max_value() / self.max_value();
+        match to {
+            BitDepth::U8 | BitDepth::U16 => converted.round().clamp(0_f64, to.max_value()),
+            BitDepth::F32 => converted,
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            BitDepth::U8 => "8 бит",
+            BitDepth::U16 => "16 бит",
+            BitDepth::F32 => "32 бита (float)",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitDepth;
+
+    #[test]
+    fn levels_match_8_bit_truncation() {
+        for val in [0.0, 0.7, 1.0, 127.5, 254.9, 255.0, 300.0, -3.0].iter() {
+            assert_eq!(BitDepth::U8.level_of(*val), *val as u8 as usize);
+            assert_eq!(BitDepth::U8.to_u8(*val), *val as u8);
+        }
+    }
+
+    #[test]
+    fn wide_ranges_are_quantized_only_on_request() {
+        assert_eq!(BitDepth::U16.level_of(65535.0), 65535);
+        assert_eq!(BitDepth::U16.level_of(1000.0), 1000);
+        assert_eq!(BitDepth::U16.to_u8(65535.0), 255);
+        assert_eq!(BitDepth::U16.to_u8(257.0 * 100.0), 100);
+
+        assert_eq!(BitDepth::F32.level_of(1.0), 65535);
+        assert_eq!(BitDepth::F32.level_of(0.0), 0);
+        assert_eq!(BitDepth::F32.to_u8(0.5), 127);
+        assert_eq!(BitDepth::F32.to_u16(1.0), 65535);
+
+        assert!((BitDepth::F32.value_of_level(65535) - 1.0).abs() < 1e-12);
+        assert!((BitDepth::U16.value_of_level(1234) - 1234.0).abs() < 1e-12);
+
+        assert_eq!(BitDepth::U8.convert_value(255.0, BitDepth::U16), 65535.0);
+        assert_eq!(BitDepth::U16.convert_value(65535.0, BitDepth::F32), 1.0);
+        assert_eq!(BitDepth::F32.convert_value(2.0, BitDepth::U8), 255.0);
+    }
+}
@@ -0,0 +1,291 @@
+use super::filter::filter_option::ImgChannel;
+
+// Every channel is kept in [0; max value of the bit depth], like the RGB ones,
+// so the filters process the channels of any color space the same way:
+//   H is the hue angle / 360, S, V and L (lightness) are in [0; 1],
+//   Cb and Cr are shifted by a half of the range,
+//   L* is divided by 100, a* and b* are shifted by 128 and divided by 255.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    Rgb,
+    Hsv,
+    Hsl,
+    YCbCr,
+    Lab,
+}
+
+const ALL: [ColorSpace; 5] = [
+    ColorSpace::Rgb,
+    ColorSpace::Hsv,
+    ColorSpace::Hsl,
+    ColorSpace::YCbCr,
+    ColorSpace::Lab,
+];
+
+// D65 white point
+const WHITE_X: f64 = 0.95047;
+const WHITE_Y: f64 = 1.0;
+const WHITE_Z: f64 = 1.08883;
+
+const LAB_EPS: f64 = 6_f64 / 29_f64;
+
+// BT.601 luma weights
+const Y_RED: f64 = 0.299;
+const Y_BLUE: f64 = 0.114;
+const Y_GREEN: f64 = 1_f64 - Y_RED - Y_BLUE;
+
+impl ColorSpace {
+    pub fn channels(&self) -> [ImgChannel; 3] {
+        match self {
+            ColorSpace::Rgb => [ImgChannel::R, ImgChannel::G, ImgChannel::B],
+            ColorSpace::Hsv => [ImgChannel::H, ImgChannel::S, ImgChannel::V],
+            ColorSpace::Hsl => [ImgChannel::H, ImgChannel::S, ImgChannel::Lt],
+            ColorSpace::YCbCr => [ImgChannel::Y, ImgChannel::Cb, ImgChannel::Cr],
+            ColorSpace::Lab => [ImgChannel::LabL, ImgChannel::LabA, ImgChannel::LabB],
+        }
+    }
+
+    pub fn of_channels(channels: &[ImgChannel]) -> Option<ColorSpace> {
+        ALL.iter()
+            .find(|color_space| {
+                color_space
+                    .channels()
+                    .iter()
+                    .all(|ch| channels.contains(ch))
+            })
+            .copied()
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            ColorSpace::Rgb => "RGB",
+            ColorSpace::Hsv => "HSV",
+            ColorSpace::Hsl => "HSL",
+            ColorSpace::YCbCr => "YCbCr",
+            ColorSpace::Lab => "L*a*b*",
+        }
+    }
+
+    // the values are normalized to [0; 1], the result is clamped to [0; 1]
+    pub fn convert_from_rgb(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let [r, g, b] = rgb;
+
+        let vals = match self {
+            ColorSpace::Rgb => rgb,
+            ColorSpace::Hsv => {
+                let (hue, max, min) = hue_max_min(r, g, b);
+                let s = if max > 0_f64 {
+                    (max - min) / max
+                } else {
+                    0_f64
+                };
+                [hue, s, max]
+            }
+            ColorSpace::Hsl => {
+                let (hue, max, min) = hue_max_min(r, g, b);
+                let l = (max + min) / 2_f64;
+                let s = if max > min {
+                    (max - min) / (1_f64 - (2_f64 * l - 1_f64).abs())
+                } else {
+                    0_f64
+                };
+                [hue, s, l]
+            }
+            ColorSpace::YCbCr => {
+                let y = Y_RED * r + Y_GREEN * g + Y_BLUE * b;
+                let cb = (b - y) / (2_f64 * (1_f64 - Y_BLUE));
+                let cr = (r - y) / (2_f64 * (1_f64 - Y_RED));
+                [y, cb + 0.5, cr + 0.5]
+            }
+            ColorSpace::Lab => {
+                let (r, g, b) = (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b));
+
+                let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+                let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+                let z = 0.0193339 * r + 0.1191920 * g + 0.9503041 * b;
+
+                let f_x = lab_f(x / WHITE_X);
+                let f_y = lab_f(y / WHITE_Y);
+                let f_z = lab_f(z / WHITE_Z);
+
+                let l = 116_f64 * f_y - 16_f64;
+                let a = 500_f64 * (f_x - f_y);
+                let b = 200_f64 * (f_y - f_z);
+
+                [
+                    l / 100_f64,
+                    (a + 128_f64) / 255_f64,
+                    (b + 128_f64) / 255_f64,
+                ]
+            }
+        };
+
+        clamp_unit(vals)
+    }
+
+    // the values are normalized to [0; 1], the result is clamped to [0; 1]
+    pub fn convert_to_rgb(&self, vals: [f64; 3]) -> [f64; 3] {
+        let rgb = match self {
+            ColorSpace::Rgb => vals,
+            ColorSpace::Hsv => {
+                let [hue, s, v] = vals;
+                let chroma = v * s;
+                hue_to_rgb(hue, chroma, v - chroma)
+            }
+            ColorSpace::Hsl => {
+                let [hue, s, l] = vals;
+                let chroma = (1_f64 - (2_f64 * l - 1_f64).abs()) * s;
+                hue_to_rgb(hue, chroma, l - chroma / 2_f64)
+            }
+            ColorSpace::YCbCr => {
+                let [y, cb, cr] = vals;
+                let r = y + 2_f64 * (1_f64 - Y_RED) * (cr - 0.5);
+                let b = y + 2_f64 * (1_f64 - Y_BLUE) * (cb - 0.5);
+                let g = (y - Y_RED * r - Y_BLUE * b) / Y_GREEN;
+                [r, g, b]
+            }
+            ColorSpace::Lab => {
+                let l = vals[0] * 100_f64;
+                let a = vals[1] * 255_f64 - 128_f64;
+                let b = vals[2] * 255_f64 - 128_f64;
+
+                let f_y = (l + 16_f64) / 116_f64;
+                let f_x = f_y + a / 500_f64;
+                let f_z = f_y - b / 200_f64;
+
+                let x = WHITE_X * lab_f_inv(f_x);
+                let y = WHITE_Y * lab_f_inv(f_y);
+                let z = WHITE_Z * lab_f_inv(f_z);
+
+                let r = 3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+                let g = -0.9692660 * x + 1.8760108 * y + 0.0415560 * z;
+                let b = 0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+
+                [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)]
+            }
+        };
+
+        clamp_unit(rgb)
+    }
+}
+
+fn clamp_unit(vals: [f64; 3]) -> [f64; 3] {
+    [
+        vals[0].clamp(0_f64, 1_f64),
+        vals[1].clamp(0_f64, 1_f64),
+        vals[2].clamp(0_f64, 1_f64),
+    ]
+}
+
+// hue in [0; 1)
+fn hue_max_min(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let sector = if delta == 0_f64 {
+        0_f64
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6_f64)
+    } else if max == g {
+        (b - r) / delta + 2_f64
+    } else {
+        (r - g) / delta + 4_f64
+    };
+
+    (sector / 6_f64, max, min)
+}
+
+fn hue_to_rgb(hue: f64, chroma: f64, min: f64) -> [f64; 3] {
+    let sector = hue.rem_euclid(1_f64) * 6_f64;
+    let x = chroma * (1_f64 - (sector % 2_f64 - 1_f64).abs());
+
+    let (r, g, b) = match sector as usize {
+        0 => (chroma, x, 0_f64),
+        1 => (x, chroma, 0_f64),
+        2 => (0_f64, chroma, x),
+        3 => (0_f64, x, chroma),
+        4 => (x, 0_f64, chroma),
+        _ => (chroma, 0_f64, x),
+    };
+
+    [r + min, g + min, b + min]
+}
+
+fn srgb_to_linear(val: f64) -> f64 {
+    if val <= 0.04045 {
+        val / 12.92
+    } else {
+        ((val + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(val: f64) -> f64 {
+    if val <= 0.0031308 {
+        val * 12.92
+    } else {
+        1.055 * val.powf(1_f64 / 2.4) - 0.055
+    }
+}
+
+fn lab_f(t: f64) -> f64 {
+    if t > LAB_EPS.powi(3) {
+        t.cbrt()
+    } else {
+        t / (3_f64 * LAB_EPS.powi(2)) + 4_f64 / 29_f64
+    }
+}
+
+fn lab_f_inv(t: f64) -> f64 {
+    if t > LAB_EPS {
+        t.powi(3)
+    } else {
+        3_f64 * LAB_EPS.powi(2) * (t - 4_f64 / 29_f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorSpace, ALL};
+
+    #[test]
+    fn conversions_are_reversible() {
+        let colors = [
+            [0_f64, 0_f64, 0_f64],
+            [1_f64, 1_f64, 1_f64],
+            [1_f64, 0_f64, 0_f64],
+            [0.2, 0.9, 0.4],
+            [0.1, 0.3, 0.8],
+            [0.7, 0.1, 0.9],
+            [0.5, 0.5, 0.3],
+        ];
+
+        for color_space in ALL.iter() {
+            for rgb in colors.iter() {
+                let vals = color_space.convert_from_rgb(*rgb);
+                assert!(
+                    vals.iter().all(|val| (0_f64..=1_f64).contains(val)),
+                    "{:?} {:?} {:?}",
+                    color_space,
+                    rgb,
+                    vals
+                );
+
+                let back = color_space.convert_to_rgb(vals);
+                for (init, res) in rgb.iter().zip(back.iter()) {
+                    assert!(
+                        (init - res).abs() < 1e-5,
+                        "{:?} {:?} {:?}",
+                        color_space,
+                        rgb,
+                        back
+                    );
+                }
+            }
+        }
+
+        let lab_white = ColorSpace::Lab.convert_from_rgb([1_f64, 1_f64, 1_f64]);
+        assert!((lab_white[0] - 1_f64).abs() < 1e-4);
+        assert!((lab_white[1] - 128_f64 / 255_f64).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,224 @@
+use super::{checked_len, invalid_size_msg};
+use crate::img::Img;
+use fltk::enums::ColorDepth;
+
+const FILE_HEADER_SIZE: usize = 14;
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+pub fn decode(bytes: &[u8]) -> Result<Img, String> {
+    let rd = ByteReader { bytes };
+
+    let data_offset = rd.u32(10)? as usize;
+    let header_size = rd.u32(FILE_HEADER_SIZE)? as usize;
+
+    let (w, h, bits_per_pixel, compression, colors_used, palette_entry_size): (
+        i64,
+        i64,
+        u16,
+        u32,
+        usize,
+        usize,
+    ) = if header_size == 12 {
+        let w = rd.u16(18)? as i64;
+        let h = rd.u16(20)? as i64;
+        let bpp = rd.u16(24)?;
+        (w, h, bpp, BI_RGB, 0, 3)
+    } else if header_size >= 40 {
+        let w = rd.i32(18)? as i64;
+        let h = rd.i32(22)? as i64;
+        let bpp = rd.u16(28)?;
+        let compression = rd.u32(30)?;
+        let colors_used = rd.u32(46)? as usize;
+        (w, h, bpp, compression, colors_used, 4)
+    } else {
+        return Err(format!("неизвестный размер заголовка {}", header_size));
+    };
+
+    if w <= 0 || h == 0 {
+        return Err(invalid_size_msg(w, h));
+    }
+
+    let top_down = h < 0;
+    let (w, h) = (w as usize, h.unsigned_abs() as usize);
+    // no more than 4 values per pixel, so the lengths of the pixels below fit as well
+    checked_len(w, h, 4)?;
+
+    let masks: Option<[u32; 4]> = match compression {
+        BI_RGB => None,
+        BI_BITFIELDS | BI_ALPHABITFIELDS if bits_per_pixel == 16 || bits_per_pixel == 32 => {
+            // masks follow a 40 bytes header and are a part of the larger ones
+            let masks_pos = FILE_HEADER_SIZE + 40;
+            let has_alpha_mask = compression == BI_ALPHABITFIELDS || header_size >= 56;
+            Some([
+                rd.u32(masks_pos)?,
+                rd.u32(masks_pos + 4)?,
+                rd.u32(masks_pos + 8)?,
+                if has_alpha_mask {
+                    rd.u32(masks_pos + 12)?
+                } else {
+                    0
+                },
+            ])
+        }
+        _ => {
+            return Err(format!(
+                "сжатие типа {} для {} бит на пиксель не поддерживается",
+                compression, bits_per_pixel
+            ))
+        }
+    };
+
+    let row_size: usize = (bits_per_pixel as usize * w).div_ceil(32) * 4;
+    let data_len: usize = row_size
+        .checked_mul(h)
+        .ok_or_else(|| invalid_size_msg(w, h))?;
+    let data = bytes.get(data_offset..).unwrap_or(&[]);
+    if data.len() < data_len {
+        return Err("неожиданный конец файла".to_string());
+    }
+
+    let rows = (0..h).map(|row| {
+        let file_row = if top_down { row } else { h - 1 - row };
+        &data[file_row * row_size..(file_row + 1) * row_size]
+    });
+
+    match bits_per_pixel {
+        1 | 4 | 8 => {
+            let palette_pos = FILE_HEADER_SIZE + header_size;
+            let palette_len = if colors_used == 0 {
+                1 << bits_per_pixel
+            } else {
+                colors_used
+            };
+
+            let mut palette = Vec::<[u8; 3]>::with_capacity(palette_len);
+            for entry_num in 0..palette_len {
+                let pos = palette_pos + entry_num * palette_entry_size;
+                let bgr = bytes
+                    .get(pos..pos + 3)
+                    .ok_or_else(|| "неожиданный конец палитры".to_string())?;
+                palette.push([bgr[2], bgr[1], bgr[0]]);
+            }
+
+            let is_gray = palette.iter().all(|c| c[0] == c[1] && c[1] == c[2]);
+            let color_depth = if is_gray {
+                ColorDepth::L8
+            } else {
+                ColorDepth::Rgb8
+            };
+
+            let bpp = bits_per_pixel as usize;
+            let pixels_per_byte = 8 / bpp;
+            let index_mask: u8 = ((1_u16 << bpp) - 1) as u8;
+
+            let mut pixels = Vec::<u8>::with_capacity(w * h * (color_depth as u8 as usize));
+            for row in rows {
+                for col in 0..w {
+                    let byte = row[col / pixels_per_byte];
+                    let shift = (pixels_per_byte - 1 - col % pixels_per_byte) * bpp;
+                    let index = ((byte >> shift) & index_mask) as usize;
+
+                    let color = palette
+                        .get(index)
+                        .ok_or_else(|| format!("индекс {} вне палитры", index))?;
+                    if is_gray {
+                        pixels.push(color[0]);
+                    } else {
+                        pixels.extend_from_slice(color);
+                    }
+                }
+            }
+
+            Ok(Img::from_pixels(w, h, color_depth, pixels))
+        }
+        24 => {
+            let mut pixels = Vec::<u8>::with_capacity(w * h * 3);
+            for row in rows {
+                for bgr in row[..w * 3].chunks_exact(3) {
+                    pixels.extend_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+                }
+            }
+
+            Ok(Img::from_pixels(w, h, ColorDepth::Rgb8, pixels))
+        }
+        16 | 32 => {
+            let bytes_per_pixel = bits_per_pixel as usize / 8;
+            let masks: [u32; 4] = masks.unwrap_or(if bits_per_pixel == 16 {
+                [0x7C00, 0x03E0, 0x001F, 0]
+            } else {
+                [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0]
+            });
+
+            let color_depth = if masks[3] != 0 {
+                ColorDepth::Rgba8
+            } else {
+                ColorDepth::Rgb8
+            };
+            let channels_count = color_depth as u8 as usize;
+
+            let mut pixels = Vec::<u8>::with_capacity(w * h * channels_count);
+            for row in rows {
+                for p in row[..w * bytes_per_pixel].chunks_exact(bytes_per_pixel) {
+                    let val: u32 = if bytes_per_pixel == 2 {
+                        u16::from_le_bytes([p[0], p[1]]) as u32
+                    } else {
+                        u32::from_le_bytes([p[0], p[1], p[2], p[3]])
+                    };
+
+                    for mask in &masks[..channels_count] {
+                        pixels.push(extract_by_mask(val, *mask));
+                    }
+                }
+            }
+
+            Ok(Img::from_pixels(w, h, color_depth, pixels))
+        }
+        _ => Err(format!(
+            "{} бит на пиксель не поддерживается",
+            bits_per_pixel
+        )),
+    }
+}
+
+fn extract_by_mask(val: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+
+    let shift = mask.trailing_zeros();
+    let max_val = (mask >> shift) as u64;
+    let channel_val = ((val & mask) >> shift) as u64;
+
+    (channel_val * 255 / max_val) as u8
+}
+
+struct ByteReader<'bytes> {
+    bytes: &'bytes [u8],
+}
+
+impl<'bytes> ByteReader<'bytes> {
+    fn get<const N: usize>(&self, pos: usize) -> Result<[u8; N], String> {
+        let mut buf = [0_u8; N];
+        let slice = self
+            .bytes
+            .get(pos..pos + N)
+            .ok_or_else(|| "неожиданный конец заголовка".to_string())?;
+        buf.copy_from_slice(slice);
+        Ok(buf)
+    }
+
+    fn u16(&self, pos: usize) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.get(pos)?))
+    }
+
+    fn u32(&self, pos: usize) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.get(pos)?))
+    }
+
+    fn i32(&self, pos: usize) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.get(pos)?))
+    }
+}
@@ -0,0 +1,56 @@
+use crate::img::Img;
+use fltk::enums::ColorDepth;
+use jpeg_decoder::{Decoder, PixelFormat};
+
+pub fn decode(bytes: &[u8]) -> Result<Img, String> {
+    let mut decoder = Decoder::new(bytes);
+
+    let pixels: Vec<u8> = decoder.decode().map_err(|err| err.to_string())?;
+
+    let info = decoder
+        .info()
+        .ok_or_else(|| "нет информации о размерах изображения".to_string())?;
+
+    let (w, h) = (info.width as usize, info.height as usize);
+
+    let (color_depth, pixels): (ColorDepth, Vec<u8>) = match info.pixel_format {
+        PixelFormat::L8 => (ColorDepth::L8, pixels),
+        PixelFormat::L16 => {
+            let pixels_16bit: Vec<u16> = pixels
+                .chunks_exact(2)
+                .map(|p| u16::from_ne_bytes([p[0], p[1]]))
+                .collect();
+            if pixels_16bit.len() != w * h {
+                return Err(format!(
+                    "получено {} значений для изображения {}x{}",
+                    pixels_16bit.len(),
+                    w,
+                    h
+                ));
+            }
+            return Ok(Img::from_pixels_u16(w, h, ColorDepth::L8, pixels_16bit));
+        }
+        PixelFormat::RGB24 => (ColorDepth::Rgb8, pixels),
+        PixelFormat::CMYK32 => {
+            let mut pixels_rgb = Vec::<u8>::with_capacity(w * h * 3);
+            for cmyk in pixels.chunks_exact(4) {
+                let k = 255 - cmyk[3] as u32;
+                for c in &cmyk[..3] {
+                    pixels_rgb.push(((255 - *c as u32) * k / 255) as u8);
+                }
+            }
+            (ColorDepth::Rgb8, pixels_rgb)
+        }
+    };
+
+    if pixels.len() != w * h * (color_depth as u8 as usize) {
+        return Err(format!(
+            "получено {} значений для изображения {}x{}",
+            pixels.len(),
+            w,
+            h
+        ));
+    }
+
+    Ok(Img::from_pixels(w, h, color_depth, pixels))
+}
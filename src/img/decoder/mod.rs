@@ -0,0 +1,309 @@
+use super::Img;
+use crate::my_err::MyError;
+
+mod bmp;
+mod jpeg;
+mod png;
+mod pnm;
+mod raw;
+
+pub use raw::encode as encode_raw;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Pnm,
+    Pfm,
+    Raw,
+}
+
+impl FileFormat {
+    fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(FileFormat::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8]) {
+            Some(FileFormat::Jpeg)
+        } else if bytes.starts_with(b"BM") {
+            Some(FileFormat::Bmp)
+        } else if bytes.len() >= 2 && bytes[0] == b'P' && (b'1'..=b'6').contains(&bytes[1]) {
+            Some(FileFormat::Pnm)
+        } else if bytes.starts_with(b"PF") || bytes.starts_with(b"Pf") {
+            Some(FileFormat::Pfm)
+        } else if bytes.starts_with(raw::MAGIC) {
+            Some(FileFormat::Raw)
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            FileFormat::Png => "PNG",
+            FileFormat::Jpeg => "JPEG",
+            FileFormat::Bmp => "BMP",
+            FileFormat::Pnm => "PGM/PPM",
+            FileFormat::Pfm => "PFM",
+            FileFormat::Raw => "IPRAW",
+        }
+    }
+}
+
+pub fn decode_file(path: &str) -> Result<Img, MyError> {
+    let bytes: Vec<u8> = std::fs::read(path).map_err(|err| {
+        MyError::new(format!(
+            "Не удалось прочитать файл изображения '{}': {}",
+            path, err
+        ))
+    })?;
+
+    decode(&bytes).map_err(|err| {
+        MyError::new(format!(
+            "Не удалось загрузить изображение '{}': {}",
+            path,
+            err.get_message()
+        ))
+    })
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Img, MyError> {
+    let format = FileFormat::detect(bytes).ok_or_else(|| {
+        MyError::new(
+            "неизвестный формат файла (поддерживаются PNG, JPEG, BMP, PGM/PPM, PFM)".to_string(),
+        )
+    })?;
+
+    let decoded = match format {
+        FileFormat::Png => png::decode(bytes),
+        FileFormat::Jpeg => jpeg::decode(bytes),
+        FileFormat::Bmp => bmp::decode(bytes),
+        FileFormat::Pnm => pnm::decode(bytes),
+        FileFormat::Pfm => pnm::decode_pfm(bytes),
+        FileFormat::Raw => raw::decode(bytes),
+    };
+
+    decoded.map_err(|reason| MyError::new(format!("ошибка формата {}: {}", format.name(), reason)))
+}
+
+// the lengths computed from a broken header may overflow
+fn checked_len(w: usize, h: usize, vals_per_pixel: usize) -> Result<usize, String> {
+    w.checked_mul(h)
+        .and_then(|len| len.checked_mul(vals_per_pixel))
+        .ok_or_else(|| invalid_size_msg(w, h))
+}
+
+fn invalid_size_msg(w: impl std::fmt::Display, h: impl std::fmt::Display) -> String {
+    format!("неверный размер изображения {}x{}", w, h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+    use crate::img::{
+        filter::filter_option::ImgChannel, BitDepth, ColorSpace, Img, ImgLayer, Matrix2D,
+    };
+    use fltk::enums::ColorDepth;
+
+    fn create_test_img(color_depth: ColorDepth) -> Img {
+        let (w, h) = (5, 3);
+        let channels: &[ImgChannel] = match color_depth {
+            ColorDepth::L8 => &[ImgChannel::L],
+            ColorDepth::La8 => &[ImgChannel::L, ImgChannel::A],
+            ColorDepth::Rgb8 => &[ImgChannel::R, ImgChannel::G, ImgChannel::B],
+            ColorDepth::Rgba8 => &[ImgChannel::R, ImgChannel::G, ImgChannel::B, ImgChannel::A],
+        };
+
+        let layers: Vec<ImgLayer> = channels
+            .iter()
+            .enumerate()
+            .map(|(ch_num, ch)| {
+                let mut mat = Matrix2D::empty_with_size(w, h);
+                for pos in mat.area().iter_pixels() {
+                    mat[pos] = (ch_num * 50 + pos.row * w + pos.col) as f64;
+                }
+                ImgLayer::new(mat, *ch)
+            })
+            .collect();
+
+        Img::from_layers(layers, color_depth)
+    }
+
+    fn assert_imgs_equal(img1: &Img, img2: &Img) {
+        assert_eq!(img1.color_depth(), img2.color_depth());
+        assert_eq!(img1.bit_depth(), img2.bit_depth());
+        assert_eq!(img1.w(), img2.w());
+        assert_eq!(img1.h(), img2.h());
+        for (l1, l2) in img1.layers().iter().zip(img2.layers().iter()) {
+            assert_eq!(l1.channel(), l2.channel());
+            assert_eq!(l1.matrix().vals(), l2.matrix().vals());
+        }
+    }
+
+    fn save_and_read(img: &Img, file_name: &str) -> Vec<u8> {
+        let path = std::env::temp_dir().join(file_name);
+        img.try_save(path.to_str().unwrap()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decodes_png_of_every_color_depth() {
+        for (num, color_depth) in [
+            ColorDepth::L8,
+            ColorDepth::La8,
+            ColorDepth::Rgb8,
+            ColorDepth::Rgba8,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let img = create_test_img(*color_depth);
+            let bytes = save_and_read(&img, &format!("image_processing_decoder_{}.png", num));
+            assert_imgs_equal(&decode(&bytes).unwrap(), &img);
+        }
+    }
+
+    #[test]
+    fn decodes_jpeg() {
+        let img = create_test_img(ColorDepth::L8);
+        let bytes = save_and_read(&img, "image_processing_decoder.jpg");
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded.color_depth(), ColorDepth::L8);
+        assert_eq!((decoded.w(), decoded.h()), (img.w(), img.h()));
+
+        let orig_vals = img.layer(0).matrix().vals();
+        let decoded_vals = decoded.layer(0).matrix().vals();
+        for (orig, dec) in orig_vals.iter().zip(decoded_vals.iter()) {
+            assert!((orig - dec).abs() <= 3.0, "{} vs {}", orig, dec);
+        }
+    }
+
+    #[test]
+    fn decodes_pgm_and_ppm() {
+        let pgm_ascii = b"P2\n# comment\n3 2\n255\n0 1 2\n3 4 255\n";
+        let pgm_bin: Vec<u8> = [&b"P5 3 2 255\n"[..], &[0, 1, 2, 3, 4, 255]].concat();
+        for bytes in [&pgm_ascii[..], &pgm_bin[..]].iter() {
+            let img = decode(bytes).unwrap();
+            assert_eq!(img.color_depth(), ColorDepth::L8);
+            assert_eq!(
+                img.layer(0).matrix().vals(),
+                &vec![0.0, 1.0, 2.0, 3.0, 4.0, 255.0]
+            );
+        }
+
+        let ppm_bin: Vec<u8> = [&b"P6\n2 1\n15\n"[..], &[15, 0, 0, 0, 15, 0]].concat();
+        let img = decode(&ppm_bin).unwrap();
+        assert_eq!(img.color_depth(), ColorDepth::Rgb8);
+        assert_eq!(img.layer(0).matrix().vals(), &vec![255.0, 0.0]);
+        assert_eq!(img.layer(1).matrix().vals(), &vec![0.0, 255.0]);
+        assert_eq!(img.layer(2).matrix().vals(), &vec![0.0, 0.0]);
+
+        let pbm_ascii = b"P1 3 1 1 0 1";
+        let img = decode(pbm_ascii).unwrap();
+        assert_eq!(img.layer(0).matrix().vals(), &vec![0.0, 255.0, 0.0]);
+    }
+
+    #[test]
+    fn keeps_16_bit_and_float_values() {
+        let pgm_16bit: Vec<u8> = [&b"P5 2 1 65535\n"[..], &[0x12, 0x34, 0xFF, 0xFF]].concat();
+        let img = decode(&pgm_16bit).unwrap();
+        assert_eq!(img.bit_depth(), BitDepth::U16);
+        assert_eq!(img.layer(0).matrix().vals(), &vec![4660.0, 65535.0]);
+
+        let mut pfm: Vec<u8> = b"Pf\n1 2\n-1.0\n".to_vec();
+        pfm.extend_from_slice(&0.25_f32.to_le_bytes());
+        pfm.extend_from_slice(&0.75_f32.to_le_bytes());
+        let img = decode(&pfm).unwrap();
+        assert_eq!(img.bit_depth(), BitDepth::F32);
+        assert_eq!(img.color_depth(), ColorDepth::L8);
+        // the bottom row goes first
+        assert_eq!(img.layer(0).matrix().vals(), &vec![0.75, 0.25]);
+
+        let img_16bit = create_test_img(ColorDepth::Rgba8)
+            .converted_to_depth(BitDepth::U16)
+            .converted_to_depth(BitDepth::U8)
+            .converted_to_depth(BitDepth::U16);
+        let bytes = save_and_read(&img_16bit, "image_processing_decoder_16bit.png");
+        assert_imgs_equal(&decode(&bytes).unwrap(), &img_16bit);
+    }
+
+    #[test]
+    fn raw_keeps_exact_values() {
+        let mut img = create_test_img(ColorDepth::La8).converted_to_depth(BitDepth::F32);
+        img.layer_mut(0).matrix_mut()[0] = 0.123456789;
+
+        let bytes = save_and_read(&img, "image_processing_decoder.ipraw");
+        assert_imgs_equal(&decode(&bytes).unwrap(), &img);
+
+        let err = decode(&bytes[..bytes.len() - 1]).err().unwrap();
+        assert!(err.get_message().contains("IPRAW"), "{}", err);
+
+        let img_hsv = create_test_img(ColorDepth::Rgba8).converted_to_color_space(ColorSpace::Hsv);
+        let bytes = save_and_read(&img_hsv, "image_processing_decoder_hsv.ipraw");
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded.color_space(), ColorSpace::Hsv);
+        assert_imgs_equal(&decoded, &img_hsv);
+
+        // width and height are the last numbers of the header
+        let mut huge = bytes[..18].to_vec();
+        huge[10..18].copy_from_slice(&[0xFF; 8]);
+        let err = decode(&huge).err().unwrap();
+        assert!(err.get_message().contains("неверный размер"), "{}", err);
+    }
+
+    #[test]
+    fn decodes_bmp() {
+        // 2x2, 24 bits, bottom-up, rows padded to 4 bytes
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&70_u32.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(&54_u32.to_le_bytes());
+        bytes.extend_from_slice(&40_u32.to_le_bytes());
+        bytes.extend_from_slice(&2_i32.to_le_bytes());
+        bytes.extend_from_slice(&2_i32.to_le_bytes());
+        bytes.extend_from_slice(&1_u16.to_le_bytes());
+        bytes.extend_from_slice(&24_u16.to_le_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+        // bottom row: blue, white
+        bytes.extend_from_slice(&[255, 0, 0, 255, 255, 255, 0, 0]);
+        // top row: red, green
+        bytes.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+
+        let img = decode(&bytes).unwrap();
+        assert_eq!(img.color_depth(), ColorDepth::Rgb8);
+        assert_eq!(img.layer(0).matrix().vals(), &vec![255.0, 0.0, 0.0, 255.0]);
+        assert_eq!(img.layer(1).matrix().vals(), &vec![0.0, 255.0, 0.0, 255.0]);
+        assert_eq!(img.layer(2).matrix().vals(), &vec![0.0, 0.0, 255.0, 255.0]);
+    }
+
+    #[test]
+    fn errors_name_format_and_reason() {
+        let err = decode(b"P5 3 2 255\n\x00\x01").err().unwrap();
+        assert!(err.get_message().contains("PGM/PPM"), "{}", err);
+
+        let err = decode(b"BM\x00\x00").err().unwrap();
+        assert!(err.get_message().contains("BMP"), "{}", err);
+
+        let err = decode(b"\x89PNG\r\n\x1a\n\x00\x00").err().unwrap();
+        assert!(err.get_message().contains("PNG"), "{}", err);
+
+        let err = decode(b"P6 4294967296 4294967296 255\n").err().unwrap();
+        assert!(err.get_message().contains("неверный размер"), "{}", err);
+
+        let mut bmp = b"BM".to_vec();
+        bmp.resize(54, 0);
+        bmp[14] = 40;
+        bmp[18..22].copy_from_slice(&i32::MAX.to_le_bytes());
+        bmp[22..26].copy_from_slice(&i32::MAX.to_le_bytes());
+        bmp[28..30].copy_from_slice(&u16::MAX.to_le_bytes());
+        let err = decode(&bmp).err().unwrap();
+        assert!(err.get_message().contains("неверный размер"), "{}", err);
+
+        let err = decode(b"not an image").err().unwrap();
+        assert!(err.get_message().contains("неизвестный формат"), "{}", err);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::img::Img;
+use ::png::{BitDepth as PngBitDepth, ColorType, Decoder, Transformations};
+use fltk::enums::ColorDepth;
+
+pub fn decode(bytes: &[u8]) -> Result<Img, String> {
+    let mut decoder = Decoder::new(bytes);
+    // palette and low bit depths are expanded to 8 bits per sample, 16 bits are kept
+    decoder.set_transformations(Transformations::EXPAND);
+
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|err| err.to_string())?;
+    buf.truncate(info.buffer_size());
+
+    let color_depth = match info.color_type {
+        ColorType::Grayscale => ColorDepth::L8,
+        ColorType::GrayscaleAlpha => ColorDepth::La8,
+        ColorType::Rgb => ColorDepth::Rgb8,
+        ColorType::Rgba => ColorDepth::Rgba8,
+        ColorType::Indexed => {
+            return Err("палитра не была преобразована в RGB".to_string());
+        }
+    };
+
+    let (w, h) = (info.width as usize, info.height as usize);
+    let bytes_per_sample: usize = match info.bit_depth {
+        PngBitDepth::Sixteen => 2,
+        _ => 1,
+    };
+    let row_len = w * (color_depth as u8 as usize) * bytes_per_sample;
+
+    // rows may be longer than needed if the encoder has padded them
+    let samples = buf
+        .chunks(info.line_size)
+        .take(h)
+        .flat_map(|row| row[..row_len].iter().copied());
+
+    match bytes_per_sample {
+        1 => Ok(Img::from_pixels(w, h, color_depth, samples.collect())),
+        _ => {
+            let bytes: Vec<u8> = samples.collect();
+            let pixels: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|p| u16::from_be_bytes([p[0], p[1]]))
+                .collect();
+            Ok(Img::from_pixels_u16(w, h, color_depth, pixels))
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use super::{checked_len, invalid_size_msg};
+use crate::img::Img;
+use fltk::enums::ColorDepth;
+
+pub fn decode(bytes: &[u8]) -> Result<Img, String> {
+    let magic: u8 = bytes[1];
+    let mut reader = HeaderReader { bytes, pos: 2 };
+
+    let w: usize = reader.read_number("ширина")?;
+    let h: usize = reader.read_number("высота")?;
+
+    let is_bitmap = magic == b'1' || magic == b'4';
+    let max_val: usize = if is_bitmap {
+        1
+    } else {
+        reader.read_number("максимальное значение")?
+    };
+
+    if w == 0 || h == 0 {
+        return Err(invalid_size_msg(w, h));
+    }
+    if max_val == 0 || max_val > u16::MAX as usize {
+        return Err(format!("неверное максимальное значение {}", max_val));
+    }
+
+    let color_depth = match magic {
+        b'3' | b'6' => ColorDepth::Rgb8,
+        _ => ColorDepth::L8,
+    };
+    let vals_count: usize = checked_len(w, h, color_depth as u8 as usize)?;
+
+    let vals: Vec<usize> = match magic {
+        b'1' => (0..vals_count)
+            .map(|_| reader.read_bit())
+            .collect::<Result<_, _>>()?,
+        b'2' | b'3' => (0..vals_count)
+            .map(|_| reader.read_number("значение пикселя"))
+            .collect::<Result<_, _>>()?,
+        b'4' => {
+            let data = reader.binary_data();
+            let row_len = w.div_ceil(8);
+            if data.len() < row_len * h {
+                return Err(unexpected_end_msg());
+            }
+            let mut vals = Vec::<usize>::with_capacity(vals_count);
+            for row in data.chunks(row_len).take(h) {
+                for col in 0..w {
+                    vals.push(((row[col / 8] >> (7 - col % 8)) & 1) as usize);
+                }
+            }
+            vals
+        }
+        _ => {
+            let data = reader.binary_data();
+            let bytes_per_val: usize = if max_val > u8::MAX as usize { 2 } else { 1 };
+            if data.len() / bytes_per_val < vals_count {
+                return Err(unexpected_end_msg());
+            }
+            data.chunks(bytes_per_val)
+                .take(vals_count)
+                .map(|v| match bytes_per_val {
+                    1 => v[0] as usize,
+                    _ => u16::from_be_bytes([v[0], v[1]]) as usize,
+                })
+                .collect()
+        }
+    };
+
+    if is_bitmap {
+        // 1 is black for bitmaps
+        let pixels: Vec<u8> = vals.iter().map(|v| if *v == 0 { 255 } else { 0 }).collect();
+        return Ok(Img::from_pixels(w, h, color_depth, pixels));
+    }
+
+    let scale =
+        |v: usize, to_max: usize| -> usize { (v.min(max_val) * to_max + max_val / 2) / max_val };
+
+    if max_val <= u8::MAX as usize {
+        let pixels: Vec<u8> = vals
+            .iter()
+            .map(|v| scale(*v, u8::MAX as usize) as u8)
+            .collect();
+        Ok(Img::from_pixels(w, h, color_depth, pixels))
+    } else {
+        let pixels: Vec<u16> = vals
+            .iter()
+            .map(|v| scale(*v, u16::MAX as usize) as u16)
+            .collect();
+        Ok(Img::from_pixels_u16(w, h, color_depth, pixels))
+    }
+}
+
+pub fn decode_pfm(bytes: &[u8]) -> Result<Img, String> {
+    let color_depth = match bytes[1] {
+        b'F' => ColorDepth::Rgb8,
+        _ => ColorDepth::L8,
+    };
+    let mut reader = HeaderReader { bytes, pos: 2 };
+
+    let w: usize = reader.read_number("ширина")?;
+    let h: usize = reader.read_number("высота")?;
+    let scale: f64 = reader.read_float("масштаб")?;
+
+    if w == 0 || h == 0 {
+        return Err(invalid_size_msg(w, h));
+    }
+
+    // negative scale means little endian values
+    let little_endian = scale < 0_f64;
+
+    let vals_count: usize = checked_len(w, h, color_depth as u8 as usize)?;
+    let row_len: usize = vals_count / h;
+    let data = reader.binary_data();
+    if data.len() / 4 < vals_count {
+        return Err(unexpected_end_msg());
+    }
+
+    let vals: Vec<f32> = data
+        .chunks_exact(4)
+        .take(vals_count)
+        .map(|v| {
+            let v: [u8; 4] = [v[0], v[1], v[2], v[3]];
+            if little_endian {
+                f32::from_le_bytes(v)
+            } else {
+                f32::from_be_bytes(v)
+            }
+        })
+        .collect();
+
+    // rows are stored from the bottom to the top
+    let pixels: Vec<f32> = vals
+        .chunks_exact(row_len)
+        .rev()
+        .flat_map(|row| row.iter().copied())
+        .collect();
+
+    Ok(Img::from_pixels_f32(w, h, color_depth, pixels))
+}
+
+fn unexpected_end_msg() -> String {
+    "неожиданный конец файла".to_string()
+}
+
+struct HeaderReader<'bytes> {
+    bytes: &'bytes [u8],
+    pos: usize,
+}
+
+impl<'bytes> HeaderReader<'bytes> {
+    fn read_number(&mut self, what: &str) -> Result<usize, String> {
+        self.skip_whitespace_and_comments();
+
+        let start = self.pos;
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+
+        if start == self.pos {
+            return if self.pos >= self.bytes.len() {
+                Err(unexpected_end_msg())
+            } else {
+                Err(format!("ожидалось число ({})", what))
+            };
+        }
+
+        // the digits are ASCII, so the slice is valid UTF-8
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .unwrap()
+            .parse::<usize>()
+            .map_err(|err| format!("{}: {}", what, err))
+    }
+
+    fn read_float(&mut self, what: &str) -> Result<f64, String> {
+        self.skip_whitespace_and_comments();
+
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+
+        String::from_utf8_lossy(&self.bytes[start..self.pos])
+            .parse::<f64>()
+            .map_err(|err| format!("{}: {}", what, err))
+    }
+
+    fn read_bit(&mut self) -> Result<usize, String> {
+        self.skip_whitespace_and_comments();
+
+        match self.bytes.get(self.pos) {
+            Some(b) if *b == b'0' || *b == b'1' => {
+                self.pos += 1;
+                Ok((*b - b'0') as usize)
+            }
+            Some(_) => Err("ожидалось значение пикселя 0 или 1".to_string()),
+            None => Err(unexpected_end_msg()),
+        }
+    }
+
+    fn binary_data(&self) -> &'bytes [u8] {
+        // exactly one whitespace separates the header from the data
+        self.bytes.get(self.pos + 1..).unwrap_or(&[])
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while self.pos < self.bytes.len() {
+            match self.bytes[self.pos] {
+                b'#' => {
+                    while self.pos < self.bytes.len() && self.bytes[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+}
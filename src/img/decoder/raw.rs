@@ -0,0 +1,142 @@
+use super::{checked_len, invalid_size_msg};
+use crate::img::{
+    filter::filter_option::ImgChannel, BitDepth, ColorSpace, Img, ImgLayer, Matrix2D,
+};
+use fltk::enums::ColorDepth;
+
+// Own lossless format for cached results: the values are stored as they are,
+// so a reloaded image is processed exactly like the original one.
+//
+//   magic "IPRAW", format version: u8,
+//   color depth: u8 (layers count), bit depth: u8, color space: u8 (since version 2),
+//   width: u32, height: u32,
+//   values of every layer one after another: f64, all numbers are little endian
+
+pub const MAGIC: &[u8] = b"IPRAW";
+const VERSION: u8 = 2;
+const HEADER_LEN_V1: usize = 5 + 1 + 1 + 1 + 4 + 4;
+const HEADER_LEN: usize = HEADER_LEN_V1 + 1;
+
+pub fn encode(img: &Img) -> Vec<u8> {
+    let mut bytes = Vec::<u8>::with_capacity(HEADER_LEN + img.w() * img.h() * img.d() * 8);
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.push(img.color_depth() as u8);
+    bytes.push(bit_depth_code(img.bit_depth()));
+    bytes.push(color_space_code(img.color_space()));
+    bytes.extend_from_slice(&(img.w() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(img.h() as u32).to_le_bytes());
+
+    for layer in img.layers() {
+        for val in layer.matrix().vals() {
+            bytes.extend_from_slice(&val.to_le_bytes());
+        }
+    }
+
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Img, String> {
+    if bytes.len() < HEADER_LEN_V1 {
+        return Err(unexpected_end_msg());
+    }
+
+    let version = bytes[5];
+    let header_len = match version {
+        1 => HEADER_LEN_V1,
+        VERSION => HEADER_LEN,
+        _ => return Err(format!("версия {} не поддерживается", version)),
+    };
+    if bytes.len() < header_len {
+        return Err(unexpected_end_msg());
+    }
+
+    let color_depth = match bytes[6] {
+        1 => ColorDepth::L8,
+        2 => ColorDepth::La8,
+        3 => ColorDepth::Rgb8,
+        4 => ColorDepth::Rgba8,
+        other => return Err(format!("неверное число каналов {}", other)),
+    };
+
+    let bit_depth = match bytes[7] {
+        0 => BitDepth::U8,
+        1 => BitDepth::U16,
+        2 => BitDepth::F32,
+        other => return Err(format!("неверная глубина цвета {}", other)),
+    };
+
+    // the first version had only RGB images
+    let color_space = match version {
+        1 => ColorSpace::Rgb,
+        _ => match bytes[8] {
+            0 => ColorSpace::Rgb,
+            1 => ColorSpace::Hsv,
+            2 => ColorSpace::Hsl,
+            3 => ColorSpace::YCbCr,
+            4 => ColorSpace::Lab,
+            other => return Err(format!("неверное цветовое пространство {}", other)),
+        },
+    };
+
+    let read_u32 = |pos: usize| -> usize {
+        u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]) as usize
+    };
+    let w = read_u32(header_len - 8);
+    let h = read_u32(header_len - 4);
+
+    if w == 0 || h == 0 {
+        return Err(invalid_size_msg(w, h));
+    }
+
+    let data = &bytes[header_len..];
+    if data.len() / 8 < checked_len(w, h, color_depth as u8 as usize)? {
+        return Err(unexpected_end_msg());
+    }
+
+    let mut vals_iter = data
+        .chunks_exact(8)
+        .map(|v| f64::from_le_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]));
+
+    let mut channels: Vec<ImgChannel> = match color_depth {
+        ColorDepth::L8 | ColorDepth::La8 => vec![ImgChannel::L],
+        ColorDepth::Rgb8 | ColorDepth::Rgba8 => color_space.channels().to_vec(),
+    };
+    if let ColorDepth::La8 | ColorDepth::Rgba8 = color_depth {
+        channels.push(ImgChannel::A);
+    }
+
+    let mut layers = Vec::<ImgLayer>::new();
+    for channel in channels {
+        let mut mat = Matrix2D::empty_with_size(w, h);
+        for (val, read_val) in mat.vals_mut().iter_mut().zip(vals_iter.by_ref()) {
+            *val = read_val;
+        }
+        layers.push(ImgLayer::new(mat, channel));
+    }
+
+    Ok(Img::from_layers_of_depth(layers, color_depth, bit_depth))
+}
+
+fn bit_depth_code(bit_depth: BitDepth) -> u8 {
+    match bit_depth {
+        BitDepth::U8 => 0,
+        BitDepth::U16 => 1,
+        BitDepth::F32 => 2,
+    }
+}
+
+fn color_space_code(color_space: ColorSpace) -> u8 {
+    match color_space {
+        ColorSpace::Rgb => 0,
+        ColorSpace::Hsv => 1,
+        ColorSpace::Hsl => 2,
+        ColorSpace::YCbCr => 3,
+        ColorSpace::Lab => 4,
+    }
+}
+
+fn unexpected_end_msg() -> String {
+    "неожиданный конец файла".to_string()
+}
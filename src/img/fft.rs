@@ -0,0 +1,495 @@
+use super::*;
+use crate::processing::{ExecutorHandle, TaskStop};
+use rayon::prelude::*;
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    // e^(i * angle)
+    pub fn from_angle(angle: f64) -> Self {
+        Complex::new(angle.cos(), angle.sin())
+    }
+
+    pub fn conj(self) -> Self {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn scaled(self, k: f64) -> Self {
+        Complex::new(self.re * k, self.im * k)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+// transform of a fixed length: radix-2 for the powers of two, Bluestein's algorithm
+// (the transform as a convolution with a chirp of a power-of-two length) for the rest
+enum FftPlan {
+    Radix2 {
+        twiddles: Vec<Complex>,
+    },
+    Bluestein {
+        chirp: Vec<Complex>,
+        chirp_spectrum: Vec<Complex>,
+        inner: Box<FftPlan>,
+    },
+}
+
+impl FftPlan {
+    fn new(len: usize) -> Self {
+        assert!(len > 0);
+
+        if len.is_power_of_two() {
+            let twiddles = (0..len / 2)
+                .map(|k| Complex::from_angle(-2_f64 * PI * k as f64 / len as f64))
+                .collect();
+            return FftPlan::Radix2 { twiddles };
+        }
+
+        let inner_len = (2 * len - 1).next_power_of_two();
+        let inner = FftPlan::new(inner_len);
+
+        // k^2 is taken modulo 2 * len to keep the angle precise for large k
+        let chirp: Vec<Complex> = (0..len)
+            .map(|k| {
+                let k_sqr = (k * k) % (2 * len);
+                Complex::from_angle(-PI * k_sqr as f64 / len as f64)
+            })
+            .collect();
+
+        let mut chirp_spectrum = vec![Complex::default(); inner_len];
+        chirp_spectrum[0] = chirp[0].conj();
+        for k in 1..len {
+            chirp_spectrum[k] = chirp[k].conj();
+            chirp_spectrum[inner_len - k] = chirp[k].conj();
+        }
+        inner.forward(&mut chirp_spectrum);
+
+        FftPlan::Bluestein {
+            chirp,
+            chirp_spectrum,
+            inner: Box::new(inner),
+        }
+    }
+
+    fn forward(&self, buf: &mut [Complex]) {
+        match self {
+            FftPlan::Radix2 { twiddles } => radix2(buf, twiddles),
+            FftPlan::Bluestein {
+                chirp,
+                chirp_spectrum,
+                inner,
+            } => {
+                let mut inner_buf = vec![Complex::default(); chirp_spectrum.len()];
+                for (k, val) in buf.iter().enumerate() {
+                    inner_buf[k] = *val * chirp[k];
+                }
+
+                inner.forward(&mut inner_buf);
+                for (val, chirp_val) in inner_buf.iter_mut().zip(chirp_spectrum.iter()) {
+                    *val = *val * *chirp_val;
+                }
+                inner.inverse(&mut inner_buf);
+
+                for (k, val) in buf.iter_mut().enumerate() {
+                    *val = inner_buf[k] * chirp[k];
+                }
+            }
+        }
+    }
+
+    // the inverse transform through the forward one of the conjugated values
+    fn inverse(&self, buf: &mut [Complex]) {
+        for val in buf.iter_mut() {
+            *val = val.conj();
+        }
+
+        self.forward(buf);
+
+        let scale = 1_f64 / buf.len() as f64;
+        for val in buf.iter_mut() {
+            *val = val.conj().scaled(scale);
+        }
+    }
+}
+
+fn radix2(buf: &mut [Complex], twiddles: &[Complex]) {
+    let len = buf.len();
+
+    let mut rev = 0_usize;
+    for ind in 1..len {
+        let mut bit = len >> 1;
+        while rev & bit != 0 {
+            rev ^= bit;
+            bit >>= 1;
+        }
+        rev |= bit;
+
+        if ind < rev {
+            buf.swap(ind, rev);
+        }
+    }
+
+    let mut size = 2_usize;
+    while size <= len {
+        let half = size / 2;
+        let twiddle_step = len / size;
+
+        for start in (0..len).step_by(size) {
+            for k in 0..half {
+                let odd = buf[start + k + half] * twiddles[k * twiddle_step];
+                let even = buf[start + k];
+                buf[start + k] = even + odd;
+                buf[start + k + half] = even - odd;
+            }
+        }
+
+        size *= 2;
+    }
+}
+
+// completes an action for each row
+fn transform_rows(
+    vals: &mut [Complex],
+    row_len: usize,
+    inverse: bool,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<(), TaskStop> {
+    let plan = FftPlan::new(row_len);
+
+    let handle: &ExecutorHandle = executor_handle;
+
+    vals.par_chunks_mut(row_len).try_for_each_init(
+        || handle.share(),
+        |worker_handle, row| {
+            if inverse {
+                plan.inverse(row);
+            } else {
+                plan.forward(row);
+            }
+            worker_handle.complete_action()
+        },
+    )
+}
+
+fn transposed(vals: &[Complex], width: usize, height: usize) -> Vec<Complex> {
+    let mut res = vec![Complex::default(); vals.len()];
+    for row in 0..height {
+        for col in 0..width {
+            res[col * height + row] = vals[row * width + col];
+        }
+    }
+    res
+}
+
+// 2D discrete Fourier transform of a matrix, the zero frequency is at (0, 0)
+#[derive(Clone)]
+pub struct Spectrum {
+    width: usize,
+    height: usize,
+    vals: Vec<Complex>,
+}
+
+impl Spectrum {
+    pub fn of(mat: &Matrix2D, executor_handle: &mut ExecutorHandle) -> Result<Self, TaskStop> {
+        Spectrum::of_padded(mat, mat.w(), mat.h(), executor_handle)
+    }
+
+    // the matrix is padded with zeros at the right and the bottom
+    pub fn of_padded(
+        mat: &Matrix2D,
+        width: usize,
+        height: usize,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Self, TaskStop> {
+        assert!(width >= mat.w() && height >= mat.h());
+
+        let mut vals = vec![Complex::default(); width * height];
+        for pos in mat.area().iter_pixels() {
+            vals[pos.row * width + pos.col] = Complex::new(mat[pos], 0_f64);
+        }
+
+        let mut spectrum = Spectrum {
+            width,
+            height,
+            vals,
+        };
+        spectrum.transform(false, executor_handle)?;
+
+        Ok(spectrum)
+    }
+
+    // real part of the inverse transform
+    pub fn to_matrix(&self, executor_handle: &mut ExecutorHandle) -> Result<Matrix2D, TaskStop> {
+        let mut copy = self.clone();
+        copy.transform(true, executor_handle)?;
+
+        let mut mat = Matrix2D::empty_with_size(self.width, self.height);
+        for (val, complex) in mat.vals_mut().iter_mut().zip(copy.vals.iter()) {
+            *val = complex.re;
+        }
+
+        Ok(mat)
+    }
+
+    // count of actions completed by a transform of a matrix of the size
+    pub fn actions_num(width: usize, height: usize) -> usize {
+        width + height
+    }
+
+    pub fn w(&self) -> usize {
+        self.width
+    }
+
+    pub fn h(&self) -> usize {
+        self.height
+    }
+
+    // distance from the zero frequency in cycles per pixel, it is in [0; 0.5 * sqrt(2)]
+    pub fn frequency_at(&self, pos: PixelPos) -> f64 {
+        let freq_row = pos.row.min(self.height - pos.row) as f64 / self.height as f64;
+        let freq_col = pos.col.min(self.width - pos.col) as f64 / self.width as f64;
+        freq_row.hypot(freq_col)
+    }
+
+    // multiplies every value by the gain for its frequency
+    pub fn apply_gain<G: Fn(f64) -> f64>(&mut self, gain: G) {
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let freq = self.frequency_at(PixelPos::new(row, col));
+                let ind = row * self.width + col;
+                self.vals[ind] = self.vals[ind].scaled(gain(freq));
+            }
+        }
+    }
+
+    pub fn multiply_by(&mut self, other: &Spectrum) {
+        assert_eq!((self.width, self.height), (other.width, other.height));
+
+        for (val, other_val) in self.vals.iter_mut().zip(other.vals.iter()) {
+            *val = *val * *other_val;
+        }
+    }
+
+    // ln(1 + |F|) with the zero frequency moved to the center
+    pub fn log_magnitude_centered(&self) -> Matrix2D {
+        let mut mat = Matrix2D::empty_with_size(self.width, self.height);
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let pos = PixelPos::new(
+                    (row + self.height / 2) % self.height,
+                    (col + self.width / 2) % self.width,
+                );
+                mat[pos] = self.vals[row * self.width + col].abs().ln_1p();
+            }
+        }
+
+        mat
+    }
+
+    // completes Spectrum::actions_num(w, h) actions
+    fn transform(
+        &mut self,
+        inverse: bool,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<(), TaskStop> {
+        transform_rows(&mut self.vals, self.width, inverse, executor_handle)?;
+
+        let mut cols = transposed(&self.vals, self.width, self.height);
+        transform_rows(&mut cols, self.height, inverse, executor_handle)?;
+        self.vals = transposed(&cols, self.height, self.width);
+
+        Ok(())
+    }
+}
+
+// the same result as the window filter with the 'kernel' coefficients gives:
+// res[pos] = sum of ext[pos + pos_w] * kernel[pos_w] over the window positions
+pub fn correlate_fft(
+    mat: &Matrix2D,
+    kernel: &Matrix2D,
+    extend_value: ExtendValue,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<Matrix2D, TaskStop> {
+    let left = kernel.w() / 2;
+    let top = kernel.h() / 2;
+    let mat_ext = mat.extended(extend_value, left, top, left, top);
+
+    let (width, height) = correlation_padded_size(mat.w(), mat.h(), kernel.w(), kernel.h());
+
+    // the correlation is the convolution with the flipped kernel
+    let mut kernel_flipped = Matrix2D::empty_size_of(kernel);
+    for pos in kernel.area().iter_pixels() {
+        let pos_flipped = PixelPos::new(kernel.max_row() - pos.row, kernel.max_col() - pos.col);
+        kernel_flipped[pos_flipped] = kernel[pos];
+    }
+
+    let mut spectrum = Spectrum::of_padded(&mat_ext, width, height, executor_handle)?;
+    let kernel_spectrum = Spectrum::of_padded(&kernel_flipped, width, height, executor_handle)?;
+    spectrum.multiply_by(&kernel_spectrum);
+    let convolved = spectrum.to_matrix(executor_handle)?;
+
+    // the convolution at (pos + kernel size - 1) covers the window at 'pos',
+    // the padding to the extended size is enough for the cyclic convolution not to wrap there
+    let offset = kernel.size_vec() - PixelPos::one();
+    let mut res = Matrix2D::empty_size_of(mat);
+    for pos in mat.area().iter_pixels() {
+        res[pos] = convolved[pos + offset];
+    }
+
+    Ok(res)
+}
+
+// count of actions completed by correlate_fft for a matrix and a kernel of the sizes
+pub fn correlation_actions_num(
+    width: usize,
+    height: usize,
+    kernel_w: usize,
+    kernel_h: usize,
+) -> usize {
+    let (width, height) = correlation_padded_size(width, height, kernel_w, kernel_h);
+    Spectrum::actions_num(width, height) * 3
+}
+
+fn correlation_padded_size(
+    width: usize,
+    height: usize,
+    kernel_w: usize,
+    kernel_h: usize,
+) -> (usize, usize) {
+    let width_ext = width + kernel_w / 2 * 2;
+    let height_ext = height + kernel_h / 2 * 2;
+    (
+        width_ext.next_power_of_two(),
+        height_ext.next_power_of_two(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{correlate_fft, correlation_actions_num, Complex, FftPlan, Spectrum};
+    use crate::{
+        img::{filter::filter_option::ExtendValue, Matrix2D, PixelPos},
+        processing::create_task_info_channel,
+    };
+    use std::f64::consts::PI;
+
+    fn dft(vals: &[Complex]) -> Vec<Complex> {
+        let len = vals.len();
+        (0..len)
+            .map(|freq| {
+                vals.iter()
+                    .enumerate()
+                    .fold(Complex::default(), |sum, (k, val)| {
+                        let angle = -2_f64 * PI * (freq * k) as f64 / len as f64;
+                        sum + *val * Complex::from_angle(angle)
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transform_matches_dft_for_any_length() {
+        for len in [1_usize, 2, 5, 8, 12, 17].iter() {
+            let vals: Vec<Complex> = (0..*len)
+                .map(|k| Complex::new((k as f64 * 0.7).sin() + 1_f64, k as f64 * 0.1))
+                .collect();
+
+            let plan = FftPlan::new(*len);
+            let mut buf = vals.clone();
+            plan.forward(&mut buf);
+
+            for (res, expected) in buf.iter().zip(dft(&vals).iter()) {
+                assert!((*res - *expected).abs() < 1e-9, "length {}", len);
+            }
+
+            plan.inverse(&mut buf);
+            for (res, init) in buf.iter().zip(vals.iter()) {
+                assert!((*res - *init).abs() < 1e-9, "length {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn spectrum_and_correlation() {
+        let mut mat = Matrix2D::empty_with_size(7, 5);
+        for (ind, val) in mat.vals_mut().iter_mut().enumerate() {
+            *val = ((ind * 37) % 11) as f64;
+        }
+
+        let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+
+        executor_handle.reset(Spectrum::actions_num(7, 5) * 2);
+        let spectrum = Spectrum::of(&mat, &mut executor_handle).unwrap();
+        let back = spectrum.to_matrix(&mut executor_handle).unwrap();
+        executor_handle.assert_all_actions_completed();
+        for (res, init) in back.vals().iter().zip(mat.vals().iter()) {
+            assert!((res - init).abs() < 1e-9);
+        }
+
+        let mut kernel = Matrix2D::empty_with_size(3, 4);
+        for (ind, val) in kernel.vals_mut().iter_mut().enumerate() {
+            *val = ind as f64 - 5_f64;
+        }
+
+        for extend_value in [ExtendValue::Closest, ExtendValue::Reflect101].iter() {
+            let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+            executor_handle.reset(correlation_actions_num(7, 5, 3, 4));
+            let res = correlate_fft(&mat, &kernel, *extend_value, &mut executor_handle).unwrap();
+            executor_handle.assert_all_actions_completed();
+
+            let mat_ext = mat.extended(*extend_value, 1, 2, 1, 2);
+            for pos in mat.area().iter_pixels() {
+                let expected: f64 = kernel
+                    .area()
+                    .iter_pixels()
+                    .map(|pos_w| mat_ext[pos + pos_w] * kernel[pos_w])
+                    .sum();
+                assert!((res[pos] - expected).abs() < 1e-9, "{:?}", pos);
+            }
+        }
+
+        assert!(spectrum.frequency_at(PixelPos::new(0, 0)).abs() < 1e-14);
+        assert!((spectrum.frequency_at(PixelPos::new(0, 3)) - 3_f64 / 7_f64).abs() < 1e-14);
+        assert!((spectrum.frequency_at(PixelPos::new(4, 0)) - 1_f64 / 5_f64).abs() < 1e-14);
+    }
+}
@@ -0,0 +1,353 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::*;
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// combines the processed image with the initial one or with the result of an earlier step,
+// a gray second image is applied to every channel
+#[derive(Clone)]
+pub struct Combine {
+    op: CombineOp,
+    with: StepRef,
+    mapping: ValueMapping,
+}
+
+impl Combine {
+    pub fn new(op: CombineOp, with: StepRef, mapping: ValueMapping) -> Self {
+        Combine { op, with, mapping }
+    }
+
+    pub fn with_op(op: CombineOp) -> Self {
+        Combine {
+            op,
+            ..Combine::default()
+        }
+    }
+
+    fn other_layer<'img>(
+        &self,
+        other: &'img Img,
+        channel: ImgChannel,
+    ) -> Result<&'img ImgLayer, MyError> {
+        other
+            .layer_by_channel(channel)
+            .or_else(|| other.layer_by_channel(ImgChannel::L))
+            .ok_or_else(|| {
+                MyError::new(format!(
+                    "У изображения, с которым идет операция, нет канала {} и канала яркости",
+                    channel
+                ))
+            })
+    }
+
+    fn combine_layers(
+        &self,
+        layer: &ImgLayer,
+        other: &ImgLayer,
+        max_value: f64,
+        other_max_value: f64,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let mut res = Matrix2D::empty_size_of(layer.matrix());
+
+        for row in 0..res.h() {
+            for col in 0..res.w() {
+                let pos = PixelPos::new(row, col);
+                let val = self
+                    .op
+                    .apply(layer[pos] / max_value, other[pos] / other_max_value);
+                res[pos] = val * max_value;
+            }
+
+            executor_handle.complete_action()?;
+        }
+
+        let (from, to) = self.op.possible_range();
+        self.mapping
+            .map_values(&mut res, (from * max_value, to * max_value), max_value);
+
+        Ok(ImgLayer::new(res, layer.channel()))
+    }
+}
+
+impl Filter for Combine {
+    fn process(&self, _img: &Img, _executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        Err(MyError::new(format!(
+            "Для операции нужно изображение, с которым она выполняется ({})",
+            self.with.get_description()
+        ))
+        .into())
+    }
+
+    fn process_with_inputs(
+        &self,
+        img: &Img,
+        inputs: &[&Img],
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Img, TaskStop> {
+        assert_eq!(inputs.len(), 1);
+        let other = inputs[0];
+
+        if other.w() != img.w() || other.h() != img.h() {
+            return Err(MyError::new(format!(
+                "Размер изображения, с которым идет операция ({}x{}), не совпадает с размером обрабатываемого ({}x{})",
+                other.h(),
+                other.w(),
+                img.h(),
+                img.w()
+            ))
+            .into());
+        }
+
+        let max_value = img.bit_depth().max_value();
+        let other_max_value = other.bit_depth().max_value();
+
+        let mut res_layers = Vec::<ImgLayer>::with_capacity(img.d());
+        for layer in img.layers() {
+            let res_layer = match layer.channel() {
+                ImgChannel::A => layer.clone(),
+                channel => self.combine_layers(
+                    layer,
+                    self.other_layer(other, channel)?,
+                    max_value,
+                    other_max_value,
+                    executor_handle,
+                )?,
+            };
+            res_layers.push(res_layer);
+        }
+
+        Ok(Img::from_layers_of_depth(
+            res_layers,
+            img.color_depth(),
+            img.bit_depth(),
+        ))
+    }
+
+    fn get_inputs(&self) -> Vec<StepRef> {
+        vec![self.with]
+    }
+
+    fn set_inputs(&mut self, inputs: Vec<StepRef>) {
+        assert_eq!(inputs.len(), 1);
+        self.with = inputs[0];
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let rows_per_layer = img.h();
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        layers_count * rows_per_layer
+    }
+
+    fn get_description(&self) -> String {
+        let op_descr = match self.op {
+            CombineOp::Blend { alpha } => format!("{} ({})", self.op.get_description(), alpha),
+            _ => self.op.get_description().to_string(),
+        };
+        format!(
+            "{} с {}, {}",
+            op_descr,
+            self.with.get_description(),
+            self.mapping.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "Combine".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for Combine {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 3 {
+            return Err(MyError::new(
+                "Нужно ввести операцию, второе изображение и отображение значений на отдельных строках".to_string(),
+            ));
+        }
+
+        let op = CombineOp::try_from_string(lines_iter.next_or_empty())?;
+        let with = StepRef::try_from_string(lines_iter.next_or_empty())?;
+        let mapping = ValueMapping::try_from_string(lines_iter.next_or_empty())?;
+
+        *self = Combine::new(op, with, mapping);
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}",
+            self.op.content_to_string(),
+            self.with.content_to_string(),
+            self.mapping.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for Combine {
+    fn default() -> Self {
+        Combine::new(CombineOp::Add, StepRef::Initial, ValueMapping::Clamped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        img::filter::test_utils::{create_gradient_img, create_img},
+        processing::{create_task_info_channel, TaskStop},
+    };
+
+    // the other image is passed as the one the step takes
+    fn process(filter: &Combine, img: &Img, other: &Img) -> Result<Img, TaskStop> {
+        let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+        executor_handle.reset(filter.get_steps_num(img));
+        let res = filter.process_with_inputs(img, &[other], &mut executor_handle);
+        if res.is_ok() {
+            executor_handle.assert_all_actions_completed();
+        }
+        res
+    }
+
+    #[test]
+    fn combine_applies_op_with_other_image() {
+        // the values 0, 50, 100, 150, 200, 250 in the columns
+        let img = create_gradient_img(6, 2, ColorDepth::L8, 0, 50);
+        let other = create_img(6, 2, ColorDepth::L8, |_, _| 102_f64);
+
+        let mut combine = Combine::default();
+        for (params, expected) in [
+            (
+                "Operation: add\nMapping: clamped",
+                [102_f64, 152_f64, 202_f64, 252_f64, 255_f64, 255_f64],
+            ),
+            (
+                "Operation: subtract\nMapping: linear",
+                [76.5, 101.5, 126.5, 151.5, 176.5, 201.5],
+            ),
+            (
+                "Operation: difference\nMapping: clamped",
+                [102_f64, 52_f64, 2_f64, 48_f64, 98_f64, 148_f64],
+            ),
+            (
+                "Operation: min\nMapping: clamped",
+                [0_f64, 50_f64, 100_f64, 102_f64, 102_f64, 102_f64],
+            ),
+            (
+                "Operation: blend 0.5\nMapping: clamped",
+                [51_f64, 76_f64, 101_f64, 126_f64, 151_f64, 176_f64],
+            ),
+            (
+                "Operation: multiply\nMapping: clamped",
+                [0_f64, 20_f64, 40_f64, 60_f64, 80_f64, 100_f64],
+            ),
+        ]
+        .iter()
+        {
+            let params = params.replace("\nMapping", "\nWith: step 2\nMapping");
+            combine.try_set_from_string(&params).unwrap();
+
+            let res = process(&combine, &img, &other).unwrap();
+            for pos in res.get_area().iter_pixels() {
+                assert!(
+                    (res.layer(0)[pos] - expected[pos.col]).abs() < 1e-9,
+                    "{} {:?}",
+                    params,
+                    pos
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mask_keeps_pixels_where_other_image_is_not_0() {
+        let img = create_gradient_img(6, 2, ColorDepth::L8, 0, 50);
+        let other = create_img(6, 2, ColorDepth::L8, |_, pos| match pos.col {
+            0..=2 => 0_f64,
+            _ => 102_f64,
+        });
+
+        let mut combine = Combine::default();
+        for (op, expected) in [
+            ("mask", [0_f64, 0_f64, 0_f64, 150_f64, 200_f64, 250_f64]),
+            ("multiply", [0_f64, 0_f64, 0_f64, 60_f64, 80_f64, 100_f64]),
+        ]
+        .iter()
+        {
+            let params = format!("Operation: {}\nWith: initial\nMapping: clamped", op);
+            combine.try_set_from_string(&params).unwrap();
+
+            let res = process(&combine, &img, &other).unwrap();
+            for pos in res.get_area().iter_pixels() {
+                assert!(
+                    (res.layer(0)[pos] - expected[pos.col]).abs() < 1e-9,
+                    "{}",
+                    op
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gray_image_is_combined_with_every_channel() {
+        let img_rgba = create_img(6, 2, ColorDepth::Rgba8, |ch, _| match ch {
+            3 => 30_f64,
+            _ => 0_f64,
+        });
+        let other = create_img(6, 2, ColorDepth::L8, |_, _| 102_f64);
+
+        // A is left as it is
+        let mut combine = Combine::default();
+        combine
+            .try_set_from_string("Operation: max\nWith: initial\nMapping: clamped")
+            .unwrap();
+        let res = process(&combine, &img_rgba, &other).unwrap();
+        for layer_num in 0..3 {
+            assert!(res
+                .layer(layer_num)
+                .matrix()
+                .vals()
+                .iter()
+                .all(|v| *v == 102_f64));
+        }
+        assert!(res.layer(3).matrix().vals().iter().all(|v| *v == 30_f64));
+
+        assert!(process(&combine, &other, &img_rgba).is_err());
+        let narrow = Img::empty_with_size(5, 2, ColorDepth::L8);
+        assert!(process(&combine, &other, &narrow).is_err());
+
+        let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+        assert!(combine.process(&other, &mut executor_handle).is_err());
+        assert_eq!(combine.get_inputs(), vec![StepRef::Initial]);
+    }
+
+    #[test]
+    fn wrong_combine_params_are_rejected() {
+        let mut combine = Combine::default();
+        for params in [
+            "Operation: blend 2\nWith: initial\nMapping: clamped",
+            "Operation: add\nWith: step 0\nMapping: clamped",
+            "Operation: add\nWith: previous\nMapping: clamped",
+        ]
+        .iter()
+        {
+            assert!(combine.try_set_from_string(params).is_err(), "{}", params);
+        }
+    }
+}
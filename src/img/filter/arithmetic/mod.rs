@@ -0,0 +1,3 @@
+mod combine;
+
+pub use combine::Combine;
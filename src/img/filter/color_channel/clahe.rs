@@ -0,0 +1,269 @@
+use super::super::{process_each_layer, ByLayer};
+use super::img::*;
+use super::traits::*;
+use super::utils::*;
+use crate::img::filter::FilterBase;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// contrast-limited adaptive histogram equalization: every tile of the grid is equalized
+// with a clipped histogram, the mappings of 4 nearest tiles are interpolated for each pixel
+#[derive(Clone)]
+pub struct Clahe {
+    grid: FilterWindowSize,
+    clip_limit: ClipLimit,
+    bit_depth: BitDepth,
+}
+
+impl Clahe {
+    pub fn new(grid: FilterWindowSize, clip_limit: ClipLimit) -> Self {
+        Clahe {
+            grid,
+            clip_limit,
+            bit_depth: BitDepth::default(),
+        }
+    }
+
+    // a tile has at least 1 pixel
+    fn tiles_count(&self, w: usize, h: usize) -> (usize, usize) {
+        (self.grid.height.min(h), self.grid.width.min(w))
+    }
+
+    // turns the tile histogram into the mapping from a level to the equalized value
+    fn make_mapping(&self, hist: &mut HistBuf, pixels_count: usize) {
+        let levels_count = hist.len() as f64;
+        let limit = (self.clip_limit.value * pixels_count as f64 / levels_count).max(1_f64);
+
+        let mut excess = 0_f64;
+        for bin in hist.iter_mut() {
+            if *bin > limit {
+                excess += *bin - limit;
+                *bin = limit;
+            }
+        }
+
+        let bin_addition = excess / levels_count;
+        let scale = self.bit_depth.max_value() / pixels_count as f64;
+
+        let mut sum = 0_f64;
+        for bin in hist.iter_mut() {
+            sum += *bin + bin_addition;
+            *bin = sum * scale;
+        }
+    }
+}
+
+// start and end of the tile number `num` of `count` tiles covering `len` pixels
+fn tile_bounds(num: usize, count: usize, len: usize) -> (usize, usize) {
+    (num * len / count, (num + 1) * len / count)
+}
+
+// 2 nearest tiles and the weight of the second one for the pixel at `pos` of `len`
+fn nearest_tiles(pos: usize, count: usize, len: usize) -> (usize, usize, f64) {
+    let coord = (pos as f64 + 0.5) * count as f64 / len as f64 - 0.5;
+    if coord <= 0_f64 {
+        return (0, 0, 0_f64);
+    }
+
+    let first = coord.floor() as usize;
+    if first >= count - 1 {
+        (count - 1, count - 1, 0_f64)
+    } else {
+        (first, first + 1, coord - first as f64)
+    }
+}
+
+impl Filter for Clahe {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = Clahe {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let (tile_rows, _) = self.tiles_count(img.w(), img.h());
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        // mappings and then pixels of each tile row
+        layers_count * tile_rows * 2
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "CLAHE ({}x{}, ограничение {})",
+            self.grid.height, self.grid.width, self.clip_limit.value
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "Clahe".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for Clahe {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let mat = match layer.channel() {
+            ImgChannel::A => return Ok(layer.clone()),
+            _ => layer.matrix(),
+        };
+
+        let (tile_rows, tile_cols) = self.tiles_count(mat.w(), mat.h());
+
+        let mut mappings = Vec::<HistBuf>::with_capacity(tile_rows * tile_cols);
+        for tile_row in 0..tile_rows {
+            let (top, bottom) = tile_bounds(tile_row, tile_rows, mat.h());
+
+            for tile_col in 0..tile_cols {
+                let (left, right) = tile_bounds(tile_col, tile_cols, mat.w());
+                let area = PixelsArea::new(
+                    PixelPos::new(top, left),
+                    PixelPos::new(bottom - 1, right - 1),
+                );
+
+                let mut hist = HistBuf::new();
+                count_area_histogram(mat, area, self.bit_depth, &mut hist);
+                self.make_mapping(&mut hist, area.w() * area.h());
+                mappings.push(hist);
+            }
+
+            executor_handle.complete_action()?;
+        }
+
+        let cols_tiles: Vec<(usize, usize, f64)> = (0..mat.w())
+            .map(|col| nearest_tiles(col, tile_cols, mat.w()))
+            .collect();
+
+        let mut res = Matrix2D::empty_size_of(mat);
+
+        for tile_row in 0..tile_rows {
+            let (top, bottom) = tile_bounds(tile_row, tile_rows, mat.h());
+
+            for row in top..bottom {
+                let (row_0, row_1, w_row) = nearest_tiles(row, tile_rows, mat.h());
+
+                for (col, (col_0, col_1, w_col)) in cols_tiles.iter().enumerate() {
+                    let pos = PixelPos::new(row, col);
+                    let level = self.bit_depth.level_of(mat[pos]);
+                    let mapped = |tile_row: usize, tile_col: usize| {
+                        mappings[tile_row * tile_cols + tile_col][level]
+                    };
+
+                    let top_val =
+                        mapped(row_0, *col_0) * (1_f64 - w_col) + mapped(row_0, *col_1) * w_col;
+                    let bottom_val =
+                        mapped(row_1, *col_0) * (1_f64 - w_col) + mapped(row_1, *col_1) * w_col;
+                    res[pos] = top_val * (1_f64 - w_row) + bottom_val * w_row;
+                }
+            }
+
+            executor_handle.complete_action()?;
+        }
+
+        Ok(ImgLayer::new(res, layer.channel()))
+    }
+}
+
+impl StringFromTo for Clahe {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 2 {
+            return Err(MyError::new(
+                "Нужно ввести размер сетки фрагментов и ограничение контраста на следующей строке"
+                    .to_string(),
+            ));
+        }
+
+        let grid = FilterWindowSize::try_from_string(lines_iter.next_or_empty())?;
+        if grid.width == 0 || grid.height == 0 {
+            return Err(MyError::new(
+                "Размеры сетки фрагментов должны быть > 0".to_string(),
+            ));
+        }
+
+        let clip_limit = ClipLimit::try_from_string(lines_iter.next_or_empty())?;
+
+        self.grid = grid;
+        self.clip_limit = clip_limit;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}",
+            self.grid.content_to_string(),
+            self.clip_limit.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for Clahe {
+    fn default() -> Self {
+        Clahe::new(FilterWindowSize::new(8, 8), ClipLimit::new(2_f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_img, run_checked};
+
+    #[test]
+    fn clahe_stretches_local_contrast() {
+        // a dim and a bright half, each with a faint pattern
+        let img = create_img(64, 48, ColorDepth::La8, |layer_num, pos| match layer_num {
+            0 => {
+                let base = if pos.col < 32 { 40 } else { 200 };
+                (base + (pos.row + pos.col) % 8) as f64
+            }
+            _ => 123_f64,
+        });
+
+        let mut filter = Clahe::default();
+        filter.try_set_from_string("4 x 4\nClip limit: 3").unwrap();
+
+        let res = run_checked(&filter, &img);
+
+        let range_of = |img: &Img, cols: std::ops::Range<usize>| {
+            let vals: Vec<f64> = img
+                .get_area()
+                .iter_pixels()
+                .filter(|pos| cols.contains(&pos.col))
+                .map(|pos| img.layer(0)[pos])
+                .collect();
+            let min = vals.iter().cloned().fold(f64::MAX, f64::min);
+            let max = vals.iter().cloned().fold(f64::MIN, f64::max);
+            max - min
+        };
+        assert!(range_of(&res, 0..24) > range_of(&img, 0..24) * 3_f64);
+        assert!(range_of(&res, 40..64) > range_of(&img, 40..64) * 3_f64);
+
+        assert!(res
+            .layer(1)
+            .matrix()
+            .vals()
+            .iter()
+            .all(|val| *val == 123_f64));
+
+        assert!(filter.try_set_from_string("0 x 4\nClip limit: 3").is_err());
+        assert!(filter.try_set_from_string("4 x 4\nClip limit: 0").is_err());
+    }
+}
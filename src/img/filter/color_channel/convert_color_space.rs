@@ -0,0 +1,135 @@
+use super::img::*;
+use super::traits::*;
+use crate::img::filter::FilterBase;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// gray images are left as they are
+#[derive(Clone)]
+pub struct ConvertColorSpace {
+    color_space: ColorSpace,
+}
+
+impl ConvertColorSpace {
+    pub fn new(color_space: ColorSpace) -> Self {
+        ConvertColorSpace { color_space }
+    }
+
+    fn changes(&self, img: &Img) -> bool {
+        let is_gray = matches!(img.color_depth(), ColorDepth::L8 | ColorDepth::La8);
+        !is_gray && img.color_space() != self.color_space
+    }
+}
+
+impl Filter for ConvertColorSpace {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        if self.changes(img) {
+            img.try_convert_color_space(self.color_space, executor_handle)
+        } else {
+            let res = img.clone();
+            executor_handle.complete_action()?;
+            Ok(res)
+        }
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        if self.changes(img) {
+            img.h()
+        } else {
+            1
+        }
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Цветовое пространство => {}",
+            self.color_space.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "ConvertColorSpace".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for ConvertColorSpace {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 1 {
+            return Err(MyError::new("Должна быть 1 строка".to_string()));
+        }
+
+        self.color_space = ColorSpace::try_from_string(lines_iter.next_or_empty())?;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        Some(self.color_space.content_to_string())
+    }
+}
+
+impl Default for ConvertColorSpace {
+    fn default() -> Self {
+        ConvertColorSpace::new(ColorSpace::Hsv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::{
+        color_channel::Rgb2Gray,
+        test_utils::{create_img, run_checked},
+    };
+
+    #[test]
+    fn color_space_conversions_go_back_to_rgb() {
+        let img = create_img(16, 8, ColorDepth::Rgba8, |ch_num, pos| {
+            ((pos.row * 37 + pos.col * 11 + ch_num * 60) % 256) as f64
+        });
+
+        let gray = run_checked(&Rgb2Gray::default(), &img);
+
+        let color_spaces = [
+            ColorSpace::Hsv,
+            ColorSpace::Hsl,
+            ColorSpace::YCbCr,
+            ColorSpace::Lab,
+        ];
+        for color_space in color_spaces.iter() {
+            let converted = run_checked(&ConvertColorSpace::new(*color_space), &img);
+            assert_eq!(converted.color_space(), *color_space);
+            assert!(converted.layer_by_channel(ImgChannel::R).is_none());
+            assert_eq!(
+                converted
+                    .layer_by_channel(ImgChannel::A)
+                    .unwrap()
+                    .matrix()
+                    .vals(),
+                img.layer(3).matrix().vals()
+            );
+
+            let back = run_checked(&ConvertColorSpace::new(ColorSpace::Rgb), &converted);
+            assert_eq!(back.color_space(), ColorSpace::Rgb);
+            for (init, res) in img.layers().iter().zip(back.layers().iter()) {
+                assert_eq!(init.channel(), res.channel());
+                for (init, res) in init.matrix().vals().iter().zip(res.matrix().vals()) {
+                    assert!((init - res).abs() < 1e-3, "{:?}", color_space);
+                }
+            }
+
+            let converted_gray = run_checked(&Rgb2Gray::default(), &converted);
+            let vals = converted_gray.layer(0).matrix().vals();
+            for (init, res) in gray.layer(0).matrix().vals().iter().zip(vals) {
+                assert!((init - res).abs() < 1e-3, "{:?}", color_space);
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::super::super::Img;
+use super::super::super::{BitDepth, Img};
 use super::super::FilterBase;
 use super::super::{process_each_layer, *};
 use super::options::*;
@@ -12,6 +12,7 @@ use fltk::enums::ColorDepth;
 pub struct CutBrightness {
     cut_range: CutBrightnessRange,
     replace_with: ValueRepaceWith,
+    bit_depth: BitDepth,
     name: String,
 }
 
@@ -20,6 +21,7 @@ impl CutBrightness {
         CutBrightness {
             cut_range,
             replace_with,
+            bit_depth: BitDepth::U8,
             name: "Вырезание яркости".to_string(),
         }
     }
@@ -27,7 +29,11 @@ impl CutBrightness {
 
 impl Filter for CutBrightness {
     fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
-        process_each_layer(img, self, executor_handle)
+        let filter = CutBrightness {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
     }
 
     fn get_steps_num(&self, img: &Img) -> usize {
@@ -109,15 +115,24 @@ impl ByLayer for CutBrightness {
 
         let mat = layer.matrix();
 
+        // the range is set in 8 bit brightness for any bit depth
+        let replace_with_val: f64 =
+            BitDepth::U8.convert_value(self.replace_with.value as f64, self.bit_depth);
+
         for pos in mat.area().iter_pixels() {
-            let pix_val = mat[pos] as u8;
-            let before_min = pix_val < self.cut_range.min;
-            let after_max = pix_val > self.cut_range.max;
+            let brightness = self.bit_depth.to_u8(mat[pos]);
+            let before_min = brightness < self.cut_range.min;
+            let after_max = brightness > self.cut_range.max;
+
+            let pix_val = match self.bit_depth {
+                BitDepth::U8 => brightness as f64,
+                BitDepth::U16 | BitDepth::F32 => mat[pos],
+            };
 
-            let result = pix_val * (!before_min) as u8 * (!after_max) as u8
-                + self.replace_with.value * before_min as u8 * after_max as u8;
+            let result = pix_val * (!before_min) as u8 as f64 * (!after_max) as u8 as f64
+                + replace_with_val * before_min as u8 as f64 * after_max as u8 as f64;
 
-            mat_res[pos] = result as f64;
+            mat_res[pos] = result;
 
             executor_handle.complete_action()?;
         }
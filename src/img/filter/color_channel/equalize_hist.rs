@@ -10,7 +10,8 @@ pub struct EqualizeHist {}
 
 impl Filter for EqualizeHist {
     fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
-        let mut buffer: HistBuf = [0_f64; PIXEL_VALUES_COUNT];
+        let bit_depth = img.bit_depth();
+        let mut buffer = HistBuf::new();
 
         let mut img_res = img.clone();
 
@@ -20,7 +21,7 @@ impl Filter for EqualizeHist {
             }
 
             // count histogram
-            count_histogram(layer.matrix(), &mut buffer);
+            count_histogram(layer.matrix(), bit_depth, &mut buffer);
 
             // cumulate histogram
             let mut sum = 0_f64;
@@ -32,7 +33,7 @@ impl Filter for EqualizeHist {
             }
 
             // equalize
-            let max_color_over_max_value = 255_f64 / buffer.last().unwrap();
+            let max_color_over_max_value = bit_depth.max_value() / buffer.last().unwrap();
             for bin in buffer.iter_mut() {
                 *bin *= max_color_over_max_value;
 
@@ -41,8 +42,8 @@ impl Filter for EqualizeHist {
 
             // apply coeff
             for pos in layer.get_area().iter_pixels() {
-                let pix_value = layer[pos] as u8 as usize;
-                layer[pos] = buffer[pix_value];
+                let pix_level = bit_depth.level_of(layer[pos]);
+                layer[pos] = buffer[pix_level];
 
                 executor_handle.complete_action()?;
             }
@@ -60,7 +61,7 @@ impl Filter for EqualizeHist {
             ColorDepth::Rgba8 => img.d() - 1,
         };
 
-        layers_count * (PIXEL_VALUES_COUNT * 2 + pixels_per_layer)
+        layers_count * (img.bit_depth().levels_count() * 2 + pixels_per_layer)
     }
 
     fn get_description(&self) -> String {
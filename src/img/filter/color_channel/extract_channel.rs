@@ -72,3 +72,33 @@ impl Default for ExtractChannel {
         ExtractChannel::new(ImgChannel::R)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::{
+        filter::test_utils::{create_img, run_checked},
+        ColorSpace,
+    };
+    use fltk::enums::ColorDepth;
+
+    #[test]
+    fn channels_of_other_color_spaces_are_extracted() {
+        let img = create_img(4, 3, ColorDepth::Rgba8, |ch, pos| {
+            ((ch * 60 + pos.col * 20 + pos.row * 10) % 256) as f64
+        })
+        .converted_to_color_space(ColorSpace::Hsv);
+
+        let mut extract = ExtractChannel::default();
+        extract.try_set_from_string("Channel: V").unwrap();
+        let res = run_checked(&extract, &img);
+
+        for (res_layer, layer) in res.layers().iter().zip(img.layers()) {
+            let kept = matches!(layer.channel(), ImgChannel::V | ImgChannel::A);
+            for pos in layer.get_area().iter_pixels() {
+                let expected = if kept { layer[pos] } else { 0_f64 };
+                assert_eq!(res_layer[pos], expected, "{:?}", layer.channel());
+            }
+        }
+    }
+}
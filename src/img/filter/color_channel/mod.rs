@@ -1,16 +1,24 @@
+mod clahe;
+mod convert_color_space;
 mod cut_brightness;
 mod equalize_hist;
 mod extract_channel;
 mod neutralize_channel;
+mod point_transform;
 mod rgb2gray;
+mod threshold;
 
 use super::super::super::img;
 use super::filter_option as options;
 use super::filter_trait as traits;
 use super::utils;
 
+pub use clahe::Clahe;
+pub use convert_color_space::ConvertColorSpace;
 pub use cut_brightness::CutBrightness;
 pub use equalize_hist::EqualizeHist;
 pub use extract_channel::ExtractChannel;
 pub use neutralize_channel::NeutralizeChannel;
+pub use point_transform::PointTransform;
 pub use rgb2gray::Rgb2Gray;
+pub use threshold::Threshold;
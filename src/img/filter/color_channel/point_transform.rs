@@ -0,0 +1,295 @@
+use super::super::super::{BitDepth, Img};
+use super::super::FilterBase;
+use super::super::{process_each_layer, *};
+use super::options::*;
+use super::traits::*;
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// every value is mapped on its own, the values are taken relative to the max value
+#[derive(Clone)]
+enum Transform {
+    Gamma(Gamma),
+    ContrastBrightness(Contrast, Brightness),
+    Levels(InputLevels, OutputLevels),
+    Invert,
+    Curve(CurvePoints),
+}
+
+#[derive(Clone)]
+pub struct PointTransform {
+    transform: Transform,
+    bit_depth: BitDepth,
+}
+
+impl PointTransform {
+    fn new(transform: Transform) -> Self {
+        PointTransform {
+            transform,
+            bit_depth: BitDepth::default(),
+        }
+    }
+
+    pub fn gamma() -> Self {
+        Self::new(Transform::Gamma(Gamma::new(2.2)))
+    }
+
+    pub fn contrast_brightness() -> Self {
+        Self::new(Transform::ContrastBrightness(
+            Contrast::new(1.2),
+            Brightness::new(0_f64),
+        ))
+    }
+
+    pub fn levels() -> Self {
+        Self::new(Transform::Levels(
+            InputLevels::new(0.1, 0.9),
+            OutputLevels::new(0_f64, 1_f64),
+        ))
+    }
+
+    pub fn invert() -> Self {
+        Self::new(Transform::Invert)
+    }
+
+    pub fn curve() -> Self {
+        Self::new(Transform::Curve(CurvePoints::new(vec![
+            (0_f64, 0_f64),
+            (0.25, 0.2),
+            (0.75, 0.8),
+            (1_f64, 1_f64),
+        ])))
+    }
+
+    // the value and the result are in [0; 1]
+    fn map(&self, val: f64) -> f64 {
+        let res = match &self.transform {
+            Transform::Gamma(gamma) => val.max(0_f64).powf(1_f64 / gamma.value),
+            Transform::ContrastBrightness(contrast, brightness) => {
+                (val - 0.5) * contrast.value + 0.5 + brightness.value
+            }
+            Transform::Levels(input, output) => {
+                let rel = ((val - input.black) / (input.white - input.black)).clamp(0_f64, 1_f64);
+                output.low + rel * (output.high - output.low)
+            }
+            Transform::Invert => 1_f64 - val,
+            Transform::Curve(points) => points.map(val),
+        };
+
+        res.clamp(0_f64, 1_f64)
+    }
+}
+
+impl Filter for PointTransform {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = PointTransform {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let rows_per_layer = img.h();
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        layers_count * rows_per_layer
+    }
+
+    fn get_description(&self) -> String {
+        match &self.transform {
+            Transform::Gamma(gamma) => format!("Гамма-коррекция ({})", gamma.value),
+            Transform::ContrastBrightness(contrast, brightness) => format!(
+                "Контраст и яркость ({}, {})",
+                contrast.value, brightness.value
+            ),
+            Transform::Levels(input, output) => format!(
+                "Уровни ({} - {} в {} - {})",
+                input.black, input.white, output.low, output.high
+            ),
+            Transform::Invert => "Инверсия".to_string(),
+            Transform::Curve(points) => format!("Кривая ({} точек)", points.points().len()),
+        }
+    }
+
+    fn get_save_name(&self) -> String {
+        match self.transform {
+            Transform::Gamma(..) => "Gamma",
+            Transform::ContrastBrightness(..) => "ContrastBrightness",
+            Transform::Levels(..) => "Levels",
+            Transform::Invert => "Invert",
+            Transform::Curve(..) => "Curve",
+        }
+        .to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for PointTransform {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        let lines_count = match self.transform {
+            Transform::Invert => 0,
+            Transform::Gamma(..) | Transform::Curve(..) => 1,
+            Transform::ContrastBrightness(..) | Transform::Levels(..) => 2,
+        };
+        if lines_iter.len() != lines_count {
+            return Err(MyError::new(match lines_count {
+                0 => "У данного фильтра нет настроек".to_string(),
+                1 => "Должна быть 1 строка".to_string(),
+                _ => format!("Должно быть {} строки", lines_count),
+            }));
+        }
+
+        self.transform = match self.transform {
+            Transform::Gamma(..) => {
+                Transform::Gamma(Gamma::try_from_string(lines_iter.next_or_empty())?)
+            }
+            Transform::ContrastBrightness(..) => {
+                let contrast = Contrast::try_from_string(lines_iter.next_or_empty())?;
+                let brightness = Brightness::try_from_string(lines_iter.next_or_empty())?;
+                Transform::ContrastBrightness(contrast, brightness)
+            }
+            Transform::Levels(..) => {
+                let input = InputLevels::try_from_string(lines_iter.next_or_empty())?;
+                let output = OutputLevels::try_from_string(lines_iter.next_or_empty())?;
+                Transform::Levels(input, output)
+            }
+            Transform::Invert => Transform::Invert,
+            Transform::Curve(..) => {
+                Transform::Curve(CurvePoints::try_from_string(lines_iter.next_or_empty())?)
+            }
+        };
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        match &self.transform {
+            Transform::Gamma(gamma) => Some(gamma.content_to_string()),
+            Transform::ContrastBrightness(contrast, brightness) => Some(format!(
+                "{}\n{}",
+                contrast.content_to_string(),
+                brightness.content_to_string()
+            )),
+            Transform::Levels(input, output) => Some(format!(
+                "{}\n{}",
+                input.content_to_string(),
+                output.content_to_string()
+            )),
+            Transform::Invert => None,
+            Transform::Curve(points) => Some(points.content_to_string()),
+        }
+    }
+}
+
+impl ByLayer for PointTransform {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        if layer.channel() == ImgChannel::A {
+            return Ok(layer.clone());
+        }
+
+        let max_value = self.bit_depth.max_value();
+        let mut mat_res = layer.matrix().clone();
+        mat_res.scalar_transform_self_area(
+            layer
+                .get_area()
+                .iter_pixels()
+                .track_progress(executor_handle),
+            |val: &mut f64, _pos| *val = self.map(*val / max_value) * max_value,
+        )?;
+
+        Ok(ImgLayer::new(mat_res, layer.channel()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_gradient_img, run_checked};
+
+    #[test]
+    fn point_transforms_map_each_value() {
+        // the values 0, 51, 102, 153, 204, 255 in the columns
+        let img = create_gradient_img(6, 2, ColorDepth::La8, 0, 51);
+
+        let check = |mut filter: PointTransform, params: &str, expected: [f64; 6]| {
+            filter.try_set_from_string(params).unwrap();
+
+            let res = run_checked(&filter, &img);
+            for pos in res.get_area().iter_pixels() {
+                let val = res.layer(0)[pos];
+                assert!(
+                    (val - expected[pos.col]).abs() < 1e-9,
+                    "{} {:?} {}",
+                    filter.get_save_name(),
+                    pos,
+                    val
+                );
+                assert_eq!(res.layer(1)[pos], img.layer(1)[pos]);
+            }
+        };
+
+        check(
+            PointTransform::gamma(),
+            "Gamma: 0.5",
+            [0_f64, 10.2, 40.8, 91.8, 163.2, 255_f64],
+        );
+        check(
+            PointTransform::contrast_brightness(),
+            "Contrast: 2\nBrightness: 0.1",
+            [0_f64, 0_f64, 102_f64, 204_f64, 255_f64, 255_f64],
+        );
+        check(
+            PointTransform::levels(),
+            "Input: 0.2 - 0.6\nOutput: 1 - 0",
+            [255_f64, 255_f64, 127.5, 0_f64, 0_f64, 0_f64],
+        );
+        check(
+            PointTransform::invert(),
+            "",
+            [255_f64, 204_f64, 153_f64, 102_f64, 51_f64, 0_f64],
+        );
+        check(
+            PointTransform::curve(),
+            "Curve: 0.2 0, 0.6 1, 1 0.5",
+            [0_f64, 0_f64, 127.5, 255_f64, 191.25, 127.5],
+        );
+    }
+
+    #[test]
+    fn wrong_point_transform_params_are_rejected() {
+        let mut curve = PointTransform::curve();
+        for params in [
+            "Curve: 0.5 0",
+            "Curve: 0.5 0, 0.4 1",
+            "Curve: 0 0, 1 1.5",
+            "Curve 0 0, 1 1",
+        ]
+        .iter()
+        {
+            assert!(curve.try_set_from_string(params).is_err(), "{}", params);
+        }
+        assert!(PointTransform::levels()
+            .try_set_from_string("Input: 0.6 - 0.2\nOutput: 0 - 1")
+            .is_err());
+        assert!(PointTransform::invert()
+            .try_set_from_string("Gamma: 1")
+            .is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use super::super::super::ImgChannel;
-use super::super::super::{Img, ImgLayer, Matrix2D};
+use super::super::super::{ColorSpace, Img, ImgLayer, Matrix2D};
 use super::super::filter_trait::*;
 use super::super::FilterBase;
 use crate::my_err::MyError;
@@ -18,6 +18,16 @@ impl Filter for Rgb2Gray {
                 Ok(res)
             }
             ColorDepth::Rgb8 | ColorDepth::Rgba8 => {
+                let converted: Img;
+                let img = match img.color_space() {
+                    ColorSpace::Rgb => img,
+                    _ => {
+                        converted =
+                            img.try_convert_color_space(ColorSpace::Rgb, executor_handle)?;
+                        &converted
+                    }
+                };
+
                 let mut img_res = img.clone();
                 let layers = img_res.layers_mut();
 
@@ -55,7 +65,11 @@ impl Filter for Rgb2Gray {
                     }
                 };
 
-                Ok(Img::from_layers(new_layers, color_depth))
+                Ok(Img::from_layers_of_depth(
+                    new_layers,
+                    color_depth,
+                    img.bit_depth(),
+                ))
             }
         }
     }
@@ -63,7 +77,13 @@ impl Filter for Rgb2Gray {
     fn get_steps_num(&self, img: &Img) -> usize {
         match img.color_depth() {
             ColorDepth::L8 | ColorDepth::La8 => 1,
-            ColorDepth::Rgb8 | ColorDepth::Rgba8 => img.w() * img.h(),
+            ColorDepth::Rgb8 | ColorDepth::Rgba8 => {
+                let to_rgb = match img.color_space() {
+                    ColorSpace::Rgb => 0,
+                    _ => img.h(),
+                };
+                to_rgb + img.w() * img.h()
+            }
         }
     }
 
@@ -0,0 +1,318 @@
+use super::super::linear::{LinearGaussian, LinearMean};
+use super::super::ByLayer;
+use super::img::*;
+use super::traits::*;
+use super::utils::*;
+use super::Rgb2Gray;
+use crate::img::filter::FilterBase;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// makes an L8 mask: 255 for the pixels brighter than the threshold, 0 for the others
+#[derive(Clone)]
+pub struct Threshold {
+    method: ThresholdMethod,
+    name: String,
+}
+
+impl Threshold {
+    pub fn new(method: ThresholdMethod) -> Self {
+        Threshold {
+            method,
+            name: "Бинаризация".to_string(),
+        }
+    }
+}
+
+impl Filter for Threshold {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let grayed: Img;
+        let img = match img.color_depth() {
+            ColorDepth::L8 | ColorDepth::La8 => img,
+            ColorDepth::Rgb8 | ColorDepth::Rgba8 => {
+                grayed = Rgb2Gray::default().process(img, executor_handle)?;
+                &grayed
+            }
+        };
+
+        let bit_depth = img.bit_depth();
+        let layer_l: &ImgLayer = img
+            .layers()
+            .iter()
+            .find(|l| l.channel() == ImgChannel::L)
+            .unwrap();
+        let mat = layer_l.matrix();
+
+        let to_bit_depth = |val_u8: f64| val_u8 / u8::MAX as f64 * bit_depth.max_value();
+
+        let mask = match self.method {
+            ThresholdMethod::Fixed { threshold } => {
+                let threshold = to_bit_depth(threshold as f64);
+                make_mask(mat, |pos| mat[pos] > threshold, executor_handle)?
+            }
+            ThresholdMethod::Otsu | ThresholdMethod::Triangle => {
+                let mut hist = HistBuf::new();
+                count_histogram(mat, bit_depth, &mut hist);
+                let level = match self.method {
+                    ThresholdMethod::Otsu => otsu_level(&hist),
+                    _ => triangle_level(&hist),
+                };
+                executor_handle.complete_action()?;
+
+                make_mask(
+                    mat,
+                    |pos| bit_depth.level_of(mat[pos]) > level,
+                    executor_handle,
+                )?
+            }
+            ThresholdMethod::AdaptiveMean { block_size, offset }
+            | ThresholdMethod::AdaptiveGaussian { block_size, offset } => {
+                let size = FilterWindowSize::new(block_size, block_size);
+                let local_mean = match self.method {
+                    ThresholdMethod::AdaptiveMean { .. } => {
+                        LinearMean::new(size, ExtendValue::Closest)
+                            .process_layer(layer_l, executor_handle)?
+                    }
+                    _ => LinearGaussian::new(size, ExtendValue::Closest)
+                        .process_layer(layer_l, executor_handle)?,
+                };
+                let offset = to_bit_depth(offset);
+
+                make_mask(
+                    mat,
+                    |pos| mat[pos] > local_mean[pos] - offset,
+                    executor_handle,
+                )?
+            }
+        };
+
+        let mask_layer = ImgLayer::new(mask, ImgChannel::L);
+        Ok(Img::from_layers_of_depth(
+            vec![mask_layer],
+            ColorDepth::L8,
+            BitDepth::U8,
+        ))
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let to_gray = match img.color_depth() {
+            ColorDepth::L8 | ColorDepth::La8 => 0,
+            ColorDepth::Rgb8 | ColorDepth::Rgba8 => Rgb2Gray::default().get_steps_num(img),
+        };
+
+        let find_threshold = match self.method {
+            ThresholdMethod::Fixed { .. } => 0,
+            ThresholdMethod::Otsu | ThresholdMethod::Triangle => 1,
+            // the local mean is counted by a separable filter
+            ThresholdMethod::AdaptiveMean { block_size, .. }
+            | ThresholdMethod::AdaptiveGaussian { block_size, .. } => {
+                img.h() + block_size / 2 * 2 + img.w()
+            }
+        };
+
+        to_gray + find_threshold + img.h()
+    }
+
+    fn get_description(&self) -> String {
+        format!("{} ({})", &self.name, self.method.get_description())
+    }
+
+    fn get_save_name(&self) -> String {
+        "Threshold".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+fn make_mask<F: Fn(PixelPos) -> bool>(
+    mat: &Matrix2D,
+    is_foreground: F,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<Matrix2D, TaskStop> {
+    let mut mask = Matrix2D::empty_size_of(mat);
+
+    for row in 0..mat.h() {
+        for col in 0..mat.w() {
+            let pos = PixelPos::new(row, col);
+            if is_foreground(pos) {
+                mask[pos] = u8::MAX as f64;
+            }
+        }
+
+        executor_handle.complete_action()?;
+    }
+
+    Ok(mask)
+}
+
+// maximizes the between-class variance
+fn otsu_level(hist: &[f64]) -> usize {
+    let total: f64 = hist.iter().sum();
+    let sum_total: f64 = hist
+        .iter()
+        .enumerate()
+        .map(|(level, count)| level as f64 * count)
+        .sum();
+
+    let mut best_level = 0;
+    let mut best_variance = 0_f64;
+
+    let mut count_below = 0_f64;
+    let mut sum_below = 0_f64;
+    for (level, count) in hist.iter().enumerate() {
+        count_below += count;
+        sum_below += level as f64 * count;
+
+        let count_above = total - count_below;
+        if count_below == 0_f64 {
+            continue;
+        }
+        if count_above == 0_f64 {
+            break;
+        }
+
+        let mean_below = sum_below / count_below;
+        let mean_above = (sum_total - sum_below) / count_above;
+        let variance = count_below * count_above * (mean_below - mean_above).powi(2);
+
+        if variance > best_variance {
+            best_variance = variance;
+            best_level = level;
+        }
+    }
+
+    best_level
+}
+
+// the level farthest from the line between the histogram peak and the end of its longer tail
+fn triangle_level(hist: &[f64]) -> usize {
+    let first = match hist.iter().position(|count| *count > 0_f64) {
+        Some(level) => level,
+        None => return 0,
+    };
+    let last = hist.iter().rposition(|count| *count > 0_f64).unwrap();
+
+    let peak = (first..=last).fold(first, |peak, level| {
+        if hist[level] > hist[peak] {
+            level
+        } else {
+            peak
+        }
+    });
+
+    let tail_end = if peak - first > last - peak {
+        first
+    } else {
+        last
+    };
+
+    let peak_count = hist[peak];
+    let (from, to) = (peak.min(tail_end), peak.max(tail_end));
+
+    let mut best_level = peak;
+    let mut best_dist = 0_f64;
+    for (level, count) in hist.iter().enumerate().take(to + 1).skip(from) {
+        // proportional to the distance from (level, count) to the line
+        let dist = (peak_count * (level as f64 - tail_end as f64)
+            - count * (peak as f64 - tail_end as f64))
+            .abs();
+        if dist > best_dist {
+            best_dist = dist;
+            best_level = level;
+        }
+    }
+
+    best_level
+}
+
+impl StringFromTo for Threshold {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 1 {
+            return Err(MyError::new("Должна быть 1 строка".to_string()));
+        }
+
+        self.method = ThresholdMethod::try_from_string(lines_iter.next_or_empty())?;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        Some(self.method.content_to_string())
+    }
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Threshold::new(ThresholdMethod::Otsu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{otsu_level, triangle_level, Threshold};
+    use crate::img::{
+        filter::{
+            filter_option::ThresholdMethod,
+            test_utils::{create_img, run_checked},
+        },
+        BitDepth,
+    };
+    use fltk::enums::ColorDepth;
+
+    #[test]
+    fn histogram_thresholds() {
+        let mut hist = vec![0_f64; 256];
+        hist[40] = 100_f64;
+        hist[50] = 60_f64;
+        hist[200] = 80_f64;
+        hist[210] = 50_f64;
+
+        let otsu = otsu_level(&hist);
+        assert!((50..200).contains(&otsu), "{}", otsu);
+
+        let mut hist = vec![0_f64; 256];
+        for level in 20..=120 {
+            hist[level] = (120 - level) as f64;
+        }
+        hist[20] = 500_f64;
+        let triangle = triangle_level(&hist);
+        assert!((21..120).contains(&triangle), "{}", triangle);
+
+        assert_eq!(otsu_level(&vec![0_f64; 256]), 0);
+        assert_eq!(triangle_level(&vec![0_f64; 256]), 0);
+    }
+
+    #[test]
+    fn threshold_makes_l8_mask() {
+        // the classes differ in size, so the triangle method has a larger peak to start from
+        let img = create_img(20, 10, ColorDepth::Rgba8, |_, pos| {
+            if pos.col < 12 {
+                30_f64
+            } else {
+                220_f64
+            }
+        })
+        .converted_to_depth(BitDepth::U16);
+
+        let methods = [
+            ThresholdMethod::Fixed { threshold: 100 },
+            ThresholdMethod::Otsu,
+            ThresholdMethod::Triangle,
+        ];
+        for method in methods.iter() {
+            let res = run_checked(&Threshold::new(*method), &img);
+
+            assert_eq!(res.color_depth(), ColorDepth::L8);
+            assert_eq!(res.bit_depth(), BitDepth::U8);
+            for pos in res.get_area().iter_pixels() {
+                let expected = if pos.col < 12 { 0_f64 } else { 255_f64 };
+                assert_eq!(res.layer(0)[pos], expected, "{:?} {:?}", method, pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::*;
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+#[derive(Clone)]
+pub struct Gradient {
+    operator: GradientOperator,
+    output: GradientOutput,
+    mapping: ValueMapping,
+    extend_value: ExtendValue,
+    bit_depth: BitDepth,
+}
+
+impl Gradient {
+    pub fn new(
+        operator: GradientOperator,
+        output: GradientOutput,
+        mapping: ValueMapping,
+        extend_value: ExtendValue,
+    ) -> Self {
+        Gradient {
+            operator,
+            output,
+            mapping,
+            extend_value,
+            bit_depth: BitDepth::default(),
+        }
+    }
+
+    pub fn with_operator(operator: GradientOperator) -> Self {
+        Gradient {
+            operator,
+            ..Gradient::default()
+        }
+    }
+
+    fn derivative(
+        &self,
+        mat: &Matrix2D,
+        coeffs: Vec<f64>,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Matrix2D, TaskStop> {
+        let filter = LinearCustom::with_coeffs(
+            coeffs,
+            3,
+            3,
+            self.extend_value,
+            NormalizeOption::NotNormalized,
+        );
+        process_with_window(mat, &filter, executor_handle)
+    }
+}
+
+impl Filter for Gradient {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = Gradient {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+        let derivatives_count = match self.output {
+            GradientOutput::X | GradientOutput::Y => 1,
+            GradientOutput::Magnitude | GradientOutput::Orientation => 2,
+        };
+
+        layers_count * derivatives_count * img.h()
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Градиент ({}, {}, {})",
+            self.operator.get_description(),
+            self.output.get_description(),
+            self.mapping.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "Gradient".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for Gradient {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let mat = match layer.channel() {
+            ImgChannel::A => return Ok(layer.clone()),
+            _ => layer.matrix(),
+        };
+
+        // the largest derivative is reached on a step from 0 to max value
+        let x_kernel = self.operator.x_kernel();
+        let max_derivative =
+            x_kernel.iter().filter(|c| **c > 0_f64).sum::<f64>() * self.bit_depth.max_value();
+
+        let (mut res, possible_range) = match self.output {
+            GradientOutput::X => (
+                self.derivative(mat, x_kernel, executor_handle)?,
+                (-max_derivative, max_derivative),
+            ),
+            GradientOutput::Y => (
+                self.derivative(mat, self.operator.y_kernel(), executor_handle)?,
+                (-max_derivative, max_derivative),
+            ),
+            GradientOutput::Magnitude | GradientOutput::Orientation => {
+                let mut dx = self.derivative(mat, x_kernel, executor_handle)?;
+                let dy = self.derivative(mat, self.operator.y_kernel(), executor_handle)?;
+
+                let magnitude = self.output == GradientOutput::Magnitude;
+                for (x, y) in dx.vals_mut().iter_mut().zip(dy.vals().iter()) {
+                    *x = if magnitude { x.hypot(*y) } else { y.atan2(*x) };
+                }
+
+                let possible_range = if magnitude {
+                    (0_f64, max_derivative * std::f64::consts::SQRT_2)
+                } else {
+                    (-std::f64::consts::PI, std::f64::consts::PI)
+                };
+                (dx, possible_range)
+            }
+        };
+
+        self.mapping
+            .map_values(&mut res, possible_range, self.bit_depth.max_value());
+
+        Ok(ImgLayer::new(res, layer.channel()))
+    }
+}
+
+impl StringFromTo for Gradient {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 4 {
+            return Err(MyError::new(
+                "Нужно ввести оператор, результат, отображение значений и способ дополнения краев на отдельных строках".to_string(),
+            ));
+        }
+
+        let operator = GradientOperator::try_from_string(lines_iter.next_or_empty())?;
+        let output = GradientOutput::try_from_string(lines_iter.next_or_empty())?;
+        let mapping = ValueMapping::try_from_string(lines_iter.next_or_empty())?;
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        self.operator = operator;
+        self.output = output;
+        self.mapping = mapping;
+        self.extend_value = extend_value;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}\n{}",
+            self.operator.content_to_string(),
+            self.output.content_to_string(),
+            self.mapping.content_to_string(),
+            self.extend_value.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient::new(
+            GradientOperator::Sobel,
+            GradientOutput::Magnitude,
+            ValueMapping::Linear,
+            ExtendValue::Closest,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_step_img, run_checked};
+
+    #[test]
+    fn gradient_maps_signed_values() {
+        let img = create_step_img(12, 8, ColorDepth::L8, |_| (0_f64, 200_f64));
+
+        // the sobel derivative on the step is 800, the largest possible one is 4 * 255
+        let mut gradient = Gradient::default();
+        for (params, on_edge, flat) in [
+            ("x\nMapping: linear", 1820_f64 / 2040_f64 * 255_f64, 127.5),
+            ("y\nMapping: linear", 127.5, 127.5),
+            ("x\nMapping: absolute", 200_f64, 0_f64),
+            ("x\nMapping: clamped", 255_f64, 0_f64),
+            ("x\nMapping: stretched", 255_f64, 0_f64),
+            (
+                "magnitude\nMapping: linear",
+                800_f64 / (1020_f64 * std::f64::consts::SQRT_2) * 255_f64,
+                0_f64,
+            ),
+            ("orientation\nMapping: linear", 127.5, 127.5),
+        ]
+        .iter()
+        {
+            let params = format!("Operator: sobel\nOutput: {}\nExt: near", params);
+            gradient.try_set_from_string(&params).unwrap();
+
+            let res = run_checked(&gradient, &img);
+            for pos in res.get_area().iter_pixels() {
+                let expected = if pos.col == 5 || pos.col == 6 {
+                    *on_edge
+                } else {
+                    *flat
+                };
+                assert!(
+                    (res.layer(0)[pos] - expected).abs() < 1e-9,
+                    "{} {:?}",
+                    params,
+                    pos
+                );
+            }
+        }
+    }
+}
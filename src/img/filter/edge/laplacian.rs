@@ -0,0 +1,311 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::*;
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+const LAPLACIAN_COEFFS: [f64; 9] = [0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0];
+
+// the laplacian of gaussian (LoG) if sigma is given
+#[derive(Clone)]
+pub struct Laplacian {
+    sigma: Option<Sigma>,
+    mapping: ValueMapping,
+    zero_crossings: ZeroCrossings,
+    extend_value: ExtendValue,
+    bit_depth: BitDepth,
+}
+
+impl Laplacian {
+    pub fn new(
+        sigma: Option<Sigma>,
+        mapping: ValueMapping,
+        zero_crossings: ZeroCrossings,
+        extend_value: ExtendValue,
+    ) -> Self {
+        Laplacian {
+            sigma,
+            mapping,
+            zero_crossings,
+            extend_value,
+            bit_depth: BitDepth::default(),
+        }
+    }
+
+    pub fn of_gaussian() -> Self {
+        Laplacian::new(
+            Some(Sigma::new(1.4)),
+            ValueMapping::Linear,
+            ZeroCrossings::Threshold(0.05),
+            ExtendValue::Closest,
+        )
+    }
+
+    fn gaussian(&self) -> Option<LinearGaussian> {
+        self.sigma
+            .map(|sigma| LinearGaussian::with_sigma(sigma, self.extend_value))
+    }
+
+    // a pixel is marked if the sign changes between it and one of the next neighbours
+    // with the jump not less than the threshold
+    fn mark_zero_crossings(
+        &self,
+        mat: &Matrix2D,
+        threshold: f64,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Matrix2D, TaskStop> {
+        let abs_max = mat.vals().iter().fold(0_f64, |max, val| max.max(val.abs()));
+        let min_jump = threshold * abs_max;
+
+        let mut res = Matrix2D::empty_size_of(mat);
+
+        for row in 0..mat.h() {
+            for col in 0..mat.w() {
+                let pos = PixelPos::new(row, col);
+                let val = mat[pos];
+
+                let mut neighbours = Vec::<PixelPos>::with_capacity(4);
+                if col < mat.max_col() {
+                    neighbours.push(pos.righter());
+                }
+                if row < mat.max_row() {
+                    neighbours.push(pos.downer());
+                    if col < mat.max_col() {
+                        neighbours.push(pos.downer_righter());
+                    }
+                    if col > 0 {
+                        neighbours.push(pos.downer_lefter());
+                    }
+                }
+
+                let crosses = neighbours.iter().any(|neighbour| {
+                    let other = mat[*neighbour];
+                    val * other < 0_f64 && (val - other).abs() >= min_jump
+                });
+                if crosses {
+                    res[pos] = self.bit_depth.max_value();
+                }
+            }
+
+            executor_handle.complete_action()?;
+        }
+
+        Ok(res)
+    }
+}
+
+impl Filter for Laplacian {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = Laplacian {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        let blur = match self.gaussian() {
+            Some(gaussian) => img.h() + gaussian.h() / 2 * 2 + img.w(),
+            None => 0,
+        };
+        let zero_crossings = match self.zero_crossings {
+            ZeroCrossings::Off => 0,
+            ZeroCrossings::Threshold(_) => img.h(),
+        };
+
+        layers_count * (blur + img.h() + zero_crossings)
+    }
+
+    fn get_description(&self) -> String {
+        let output = match self.zero_crossings {
+            ZeroCrossings::Off => self.mapping.get_description().to_string(),
+            ZeroCrossings::Threshold(val) => format!("пересечения нуля, порог {}", val),
+        };
+        match self.sigma {
+            Some(sigma) => format!("Лапласиан гауссиана (сигма {}, {})", sigma.value, output),
+            None => format!("Лапласиан ({})", output),
+        }
+    }
+
+    fn get_save_name(&self) -> String {
+        match self.sigma {
+            Some(_) => "LaplacianOfGaussian".to_string(),
+            None => "Laplacian".to_string(),
+        }
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for Laplacian {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let mat = match layer.channel() {
+            ImgChannel::A => return Ok(layer.clone()),
+            _ => layer.matrix(),
+        };
+
+        let blured: Matrix2D;
+        let mat = match self.gaussian() {
+            Some(gaussian) => {
+                blured = process_separable(mat, &gaussian, executor_handle)?;
+                &blured
+            }
+            None => mat,
+        };
+
+        let laplacian_filter = LinearCustom::with_coeffs(
+            LAPLACIAN_COEFFS.to_vec(),
+            3,
+            3,
+            self.extend_value,
+            NormalizeOption::NotNormalized,
+        );
+        let mut res = process_with_window(mat, &laplacian_filter, executor_handle)?;
+
+        match self.zero_crossings {
+            ZeroCrossings::Threshold(threshold) => {
+                res = self.mark_zero_crossings(&res, threshold, executor_handle)?;
+            }
+            ZeroCrossings::Off => {
+                let max_value = self.bit_depth.max_value();
+                self.mapping.map_values(
+                    &mut res,
+                    (-4_f64 * max_value, 4_f64 * max_value),
+                    max_value,
+                );
+            }
+        }
+
+        Ok(ImgLayer::new(res, layer.channel()))
+    }
+}
+
+impl StringFromTo for Laplacian {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+
+        let lines_count = if self.sigma.is_some() { 4 } else { 3 };
+        if lines_iter.len() != lines_count {
+            let sigma_line = if self.sigma.is_some() {
+                "сигму, "
+            } else {
+                ""
+            };
+            return Err(MyError::new(format!(
+                "Нужно ввести {}отображение значений, пересечения нуля и способ дополнения краев на отдельных строках",
+                sigma_line
+            )));
+        }
+
+        let sigma = match self.sigma {
+            Some(_) => Some(Sigma::try_from_string(lines_iter.next_or_empty())?),
+            None => None,
+        };
+        let mapping = ValueMapping::try_from_string(lines_iter.next_or_empty())?;
+        let zero_crossings = ZeroCrossings::try_from_string(lines_iter.next_or_empty())?;
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        self.sigma = sigma;
+        self.mapping = mapping;
+        self.zero_crossings = zero_crossings;
+        self.extend_value = extend_value;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let mut params_str = String::new();
+        if let Some(sigma) = self.sigma {
+            params_str.push_str(&format!("{}\n", sigma.content_to_string()));
+        }
+        params_str.push_str(&format!(
+            "{}\n{}\n{}",
+            self.mapping.content_to_string(),
+            self.zero_crossings.content_to_string(),
+            self.extend_value.content_to_string()
+        ));
+        Some(params_str)
+    }
+}
+
+impl Default for Laplacian {
+    fn default() -> Self {
+        Laplacian::new(
+            None,
+            ValueMapping::Linear,
+            ZeroCrossings::Off,
+            ExtendValue::Closest,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_step_img, run_checked};
+
+    #[test]
+    fn laplacian_maps_signed_values_and_marks_zero_crossings() {
+        let img = create_step_img(12, 8, ColorDepth::L8, |_| (0_f64, 200_f64));
+
+        let process = |filter: &mut Laplacian, params: &str| {
+            filter.try_set_from_string(params).unwrap();
+            run_checked(filter, &img)
+        };
+
+        let mut laplacian = Laplacian::default();
+        let res = process(
+            &mut laplacian,
+            "Mapping: linear\nZero crossings: off\nExt: near",
+        );
+        assert_eq!(res.layer(0)[PixelPos::new(3, 2)], 127.5);
+        assert_eq!(
+            res.layer(0)[PixelPos::new(3, 5)],
+            1220_f64 / 2040_f64 * 255_f64
+        );
+
+        let res = process(
+            &mut laplacian,
+            "Mapping: linear\nZero crossings: 0.1\nExt: near",
+        );
+        for pos in res.get_area().iter_pixels() {
+            let marked = res.layer(0)[pos] == 255_f64;
+            match pos.col {
+                5 => assert!(marked, "{:?}", pos),
+                6 => {}
+                _ => assert!(!marked, "{:?}", pos),
+            }
+        }
+
+        let mut log = Laplacian::of_gaussian();
+        let res = process(
+            &mut log,
+            "Sigma: 1\nMapping: linear\nZero crossings: 0.05\nExt: reflect101",
+        );
+        assert!(
+            res.layer(0)[PixelPos::new(3, 5)] == 255_f64
+                || res.layer(0)[PixelPos::new(3, 6)] == 255_f64
+        );
+        assert_eq!(res.layer(0)[PixelPos::new(3, 1)], 0_f64);
+        assert_eq!(log.get_save_name(), "LaplacianOfGaussian");
+        assert!(log
+            .try_set_from_string("Mapping: linear\nZero crossings: off\nExt: near")
+            .is_err());
+    }
+}
@@ -0,0 +1,5 @@
+mod gradient;
+mod laplacian;
+
+pub use gradient::Gradient;
+pub use laplacian::Laplacian;
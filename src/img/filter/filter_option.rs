@@ -1,4 +1,5 @@
 use crate::{
+    img::{ColorSpace, Matrix2D},
     my_err::MyError,
     utils::{self, LinesIter, WordsIter},
 };
@@ -148,10 +149,58 @@ impl Parceable for NormalizeOption {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// how the values outside of the image are taken, e.g. for "abcdefgh":
+//   Closest:    aaaaaa|abcdefgh|hhhhhhh
+//   Given(z):   zzzzzz|abcdefgh|zzzzzzz
+//   Reflect:    fedcba|abcdefgh|hgfedcb
+//   Reflect101: gfedcb|abcdefgh|gfedcba
+//   Wrap:       cdefgh|abcdefgh|abcdefg
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ExtendValue {
     Closest,
     Given(f64),
+    Reflect,
+    Reflect101,
+    Wrap,
+}
+impl ExtendValue {
+    // index in [0; len) the value at `ind` is taken from, None for the given value
+    pub fn source_index(&self, ind: isize, len: usize) -> Option<usize> {
+        assert!(len > 0);
+
+        if ind >= 0 && (ind as usize) < len {
+            return Some(ind as usize);
+        }
+
+        let len = len as isize;
+        let src_ind = match self {
+            ExtendValue::Closest => ind.clamp(0, len - 1),
+            ExtendValue::Given(_) => return None,
+            ExtendValue::Reflect => {
+                let period_pos = ind.rem_euclid(2 * len);
+                if period_pos < len {
+                    period_pos
+                } else {
+                    2 * len - 1 - period_pos
+                }
+            }
+            ExtendValue::Reflect101 => {
+                if len == 1 {
+                    0
+                } else {
+                    let period_pos = ind.rem_euclid(2 * len - 2);
+                    if period_pos < len {
+                        period_pos
+                    } else {
+                        2 * len - 2 - period_pos
+                    }
+                }
+            }
+            ExtendValue::Wrap => ind.rem_euclid(len),
+        };
+
+        Some(src_ind as usize)
+    }
 }
 impl Parceable for ExtendValue {
     fn try_from_string(string: &str) -> Result<Self, MyError> {
@@ -160,7 +209,7 @@ impl Parceable for ExtendValue {
 
         let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
 
-        let foemat_err_msg = "Формат граничных условий: 'Ext: near' или 'Ext: 0'".to_string();
+        let foemat_err_msg = "Формат граничных условий: 'Ext: near', 'Ext: reflect', 'Ext: reflect101', 'Ext: wrap' или 'Ext: <дробное число>'".to_string();
 
         if words_iter.len() != 2 {
             return Err(MyError::new(foemat_err_msg));
@@ -171,11 +220,16 @@ impl Parceable for ExtendValue {
         }
 
         let ext_value = match words_iter.next_or_empty() {
-            "0" => ExtendValue::Given(0_f64),
             "near" => ExtendValue::Closest,
-            _ => {
-                return Err(MyError::new(foemat_err_msg));
-            }
+            "reflect" => ExtendValue::Reflect,
+            "reflect101" => ExtendValue::Reflect101,
+            "wrap" => ExtendValue::Wrap,
+            word => match word.parse::<f64>() {
+                Ok(val) if val.is_finite() => ExtendValue::Given(val),
+                _ => {
+                    return Err(MyError::new(foemat_err_msg));
+                }
+            },
         };
 
         Ok(ext_value)
@@ -185,6 +239,9 @@ impl Parceable for ExtendValue {
         match self {
             ExtendValue::Closest => "Ext: near".to_string(),
             ExtendValue::Given(val) => format!("Ext: {}", val),
+            ExtendValue::Reflect => "Ext: reflect".to_string(),
+            ExtendValue::Reflect101 => "Ext: reflect101".to_string(),
+            ExtendValue::Wrap => "Ext: wrap".to_string(),
         }
     }
 }
@@ -238,6 +295,442 @@ impl Parceable for ARange {
     }
 }
 
+#[derive(Clone, Copy)]
+pub struct Percentile {
+    pub value: f64,
+}
+impl Percentile {
+    pub fn new(value: f64) -> Self {
+        assert!((0_f64..=100_f64).contains(&value));
+        Percentile { value }
+    }
+
+    // index of the value in the sorted sequence of 'count' values
+    pub fn rank_of(&self, count: usize) -> usize {
+        assert!(count > 0);
+        ((count - 1) as f64 * self.value / 100_f64).round() as usize
+    }
+}
+impl Parceable for Percentile {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат ранга: 'Percentile: <дробное число от 0 до 100 включительно>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Percentile:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let value = match words_iter.next_or_empty().parse::<f64>() {
+            Ok(val) if (0_f64..=100_f64).contains(&val) => val,
+            _ => {
+                return Err(MyError::new(format_err_msg));
+            }
+        };
+
+        Ok(Percentile::new(value))
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Percentile: {}", self.value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MorphOp {
+    Erode,
+    Dilate,
+    Open,
+    Close,
+    Gradient,
+    WhiteTopHat,
+    BlackTopHat,
+}
+impl MorphOp {
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            MorphOp::Erode => "Эрозия",
+            MorphOp::Dilate => "Дилатация",
+            MorphOp::Open => "Размыкание",
+            MorphOp::Close => "Замыкание",
+            MorphOp::Gradient => "Морфологический градиент",
+            MorphOp::WhiteTopHat => "Белый цилиндр",
+            MorphOp::BlackTopHat => "Черный цилиндр",
+        }
+    }
+
+    fn save_name(&self) -> &'static str {
+        match self {
+            MorphOp::Erode => "erode",
+            MorphOp::Dilate => "dilate",
+            MorphOp::Open => "open",
+            MorphOp::Close => "close",
+            MorphOp::Gradient => "gradient",
+            MorphOp::WhiteTopHat => "tophat",
+            MorphOp::BlackTopHat => "blackhat",
+        }
+    }
+}
+impl Parceable for MorphOp {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат операции: 'Op: <erode, dilate, open, close, gradient, tophat или blackhat>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Op:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let word = words_iter.next_or_empty();
+        let op = [
+            MorphOp::Erode,
+            MorphOp::Dilate,
+            MorphOp::Open,
+            MorphOp::Close,
+            MorphOp::Gradient,
+            MorphOp::WhiteTopHat,
+            MorphOp::BlackTopHat,
+        ]
+        .iter()
+        .find(|op| op.save_name() == word);
+
+        match op {
+            Some(op) => Ok(*op),
+            None => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Op: {}", self.save_name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StructElementShape {
+    Rect,
+    Cross,
+    Disk,
+    Custom,
+}
+impl StructElementShape {
+    fn save_name(&self) -> &'static str {
+        match self {
+            StructElementShape::Rect => "rect",
+            StructElementShape::Cross => "cross",
+            StructElementShape::Disk => "disk",
+            StructElementShape::Custom => "custom",
+        }
+    }
+}
+
+// structuring element of morphological operations, its origin is in (h / 2, w / 2)
+#[derive(Clone)]
+pub struct StructElement {
+    shape: StructElementShape,
+    width: usize,
+    height: usize,
+    mask: Vec<bool>,
+}
+impl StructElement {
+    pub fn new(shape: StructElementShape, size: FilterWindowSize) -> Self {
+        assert_ne!(shape, StructElementShape::Custom);
+        assert_eq!(size.width % 2, 1);
+        assert_eq!(size.height % 2, 1);
+
+        let (w, h) = (size.width, size.height);
+        let (half_w, half_h) = ((w / 2) as f64, (h / 2) as f64);
+
+        let mut mask = Vec::<bool>::with_capacity(w * h);
+        for row in 0..h {
+            for col in 0..w {
+                let dr = row as f64 - half_h;
+                let dc = col as f64 - half_w;
+                let inside = match shape {
+                    StructElementShape::Rect => true,
+                    StructElementShape::Cross => dr == 0_f64 || dc == 0_f64,
+                    StructElementShape::Disk => {
+                        let r_term = if half_h > 0_f64 { dr / half_h } else { 0_f64 };
+                        let c_term = if half_w > 0_f64 { dc / half_w } else { 0_f64 };
+                        r_term * r_term + c_term * c_term <= 1_f64
+                    }
+                    StructElementShape::Custom => unreachable!(),
+                };
+                mask.push(inside);
+            }
+        }
+
+        StructElement {
+            shape,
+            width: w,
+            height: h,
+            mask,
+        }
+    }
+
+    pub fn custom(mask: Vec<bool>, width: usize, height: usize) -> Self {
+        assert_eq!(mask.len(), width * height);
+        assert!(mask.iter().any(|v| *v));
+
+        StructElement {
+            shape: StructElementShape::Custom,
+            width,
+            height,
+            mask,
+        }
+    }
+
+    pub fn w(&self) -> usize {
+        self.width
+    }
+
+    pub fn h(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        self.mask[row * self.width + col]
+    }
+
+    pub fn get_description(&self) -> String {
+        let shape = match self.shape {
+            StructElementShape::Rect => "прямоугольник",
+            StructElementShape::Cross => "крест",
+            StructElementShape::Disk => "круг",
+            StructElementShape::Custom => "своя матрица",
+        };
+        format!("{} {}x{}", shape, self.height, self.width)
+    }
+}
+impl Parceable for StructElement {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+
+        let format_err_msg = "Формат структурного элемента: 'Element: <rect, cross или disk>' и размер на следующей строке или 'Element: custom' и матрица из 0 и 1 на следующих строках".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Element:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let shape = match words_iter.next_or_empty() {
+            "rect" => StructElementShape::Rect,
+            "cross" => StructElementShape::Cross,
+            "disk" => StructElementShape::Disk,
+            "custom" => StructElementShape::Custom,
+            _ => {
+                return Err(MyError::new(format_err_msg));
+            }
+        };
+
+        if shape != StructElementShape::Custom {
+            if lines_iter.len() != 1 {
+                return Err(MyError::new(format_err_msg));
+            }
+            let size =
+                FilterWindowSize::try_from_string(lines_iter.next_or_empty())?.check_w_h_odd()?;
+            return Ok(StructElement::new(shape, size));
+        }
+
+        let mut mask = Vec::<bool>::new();
+        let mut width: Option<usize> = None;
+        let height = lines_iter.len();
+        for _ in 0..height {
+            let mut row_len = 0;
+            let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), ",");
+            while let Some(word) = words_iter.next() {
+                match word {
+                    "0" => mask.push(false),
+                    "1" => mask.push(true),
+                    _ => {
+                        return Err(MyError::new(
+                            "Матрица элемента должна состоять из 0 и 1".to_string(),
+                        ));
+                    }
+                }
+                row_len += 1;
+            }
+
+            if *width.get_or_insert(row_len) != row_len {
+                return Err(MyError::new("Некорректная разменость матрицы".to_string()));
+            }
+        }
+
+        match width {
+            Some(width) if width > 0 && mask.iter().any(|v| *v) => {
+                Ok(StructElement::custom(mask, width, height))
+            }
+            _ => Err(MyError::new(
+                "Матрица элемента должна содержать хотя бы одну 1".to_string(),
+            )),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        let mut content = format!("Element: {}", self.shape.save_name());
+
+        match self.shape {
+            StructElementShape::Custom => {
+                for row in 0..self.height {
+                    let row_str: Vec<&str> = (0..self.width)
+                        .map(|col| if self.contains(row, col) { "1" } else { "0" })
+                        .collect();
+                    content.push('\n');
+                    content.push_str(&row_str.join(", "));
+                }
+            }
+            _ => {
+                let size = FilterWindowSize::new(self.width, self.height);
+                content.push('\n');
+                content.push_str(&size.content_to_string());
+            }
+        }
+
+        content
+    }
+}
+
+// thresholds and offsets are set in 8 bit brightness for any bit depth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdMethod {
+    Fixed { threshold: u8 },
+    Otsu,
+    Triangle,
+    AdaptiveMean { block_size: usize, offset: f64 },
+    AdaptiveGaussian { block_size: usize, offset: f64 },
+}
+impl ThresholdMethod {
+    pub fn get_description(&self) -> String {
+        match self {
+            ThresholdMethod::Fixed { threshold } => format!("порог {}", threshold),
+            ThresholdMethod::Otsu => "метод Оцу".to_string(),
+            ThresholdMethod::Triangle => "метод треугольника".to_string(),
+            ThresholdMethod::AdaptiveMean { block_size, offset } => {
+                format!(
+                    "адаптивный средний {}x{}, {}",
+                    block_size, block_size, offset
+                )
+            }
+            ThresholdMethod::AdaptiveGaussian { block_size, offset } => {
+                format!(
+                    "адаптивный гауссовский {}x{}, {}",
+                    block_size, block_size, offset
+                )
+            }
+        }
+    }
+}
+impl Parceable for ThresholdMethod {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат метода: 'Method: fixed <порог от 0 до 255>', 'Method: otsu', 'Method: triangle', 'Method: mean <нечетный размер блока> <смещение>' или 'Method: gaussian <нечетный размер блока> <смещение>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.next_or_empty() != "Method:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let method_name = words_iter.next_or_empty().to_string();
+        let args_count = words_iter.len();
+
+        let method = match (method_name.as_str(), args_count) {
+            ("fixed", 1) => match words_iter.next_or_empty().parse::<u8>() {
+                Ok(threshold) => ThresholdMethod::Fixed { threshold },
+                Err(_) => return Err(MyError::new(format_err_msg)),
+            },
+            ("otsu", 0) => ThresholdMethod::Otsu,
+            ("triangle", 0) => ThresholdMethod::Triangle,
+            ("mean", 2) | ("gaussian", 2) => {
+                let block_size = match words_iter.next_or_empty().parse::<usize>() {
+                    Ok(val) if val >= 3 && val % 2 == 1 => val,
+                    _ => return Err(MyError::new(format_err_msg)),
+                };
+                let offset = match words_iter.next_or_empty().parse::<f64>() {
+                    Ok(val) => val,
+                    Err(_) => return Err(MyError::new(format_err_msg)),
+                };
+                if method_name == "mean" {
+                    ThresholdMethod::AdaptiveMean { block_size, offset }
+                } else {
+                    ThresholdMethod::AdaptiveGaussian { block_size, offset }
+                }
+            }
+            _ => return Err(MyError::new(format_err_msg)),
+        };
+
+        Ok(method)
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            ThresholdMethod::Fixed { threshold } => format!("Method: fixed {}", threshold),
+            ThresholdMethod::Otsu => "Method: otsu".to_string(),
+            ThresholdMethod::Triangle => "Method: triangle".to_string(),
+            ThresholdMethod::AdaptiveMean { block_size, offset } => {
+                format!("Method: mean {} {}", block_size, offset)
+            }
+            ThresholdMethod::AdaptiveGaussian { block_size, offset } => {
+                format!("Method: gaussian {} {}", block_size, offset)
+            }
+        }
+    }
+}
+
+// the highest histogram bin relative to the mean bin height
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipLimit {
+    pub value: f64,
+}
+impl ClipLimit {
+    pub fn new(value: f64) -> Self {
+        assert!(value > 0_f64);
+        ClipLimit { value }
+    }
+}
+impl Parceable for ClipLimit {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат ограничения контраста: 'Clip limit: <положительное дробное число>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 3 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Clip" || words_iter.next_or_empty() != "limit:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if value > 0_f64 && value.is_finite() => Ok(ClipLimit::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Clip limit: {}", self.value)
+    }
+}
+
 #[derive(Clone)]
 pub struct CutBrightnessRange {
     pub min: u8,
@@ -332,6 +825,16 @@ pub enum ImgChannel {
     G,
     B,
     A,
+    H,
+    S,
+    V,
+    Lt,
+    Y,
+    Cb,
+    Cr,
+    LabL,
+    LabA,
+    LabB,
 }
 
 impl Parceable for ImgChannel {
@@ -340,7 +843,8 @@ impl Parceable for ImgChannel {
         Self: Sized,
     {
         let format_err_msg =
-            "Должна быть одна строка: 'Channel: <Название канала A, R, G, B L>".to_string();
+            "Должна быть одна строка: 'Channel: <Название канала A, R, G, B, L, H, S, V, Lt, Y, Cb, Cr, L*, a*, b*>'"
+                .to_string();
 
         let mut lines = utils::LinesIter::new(string);
         if lines.len() != 1 {
@@ -360,6 +864,16 @@ impl Parceable for ImgChannel {
             "G" => ImgChannel::G,
             "B" => ImgChannel::B,
             "L" => ImgChannel::L,
+            "H" => ImgChannel::H,
+            "S" => ImgChannel::S,
+            "V" => ImgChannel::V,
+            "Lt" => ImgChannel::Lt,
+            "Y" => ImgChannel::Y,
+            "Cb" => ImgChannel::Cb,
+            "Cr" => ImgChannel::Cr,
+            "L*" => ImgChannel::LabL,
+            "a*" => ImgChannel::LabA,
+            "b*" => ImgChannel::LabB,
             _ => {
                 return Err(MyError::new(format_err_msg));
             }
@@ -383,12 +897,1455 @@ impl fmt::Display for ImgChannel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let channel_str: &str = match self {
             ImgChannel::L => "L",
-            ImgChannel::R => "B",
+            ImgChannel::R => "R",
             ImgChannel::G => "G",
             ImgChannel::B => "B",
             ImgChannel::A => "A",
+            ImgChannel::H => "H",
+            ImgChannel::S => "S",
+            ImgChannel::V => "V",
+            ImgChannel::Lt => "Lt",
+            ImgChannel::Y => "Y",
+            ImgChannel::Cb => "Cb",
+            ImgChannel::Cr => "Cr",
+            ImgChannel::LabL => "L*",
+            ImgChannel::LabA => "a*",
+            ImgChannel::LabB => "b*",
         };
 
         write!(f, "{}", channel_str)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+    Bicubic,
+}
+impl Interpolation {
+    pub fn get_description(&self) -> String {
+        match self {
+            Interpolation::Nearest => "ближайший сосед",
+            Interpolation::Bilinear => "билинейная",
+            Interpolation::Bicubic => "бикубическая",
+        }
+        .to_string()
+    }
+}
+impl Parceable for Interpolation {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат интерполяции: 'Interpolation: nearest', 'Interpolation: bilinear' или 'Interpolation: bicubic'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Interpolation:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let interpolation = match words_iter.next_or_empty() {
+            "nearest" => Interpolation::Nearest,
+            "bilinear" => Interpolation::Bilinear,
+            "bicubic" => Interpolation::Bicubic,
+            _ => return Err(MyError::new(format_err_msg)),
+        };
+
+        Ok(interpolation)
+    }
+
+    fn content_to_string(&self) -> String {
+        let name = match self {
+            Interpolation::Nearest => "nearest",
+            Interpolation::Bilinear => "bilinear",
+            Interpolation::Bicubic => "bicubic",
+        };
+        format!("Interpolation: {}", name)
+    }
+}
+
+// bigger results would take gigabytes of memory
+const RESIZE_MAX_PIXELS: usize = 100_000_000;
+const RESIZE_MAX_SCALE: f64 = 100_f64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeTarget {
+    Size { width: usize, height: usize },
+    Scale(f64),
+}
+impl ResizeTarget {
+    pub fn size_for(&self, width: usize, height: usize) -> (usize, usize) {
+        match *self {
+            ResizeTarget::Size { width, height } => (width, height),
+            ResizeTarget::Scale(scale) => (
+                ((width as f64 * scale).round() as usize).max(1),
+                ((height as f64 * scale).round() as usize).max(1),
+            ),
+        }
+    }
+
+    pub fn try_size_for(&self, width: usize, height: usize) -> Result<(usize, usize), MyError> {
+        let (res_w, res_h) = self.size_for(width, height);
+        check_resize_size(res_w, res_h)?;
+        Ok((res_w, res_h))
+    }
+
+    pub fn get_description(&self) -> String {
+        match self {
+            ResizeTarget::Size { width, height } => format!("{}x{}", height, width),
+            ResizeTarget::Scale(scale) => format!("x{}", scale),
+        }
+    }
+}
+impl Parceable for ResizeTarget {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат размера: 'Size: <высота> x <ширина>' или 'Scale: <положительное дробное число>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+
+        let kind = words_iter.next_or_empty().to_string();
+        let target = match (kind.as_str(), words_iter.len()) {
+            ("Size:", 3) => {
+                let height = words_iter.next_or_empty().parse::<usize>();
+                let separator = words_iter.next_or_empty().to_string();
+                let width = words_iter.next_or_empty().parse::<usize>();
+                match (height, separator.as_str(), width) {
+                    (Ok(height), "x", Ok(width)) if height > 0 && width > 0 => {
+                        check_resize_size(width, height)?;
+                        ResizeTarget::Size { width, height }
+                    }
+                    _ => return Err(MyError::new(format_err_msg)),
+                }
+            }
+            ("Scale:", 1) => match words_iter.next_or_empty().parse::<f64>() {
+                Ok(scale) if scale > RESIZE_MAX_SCALE && scale.is_finite() => {
+                    return Err(MyError::new(format!(
+                        "Масштаб не может быть больше {}",
+                        RESIZE_MAX_SCALE
+                    )));
+                }
+                Ok(scale) if scale > 0_f64 && scale.is_finite() => ResizeTarget::Scale(scale),
+                _ => return Err(MyError::new(format_err_msg)),
+            },
+            _ => return Err(MyError::new(format_err_msg)),
+        };
+
+        Ok(target)
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            ResizeTarget::Size { width, height } => format!("Size: {} x {}", height, width),
+            ResizeTarget::Scale(scale) => format!("Scale: {}", scale),
+        }
+    }
+}
+
+fn check_resize_size(width: usize, height: usize) -> Result<(), MyError> {
+    match width.checked_mul(height) {
+        Some(pixels_count) if pixels_count <= RESIZE_MAX_PIXELS => Ok(()),
+        _ => Err(MyError::new(format!(
+            "Размер результата {}x{} больше допустимого ({} пикселей)",
+            height, width, RESIZE_MAX_PIXELS
+        ))),
+    }
+}
+
+// counterclockwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RotationAngle {
+    pub degrees: f64,
+}
+impl RotationAngle {
+    pub fn new(degrees: f64) -> Self {
+        RotationAngle { degrees }
+    }
+
+    // number of counterclockwise quarter turns if the angle is a multiple of 90 degrees
+    pub fn quarter_turns(&self) -> Option<usize> {
+        let turns = self.degrees / 90_f64;
+        if turns.fract() == 0_f64 {
+            Some(turns.rem_euclid(4_f64) as usize)
+        } else {
+            None
+        }
+    }
+}
+impl Parceable for RotationAngle {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат угла: 'Angle: <угол в градусах>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Angle:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(degrees) if degrees.is_finite() => Ok(RotationAngle::new(degrees)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Angle: {}", self.degrees)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlipDirection {
+    Horizontal,
+    Vertical,
+}
+impl FlipDirection {
+    pub fn get_description(&self) -> String {
+        match self {
+            FlipDirection::Horizontal => "по горизонтали",
+            FlipDirection::Vertical => "по вертикали",
+        }
+        .to_string()
+    }
+}
+impl Parceable for FlipDirection {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат отражения: 'Flip: horizontal' или 'Flip: vertical'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Flip:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty() {
+            "horizontal" => Ok(FlipDirection::Horizontal),
+            "vertical" => Ok(FlipDirection::Vertical),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            FlipDirection::Horizontal => "Flip: horizontal".to_string(),
+            FlipDirection::Vertical => "Flip: vertical".to_string(),
+        }
+    }
+}
+
+impl Parceable for ColorSpace {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат цветового пространства: 'Color space: <rgb, hsv, hsl, ycbcr или lab>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 3 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Color" || words_iter.next_or_empty() != "space:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let color_space = match words_iter.next_or_empty() {
+            "rgb" => ColorSpace::Rgb,
+            "hsv" => ColorSpace::Hsv,
+            "hsl" => ColorSpace::Hsl,
+            "ycbcr" => ColorSpace::YCbCr,
+            "lab" => ColorSpace::Lab,
+            _ => return Err(MyError::new(format_err_msg)),
+        };
+
+        Ok(color_space)
+    }
+
+    fn content_to_string(&self) -> String {
+        let name = match self {
+            ColorSpace::Rgb => "rgb",
+            ColorSpace::Hsv => "hsv",
+            ColorSpace::Hsl => "hsl",
+            ColorSpace::YCbCr => "ycbcr",
+            ColorSpace::Lab => "lab",
+        };
+        format!("Color space: {}", name)
+    }
+}
+
+// how the gain of a frequency filter falls off around the cutoff frequency
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrequencyResponse {
+    Ideal,
+    Butterworth { order: u32 },
+    Gaussian,
+}
+impl FrequencyResponse {
+    // gain of the low-pass filter at the distance 'freq' from the zero frequency
+    pub fn low_pass_gain(&self, freq: f64, cutoff: f64) -> f64 {
+        match self {
+            FrequencyResponse::Ideal => {
+                if freq <= cutoff {
+                    1_f64
+                } else {
+                    0_f64
+                }
+            }
+            FrequencyResponse::Butterworth { order } => {
+                1_f64 / (1_f64 + (freq / cutoff).powi(2 * *order as i32))
+            }
+            FrequencyResponse::Gaussian => (-freq * freq / (2_f64 * cutoff * cutoff)).exp(),
+        }
+    }
+
+    pub fn get_description(&self) -> String {
+        match self {
+            FrequencyResponse::Ideal => "идеальный".to_string(),
+            FrequencyResponse::Butterworth { order } => {
+                format!("Баттерворта порядка {}", order)
+            }
+            FrequencyResponse::Gaussian => "гауссовский".to_string(),
+        }
+    }
+}
+impl Parceable for FrequencyResponse {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат характеристики: 'Response: ideal', 'Response: gaussian' или 'Response: butterworth <порядок от 1 до 10>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.next_or_empty() != "Response:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let response_name = words_iter.next_or_empty().to_string();
+        let args_count = words_iter.len();
+
+        let response = match (response_name.as_str(), args_count) {
+            ("ideal", 0) => FrequencyResponse::Ideal,
+            ("gaussian", 0) => FrequencyResponse::Gaussian,
+            ("butterworth", 1) => match words_iter.next_or_empty().parse::<u32>() {
+                Ok(order) if (1..=10).contains(&order) => FrequencyResponse::Butterworth { order },
+                _ => return Err(MyError::new(format_err_msg)),
+            },
+            _ => return Err(MyError::new(format_err_msg)),
+        };
+
+        Ok(response)
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            FrequencyResponse::Ideal => "Response: ideal".to_string(),
+            FrequencyResponse::Butterworth { order } => format!("Response: butterworth {}", order),
+            FrequencyResponse::Gaussian => "Response: gaussian".to_string(),
+        }
+    }
+}
+
+// cutoff frequencies are in cycles per pixel, the highest frequency along a side is 0.5
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrequencyBand {
+    LowPass { cutoff: f64 },
+    HighPass { cutoff: f64 },
+    BandPass { low: f64, high: f64 },
+}
+impl FrequencyBand {
+    pub fn gain(&self, response: FrequencyResponse, freq: f64) -> f64 {
+        match self {
+            FrequencyBand::LowPass { cutoff } => response.low_pass_gain(freq, *cutoff),
+            FrequencyBand::HighPass { cutoff } => 1_f64 - response.low_pass_gain(freq, *cutoff),
+            FrequencyBand::BandPass { low, high } => {
+                response.low_pass_gain(freq, *high) * (1_f64 - response.low_pass_gain(freq, *low))
+            }
+        }
+    }
+
+    pub fn get_description(&self) -> String {
+        match self {
+            FrequencyBand::LowPass { cutoff } => format!("нижних частот до {}", cutoff),
+            FrequencyBand::HighPass { cutoff } => format!("верхних частот от {}", cutoff),
+            FrequencyBand::BandPass { low, high } => format!("полосовой {} - {}", low, high),
+        }
+    }
+}
+impl Parceable for FrequencyBand {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат полосы: 'Band: low <частота среза>', 'Band: high <частота среза>' или 'Band: pass <нижняя частота> <верхняя частота>', частоты - положительные дробные числа (периодов на пиксель)".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.next_or_empty() != "Band:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let band_name = words_iter.next_or_empty().to_string();
+
+        let mut freqs = Vec::<f64>::new();
+        while words_iter.len() > 0 {
+            match words_iter.next_or_empty().parse::<f64>() {
+                Ok(freq) if freq.is_finite() && freq > 0_f64 => freqs.push(freq),
+                _ => return Err(MyError::new(format_err_msg)),
+            }
+        }
+
+        let band = match (band_name.as_str(), freqs.as_slice()) {
+            ("low", [cutoff]) => FrequencyBand::LowPass { cutoff: *cutoff },
+            ("high", [cutoff]) => FrequencyBand::HighPass { cutoff: *cutoff },
+            ("pass", [low, high]) if low < high => FrequencyBand::BandPass {
+                low: *low,
+                high: *high,
+            },
+            _ => return Err(MyError::new(format_err_msg)),
+        };
+
+        Ok(band)
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            FrequencyBand::LowPass { cutoff } => format!("Band: low {}", cutoff),
+            FrequencyBand::HighPass { cutoff } => format!("Band: high {}", cutoff),
+            FrequencyBand::BandPass { low, high } => format!("Band: pass {} {}", low, high),
+        }
+    }
+}
+
+// standard deviation of a gaussian in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sigma {
+    pub value: f64,
+}
+impl Sigma {
+    pub fn new(value: f64) -> Self {
+        assert!(value > 0_f64);
+        Sigma { value }
+    }
+}
+impl Parceable for Sigma {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат сигмы: 'Sigma: <дробное число от 0.1 до 20>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Sigma:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if (0.1..=20_f64).contains(&value) => Ok(Sigma::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Sigma: {}", self.value)
+    }
+}
+
+// 3x3 kernels of the derivatives, Y goes down like the rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientOperator {
+    Sobel,
+    Scharr,
+    Prewitt,
+}
+impl GradientOperator {
+    pub fn x_kernel(&self) -> Vec<f64> {
+        let (side, center) = match self {
+            GradientOperator::Sobel => (1_f64, 2_f64),
+            GradientOperator::Scharr => (3_f64, 10_f64),
+            GradientOperator::Prewitt => (1_f64, 1_f64),
+        };
+        vec![
+            -side, 0_f64, side, -center, 0_f64, center, -side, 0_f64, side,
+        ]
+    }
+
+    pub fn y_kernel(&self) -> Vec<f64> {
+        let x_kernel = self.x_kernel();
+        (0..9).map(|ind| x_kernel[ind % 3 * 3 + ind / 3]).collect()
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            GradientOperator::Sobel => "Собель",
+            GradientOperator::Scharr => "Шарр",
+            GradientOperator::Prewitt => "Превитт",
+        }
+    }
+}
+impl Parceable for GradientOperator {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат оператора градиента: 'Operator: <sobel, scharr или prewitt>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Operator:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty() {
+            "sobel" => Ok(GradientOperator::Sobel),
+            "scharr" => Ok(GradientOperator::Scharr),
+            "prewitt" => Ok(GradientOperator::Prewitt),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        let name = match self {
+            GradientOperator::Sobel => "sobel",
+            GradientOperator::Scharr => "scharr",
+            GradientOperator::Prewitt => "prewitt",
+        };
+        format!("Operator: {}", name)
+    }
+}
+
+// hysteresis thresholds relative to the max gradient magnitude,
+// the automatic ones are taken from the gradient magnitude histogram
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CannyThresholds {
+    Auto,
+    Given { low: f64, high: f64 },
+}
+impl CannyThresholds {
+    pub fn get_description(&self) -> String {
+        match self {
+            CannyThresholds::Auto => "пороги автоматически".to_string(),
+            CannyThresholds::Given { low, high } => format!("пороги {} - {}", low, high),
+        }
+    }
+}
+impl Parceable for CannyThresholds {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат порогов: 'Thresholds: auto' или 'Thresholds: <нижний> <верхний>', пороги - дробные числа от 0 до 1 относительно максимума градиента, нижний не больше верхнего".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.next_or_empty() != "Thresholds:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.len() {
+            1 if words_iter.next_or_empty() == "auto" => Ok(CannyThresholds::Auto),
+            2 => {
+                let low = words_iter.next_or_empty().parse::<f64>();
+                let high = words_iter.next_or_empty().parse::<f64>();
+                match (low, high) {
+                    (Ok(low), Ok(high)) if 0_f64 <= low && low <= high && high <= 1_f64 => {
+                        Ok(CannyThresholds::Given { low, high })
+                    }
+                    _ => Err(MyError::new(format_err_msg)),
+                }
+            }
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            CannyThresholds::Auto => "Thresholds: auto".to_string(),
+            CannyThresholds::Given { low, high } => format!("Thresholds: {} {}", low, high),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientOutput {
+    X,
+    Y,
+    Magnitude,
+    Orientation,
+}
+impl GradientOutput {
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            GradientOutput::X => "по X",
+            GradientOutput::Y => "по Y",
+            GradientOutput::Magnitude => "модуль",
+            GradientOutput::Orientation => "направление",
+        }
+    }
+}
+impl Parceable for GradientOutput {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат результата: 'Output: <x, y, magnitude или orientation>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Output:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty() {
+            "x" => Ok(GradientOutput::X),
+            "y" => Ok(GradientOutput::Y),
+            "magnitude" => Ok(GradientOutput::Magnitude),
+            "orientation" => Ok(GradientOutput::Orientation),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        let name = match self {
+            GradientOutput::X => "x",
+            GradientOutput::Y => "y",
+            GradientOutput::Magnitude => "magnitude",
+            GradientOutput::Orientation => "orientation",
+        };
+        format!("Output: {}", name)
+    }
+}
+
+// how a result that may be negative or exceed the max value is made displayable:
+//   linear - the possible range of the result is mapped onto [0; max value],
+//            for the signed results zero becomes the middle gray,
+//   absolute - the absolute value relative to the possible one is mapped onto [0; max value],
+//   clamped - the values are cut to [0; max value] as they are,
+//   stretched - the actual range of the result is mapped onto [0; max value]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueMapping {
+    Linear,
+    Absolute,
+    Clamped,
+    Stretched,
+}
+impl ValueMapping {
+    pub fn map_values(&self, mat: &mut Matrix2D, possible_range: (f64, f64), max_value: f64) {
+        let (from, to) = match self {
+            ValueMapping::Linear => possible_range,
+            ValueMapping::Absolute => {
+                let abs_max = possible_range.0.abs().max(possible_range.1.abs());
+                for val in mat.vals_mut() {
+                    *val = val.abs();
+                }
+                (0_f64, abs_max)
+            }
+            ValueMapping::Clamped => (0_f64, max_value),
+            ValueMapping::Stretched => mat
+                .vals()
+                .iter()
+                .fold((f64::MAX, f64::MIN), |(min, max), val| {
+                    (min.min(*val), max.max(*val))
+                }),
+        };
+
+        let scale = if to > from {
+            max_value / (to - from)
+        } else {
+            0_f64
+        };
+
+        for val in mat.vals_mut() {
+            *val = ((*val - from) * scale).clamp(0_f64, max_value);
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            ValueMapping::Linear => "линейно",
+            ValueMapping::Absolute => "по модулю",
+            ValueMapping::Clamped => "с обрезкой",
+            ValueMapping::Stretched => "с растяжением",
+        }
+    }
+}
+impl Parceable for ValueMapping {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат отображения значений: 'Mapping: <linear, absolute, clamped или stretched>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Mapping:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty() {
+            "linear" => Ok(ValueMapping::Linear),
+            "absolute" => Ok(ValueMapping::Absolute),
+            "clamped" => Ok(ValueMapping::Clamped),
+            "stretched" => Ok(ValueMapping::Stretched),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        let name = match self {
+            ValueMapping::Linear => "linear",
+            ValueMapping::Absolute => "absolute",
+            ValueMapping::Clamped => "clamped",
+            ValueMapping::Stretched => "stretched",
+        };
+        format!("Mapping: {}", name)
+    }
+}
+
+// the threshold is relative to the max absolute value of the result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZeroCrossings {
+    Off,
+    Threshold(f64),
+}
+impl Parceable for ZeroCrossings {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат пересечений нуля: 'Zero crossings: off' или 'Zero crossings: <порог от 0 до 1>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 3 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Zero" || words_iter.next_or_empty() != "crossings:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty() {
+            "off" => Ok(ZeroCrossings::Off),
+            word => match word.parse::<f64>() {
+                Ok(val) if (0_f64..=1_f64).contains(&val) => Ok(ZeroCrossings::Threshold(val)),
+                _ => Err(MyError::new(format_err_msg)),
+            },
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            ZeroCrossings::Off => "Zero crossings: off".to_string(),
+            ZeroCrossings::Threshold(val) => format!("Zero crossings: {}", val),
+        }
+    }
+}
+
+// sigma of the difference of values in the bilateral filter, relative to the max value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeSigma {
+    pub value: f64,
+}
+impl RangeSigma {
+    pub fn new(value: f64) -> Self {
+        assert!(value > 0_f64);
+        RangeSigma { value }
+    }
+}
+impl Parceable for RangeSigma {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат сигмы яркости: 'Range sigma: <дробное число больше 0 и не больше 1>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 3 {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        if words_iter.next_or_empty() != "Range" || words_iter.next_or_empty() != "sigma:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if value > 0_f64 && value <= 1_f64 => Ok(RangeSigma::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Range sigma: {}", self.value)
+    }
+}
+
+// the filtering strength h of the non-local means, relative to the max value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DenoiseStrength {
+    pub value: f64,
+}
+impl DenoiseStrength {
+    pub fn new(value: f64) -> Self {
+        assert!(value > 0_f64);
+        DenoiseStrength { value }
+    }
+}
+impl Parceable for DenoiseStrength {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат силы фильтрации: 'Strength: <дробное число больше 0 и не больше 1>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Strength:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if value > 0_f64 && value <= 1_f64 => Ok(DenoiseStrength::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Strength: {}", self.value)
+    }
+}
+
+// factor of the added difference between the image and its blurred copy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SharpenAmount {
+    pub value: f64,
+}
+impl SharpenAmount {
+    pub fn new(value: f64) -> Self {
+        assert!(value > 0_f64);
+        SharpenAmount { value }
+    }
+}
+impl Parceable for SharpenAmount {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат силы резкости: 'Amount: <дробное число больше 0 и не больше 10>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Amount:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if value > 0_f64 && value <= 10_f64 => Ok(SharpenAmount::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Amount: {}", self.value)
+    }
+}
+
+// the smaller differences from the blurred copy are left as they are,
+// relative to the max value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SharpenThreshold {
+    pub value: f64,
+}
+impl SharpenThreshold {
+    pub fn new(value: f64) -> Self {
+        assert!((0_f64..1_f64).contains(&value));
+        SharpenThreshold { value }
+    }
+}
+impl Parceable for SharpenThreshold {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат порога резкости: 'Threshold: <дробное число от 0 до 1, не включая 1>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Threshold:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if (0_f64..1_f64).contains(&value) => Ok(SharpenThreshold::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Threshold: {}", self.value)
+    }
+}
+
+// luminance - only the Y of YCbCr is processed for the color images, so the colors stay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SharpenChannels {
+    All,
+    Luminance,
+}
+impl SharpenChannels {
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            SharpenChannels::All => "все каналы",
+            SharpenChannels::Luminance => "только яркость",
+        }
+    }
+}
+impl Parceable for SharpenChannels {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат каналов: 'Channels: <all или luminance>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Channels:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty() {
+            "all" => Ok(SharpenChannels::All),
+            "luminance" => Ok(SharpenChannels::Luminance),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        let name = match self {
+            SharpenChannels::All => "all",
+            SharpenChannels::Luminance => "luminance",
+        };
+        format!("Channels: {}", name)
+    }
+}
+
+// the values relative to the max value are raised to the power of 1 / gamma,
+// so the gamma above 1 makes the image lighter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gamma {
+    pub value: f64,
+}
+impl Gamma {
+    pub fn new(value: f64) -> Self {
+        assert!(value > 0_f64);
+        Gamma { value }
+    }
+}
+impl Parceable for Gamma {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат гаммы: 'Gamma: <дробное число больше 0 и не больше 10>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Gamma:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if value > 0_f64 && value <= 10_f64 => Ok(Gamma::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Gamma: {}", self.value)
+    }
+}
+
+// factor of the distance from the middle gray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contrast {
+    pub value: f64,
+}
+impl Contrast {
+    pub fn new(value: f64) -> Self {
+        assert!(value >= 0_f64);
+        Contrast { value }
+    }
+}
+impl Parceable for Contrast {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат контраста: 'Contrast: <дробное число от 0 до 10 включительно>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Contrast:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if (0_f64..=10_f64).contains(&value) => Ok(Contrast::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Contrast: {}", self.value)
+    }
+}
+
+// the added value, relative to the max value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brightness {
+    pub value: f64,
+}
+impl Brightness {
+    pub fn new(value: f64) -> Self {
+        assert!((-1_f64..=1_f64).contains(&value));
+        Brightness { value }
+    }
+}
+impl Parceable for Brightness {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат яркости: 'Brightness: <дробное число от -1 до 1 включительно>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 2 || words_iter.next_or_empty() != "Brightness:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match words_iter.next_or_empty().parse::<f64>() {
+            Ok(value) if (-1_f64..=1_f64).contains(&value) => Ok(Brightness::new(value)),
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Brightness: {}", self.value)
+    }
+}
+
+// the values below the black point become black, the ones above the white point - white,
+// relative to the max value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputLevels {
+    pub black: f64,
+    pub white: f64,
+}
+impl InputLevels {
+    pub fn new(black: f64, white: f64) -> Self {
+        assert!(0_f64 <= black && black < white && white <= 1_f64);
+        InputLevels { black, white }
+    }
+}
+impl Parceable for InputLevels {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат входных уровней: 'Input: <точка черного> - <точка белого>', дробные числа от 0 до 1, черный меньше белого".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 4 || words_iter.next_or_empty() != "Input:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let black = words_iter.next_or_empty().parse::<f64>();
+        let dash = words_iter.next_or_empty().to_string();
+        let white = words_iter.next_or_empty().parse::<f64>();
+
+        match (black, dash.as_str(), white) {
+            (Ok(black), "-", Ok(white)) if 0_f64 <= black && black < white && white <= 1_f64 => {
+                Ok(InputLevels::new(black, white))
+            }
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Input: {} - {}", self.black, self.white)
+    }
+}
+
+// the range the black and white points are mapped onto, relative to the max value;
+// the low end may be above the high one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputLevels {
+    pub low: f64,
+    pub high: f64,
+}
+impl OutputLevels {
+    pub fn new(low: f64, high: f64) -> Self {
+        assert!((0_f64..=1_f64).contains(&low) && (0_f64..=1_f64).contains(&high));
+        OutputLevels { low, high }
+    }
+}
+impl Parceable for OutputLevels {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат выходных уровней: 'Output: <нижний> - <верхний>', дробные числа от 0 до 1"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.len() != 4 || words_iter.next_or_empty() != "Output:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let low = words_iter.next_or_empty().parse::<f64>();
+        let dash = words_iter.next_or_empty().to_string();
+        let high = words_iter.next_or_empty().parse::<f64>();
+
+        let unit_range = 0_f64..=1_f64;
+        match (low, dash.as_str(), high) {
+            (Ok(low), "-", Ok(high)) if unit_range.contains(&low) && unit_range.contains(&high) => {
+                Ok(OutputLevels::new(low, high))
+            }
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        format!("Output: {} - {}", self.low, self.high)
+    }
+}
+
+// control points (input, output) of a piecewise-linear curve, relative to the max value;
+// the values outside of the first and last inputs are mapped as those inputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurvePoints {
+    points: Vec<(f64, f64)>,
+}
+impl CurvePoints {
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        assert!(points.len() >= 2);
+        assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        CurvePoints { points }
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    pub fn map(&self, val: f64) -> f64 {
+        // NaN has no place on the curve, so the non-finite values are kept as they are
+        if !val.is_finite() {
+            return val;
+        }
+
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        if val <= first.0 {
+            return first.1;
+        }
+        if val >= last.0 {
+            return last.1;
+        }
+
+        let right = self.points.iter().position(|p| p.0 > val).unwrap();
+        let (x0, y0) = self.points[right - 1];
+        let (x1, y1) = self.points[right];
+        y0 + (y1 - y0) * (val - x0) / (x1 - x0)
+    }
+}
+impl Parceable for CurvePoints {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат кривой: 'Curve: <вход> <выход>, <вход> <выход>, ...', не меньше 2 точек, дробные числа от 0 до 1, входы по возрастанию".to_string();
+
+        let line = lines_iter.next_or_empty();
+        let points_str = match line.strip_prefix("Curve:") {
+            Some(points_str) => points_str,
+            None => return Err(MyError::new(format_err_msg)),
+        };
+
+        let unit_range = 0_f64..=1_f64;
+        let mut points = Vec::<(f64, f64)>::new();
+        for point_str in points_str.split(',') {
+            let mut words_iter = WordsIter::new(point_str, " ");
+            if words_iter.len() != 2 {
+                return Err(MyError::new(format_err_msg));
+            }
+
+            let input = words_iter.next_or_empty().parse::<f64>();
+            let output = words_iter.next_or_empty().parse::<f64>();
+            match (input, output) {
+                (Ok(input), Ok(output))
+                    if unit_range.contains(&input) && unit_range.contains(&output) =>
+                {
+                    points.push((input, output))
+                }
+                _ => return Err(MyError::new(format_err_msg)),
+            }
+        }
+
+        if points.len() < 2 || points.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        Ok(CurvePoints::new(points))
+    }
+
+    fn content_to_string(&self) -> String {
+        let points: Vec<String> = self
+            .points
+            .iter()
+            .map(|(input, output)| format!("{} {}", input, output))
+            .collect();
+        format!("Curve: {}", points.join(", "))
+    }
+}
+
+// the image a step takes besides the result of the previous one,
+// the steps are numbered from 0 here and from 1 in the text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepRef {
+    Initial,
+    Step(usize),
+}
+impl StepRef {
+    pub fn get_description(&self) -> String {
+        match self {
+            StepRef::Initial => "исходным изображением".to_string(),
+            StepRef::Step(step_num) => format!("шагом {}", step_num + 1),
+        }
+    }
+}
+impl Parceable for StepRef {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg =
+            "Формат второго изображения: 'With: <initial или step N, где N - номер шага от 1>'"
+                .to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.next_or_empty() != "With:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        match (words_iter.len(), words_iter.next_or_empty()) {
+            (1, "initial") => Ok(StepRef::Initial),
+            (2, "step") => match words_iter.next_or_empty().parse::<usize>() {
+                Ok(step_num) if step_num >= 1 => Ok(StepRef::Step(step_num - 1)),
+                _ => Err(MyError::new(format_err_msg)),
+            },
+            _ => Err(MyError::new(format_err_msg)),
+        }
+    }
+
+    fn content_to_string(&self) -> String {
+        match self {
+            StepRef::Initial => "With: initial".to_string(),
+            StepRef::Step(step_num) => format!("With: step {}", step_num + 1),
+        }
+    }
+}
+
+// a is the processed image, b is the other one:
+//   blend - (1 - alpha) * a + alpha * b,
+//   mask - a is multiplied by b relative to the max value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CombineOp {
+    Add,
+    Subtract,
+    Multiply,
+    Difference,
+    Min,
+    Max,
+    Blend { alpha: f64 },
+    Mask,
+}
+impl CombineOp {
+    // a and b are relative to the max value
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            CombineOp::Add => a + b,
+            CombineOp::Subtract => a - b,
+            CombineOp::Multiply => a * b,
+            CombineOp::Difference => (a - b).abs(),
+            CombineOp::Min => a.min(b),
+            CombineOp::Max => a.max(b),
+            CombineOp::Blend { alpha } => (1_f64 - alpha) * a + alpha * b,
+            // the other image selects the pixels to keep
+            CombineOp::Mask if b > 0_f64 => a,
+            CombineOp::Mask => 0_f64,
+        }
+    }
+
+    // relative to the max value
+    pub fn possible_range(&self) -> (f64, f64) {
+        match self {
+            CombineOp::Add => (0_f64, 2_f64),
+            CombineOp::Subtract => (-1_f64, 1_f64),
+            _ => (0_f64, 1_f64),
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            CombineOp::Add => "Сложение",
+            CombineOp::Subtract => "Вычитание",
+            CombineOp::Multiply => "Умножение",
+            CombineOp::Difference => "Модуль разности",
+            CombineOp::Min => "Минимум",
+            CombineOp::Max => "Максимум",
+            CombineOp::Blend { .. } => "Смешивание",
+            CombineOp::Mask => "Маска",
+        }
+    }
+}
+impl Parceable for CombineOp {
+    fn try_from_string(string: &str) -> Result<Self, MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        assert_eq!(lines_iter.len(), 1);
+
+        let format_err_msg = "Формат операции: 'Operation: <add, subtract, multiply, difference, min, max, mask или blend A, где A - доля второго изображения от 0 до 1>'".to_string();
+
+        let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), " ");
+        if words_iter.next_or_empty() != "Operation:" {
+            return Err(MyError::new(format_err_msg));
+        }
+
+        let op = match (words_iter.len(), words_iter.next_or_empty()) {
+            (1, "add") => CombineOp::Add,
+            (1, "subtract") => CombineOp::Subtract,
+            (1, "multiply") => CombineOp::Multiply,
+            (1, "difference") => CombineOp::Difference,
+            (1, "min") => CombineOp::Min,
+            (1, "max") => CombineOp::Max,
+            (1, "mask") => CombineOp::Mask,
+            (2, "blend") => match words_iter.next_or_empty().parse::<f64>() {
+                Ok(alpha) if (0_f64..=1_f64).contains(&alpha) => CombineOp::Blend { alpha },
+                _ => return Err(MyError::new(format_err_msg)),
+            },
+            _ => return Err(MyError::new(format_err_msg)),
+        };
+
+        Ok(op)
+    }
+
+    fn content_to_string(&self) -> String {
+        let name = match self {
+            CombineOp::Add => "add".to_string(),
+            CombineOp::Subtract => "subtract".to_string(),
+            CombineOp::Multiply => "multiply".to_string(),
+            CombineOp::Difference => "difference".to_string(),
+            CombineOp::Min => "min".to_string(),
+            CombineOp::Max => "max".to_string(),
+            CombineOp::Blend { alpha } => format!("blend {}", alpha),
+            CombineOp::Mask => "mask".to_string(),
+        };
+        format!("Operation: {}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CurvePoints, ExtendValue, Parceable};
+    use crate::img::{
+        filter::{
+            linear::{LinearCustom, LinearGaussian, LinearMean},
+            non_linear::{HistogramLocalContrast, MedianFilter, Morphology},
+            test_utils::run_checked,
+            FilterBase,
+        },
+        Img,
+    };
+    use fltk::enums::ColorDepth;
+
+    #[test]
+    fn window_filters_accept_all_border_modes() {
+        let modes = [
+            ExtendValue::Closest,
+            ExtendValue::Given(128_f64),
+            ExtendValue::Given(0.5),
+            ExtendValue::Reflect,
+            ExtendValue::Reflect101,
+            ExtendValue::Wrap,
+        ];
+
+        let filters: Vec<FilterBase> = vec![
+            Box::new(LinearCustom::default()) as FilterBase,
+            Box::new(LinearGaussian::default()) as FilterBase,
+            Box::new(LinearMean::default()) as FilterBase,
+            Box::new(MedianFilter::default()) as FilterBase,
+            Box::new(Morphology::default()) as FilterBase,
+            Box::new(HistogramLocalContrast::default()) as FilterBase,
+        ];
+
+        let img = Img::empty_with_size(10, 7, ColorDepth::Rgb8);
+
+        for mode in modes.iter() {
+            let mode_str = mode.content_to_string();
+            assert_eq!(ExtendValue::try_from_string(&mode_str).unwrap(), *mode);
+
+            for filter in filters.iter() {
+                let params = filter
+                    .params_to_string()
+                    .unwrap()
+                    .replace("Ext: near", &mode_str);
+                assert!(params.contains(&mode_str), "{}", filter.get_save_name());
+
+                let mut filter = filter.get_copy();
+                filter.try_set_from_string(&params).unwrap();
+                assert_eq!(filter.params_to_string().unwrap(), params);
+
+                run_checked(filter.as_ref(), &img);
+            }
+        }
+
+        assert!(ExtendValue::try_from_string("Ext: mirror").is_err());
+    }
+
+    #[test]
+    fn curve_keeps_non_finite_values() {
+        let curve = CurvePoints::new(vec![(0.2, 0_f64), (0.6, 1_f64), (1_f64, 0.5)]);
+        assert_eq!(curve.map(0.1), 0_f64);
+        assert!((curve.map(0.4) - 0.5).abs() < 1e-9);
+        assert_eq!(curve.map(2_f64), 0.5);
+
+        assert!(curve.map(f64::NAN).is_nan());
+        assert_eq!(curve.map(f64::INFINITY), f64::INFINITY);
+        assert_eq!(curve.map(f64::NEG_INFINITY), f64::NEG_INFINITY);
+    }
+}
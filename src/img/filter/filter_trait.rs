@@ -2,7 +2,10 @@ use crate::my_err::MyError;
 use crate::processing::{ExecutorHandle, TaskStop};
 
 use super::super::Img;
-use super::{filter_option::ExtendValue, FilterBase, FilterIterator};
+use super::{
+    filter_option::{ExtendValue, StepRef},
+    FilterBase, FilterIterator,
+};
 
 pub trait StringFromTo {
     fn params_to_string(&self) -> Option<String>;
@@ -15,12 +18,38 @@ pub trait Filter: StringFromTo + Send {
     fn get_description(&self) -> String;
     fn get_save_name(&self) -> String;
     fn get_copy(&self) -> FilterBase;
+
+    // the images the filter takes besides the result of the previous step
+    fn get_inputs(&self) -> Vec<StepRef> {
+        Vec::new()
+    }
+
+    // `inputs` are in the same order as in `get_inputs`, used when the steps are renumbered
+    fn set_inputs(&mut self, _inputs: Vec<StepRef>) {}
+
+    // `inputs` are the images of `get_inputs` in the same order
+    fn process_with_inputs(
+        &self,
+        img: &Img,
+        _inputs: &[&Img],
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Img, TaskStop> {
+        self.process(img, executor_handle)
+    }
 }
 
-pub trait WindowFilter: Filter {
+pub trait WindowFilter: Filter + Sync {
     fn process_window(&self, window_buffer: &mut [f64]) -> f64;
     fn w(&self) -> usize;
     fn h(&self) -> usize;
     fn get_extend_value(&self) -> ExtendValue;
     fn get_iter(&self) -> FilterIterator;
 }
+
+// the window is a product of two 1-D kernels, so it can be applied
+// as a pass along rows followed by a pass along columns
+pub trait SeparableFilter: WindowFilter {
+    // res[i] is counted from line[i..i + kernel_len],
+    // where kernel_len == line.len() - res.len() + 1
+    fn process_line(&self, line: &[f64], res: &mut [f64]);
+}
@@ -0,0 +1,163 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::{process_each_layer, ByLayer, FilterBase};
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// the layer spectrum is multiplied by the gain of the band for each frequency,
+// the image is treated as periodic
+#[derive(Clone)]
+pub struct FrequencyFilter {
+    band: FrequencyBand,
+    response: FrequencyResponse,
+    bit_depth: BitDepth,
+}
+
+impl FrequencyFilter {
+    pub fn new(band: FrequencyBand, response: FrequencyResponse) -> Self {
+        FrequencyFilter {
+            band,
+            response,
+            bit_depth: BitDepth::default(),
+        }
+    }
+}
+
+impl Filter for FrequencyFilter {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = FrequencyFilter {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        // forward and inverse transforms
+        layers_count * Spectrum::actions_num(img.w(), img.h()) * 2
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Фильтр {} ({})",
+            self.band.get_description(),
+            self.response.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "FrequencyFilter".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for FrequencyFilter {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let mat = match layer.channel() {
+            ImgChannel::A => return Ok(layer.clone()),
+            _ => layer.matrix(),
+        };
+
+        let mut spectrum = Spectrum::of(mat, executor_handle)?;
+        spectrum.apply_gain(|freq| self.band.gain(self.response, freq));
+
+        let mut res = spectrum.to_matrix(executor_handle)?;
+        let max = self.bit_depth.max_value();
+        for val in res.vals_mut() {
+            *val = val.clamp(0_f64, max);
+        }
+
+        Ok(ImgLayer::new(res, layer.channel()))
+    }
+}
+
+impl StringFromTo for FrequencyFilter {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 2 {
+            return Err(MyError::new(
+                "Нужно ввести полосу частот и характеристику фильтра на следующей строке"
+                    .to_string(),
+            ));
+        }
+
+        let band = FrequencyBand::try_from_string(lines_iter.next_or_empty())?;
+        let response = FrequencyResponse::try_from_string(lines_iter.next_or_empty())?;
+
+        self.band = band;
+        self.response = response;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}",
+            self.band.content_to_string(),
+            self.response.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for FrequencyFilter {
+    fn default() -> Self {
+        FrequencyFilter::new(
+            FrequencyBand::LowPass { cutoff: 0.1 },
+            FrequencyResponse::Butterworth { order: 2 },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_gradient_img, run_checked};
+
+    #[test]
+    fn bands_keep_or_change_image() {
+        let img = create_gradient_img(23, 18, ColorDepth::L8, 31, 17);
+
+        let mut filter = FrequencyFilter::default();
+        for (params, keeps_img) in [
+            ("Band: low 1\nResponse: ideal", true),
+            ("Band: high 0.1\nResponse: gaussian", false),
+            ("Band: pass 0.05 0.2\nResponse: butterworth 3", false),
+        ]
+        .iter()
+        {
+            filter.try_set_from_string(params).unwrap();
+
+            let res = run_checked(&filter, &img);
+            let diff = res
+                .layer(0)
+                .matrix()
+                .vals()
+                .iter()
+                .zip(img.layer(0).matrix().vals())
+                .map(|(res, init)| (res - init).abs())
+                .fold(0_f64, f64::max);
+            assert_eq!(diff < 1e-6, *keeps_img, "{}", params);
+        }
+        assert!(filter
+            .try_set_from_string("Band: pass 0.2 0.1\nResponse: ideal")
+            .is_err());
+    }
+}
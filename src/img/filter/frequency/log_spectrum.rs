@@ -0,0 +1,110 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::{process_each_layer, ByLayer, FilterBase};
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use fltk::enums::ColorDepth;
+
+// ln(1 + |F|) of each layer with the zero frequency in the center,
+// scaled so that the highest value is the max value of the bit depth
+#[derive(Clone, Default)]
+pub struct LogSpectrum {
+    bit_depth: BitDepth,
+}
+
+impl Filter for LogSpectrum {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = LogSpectrum {
+            bit_depth: img.bit_depth(),
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        layers_count * Spectrum::actions_num(img.w(), img.h())
+    }
+
+    fn get_description(&self) -> String {
+        "Спектр (логарифм амплитуды)".to_string()
+    }
+
+    fn get_save_name(&self) -> String {
+        "LogSpectrum".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for LogSpectrum {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let mat = match layer.channel() {
+            ImgChannel::A => return Ok(layer.clone()),
+            _ => layer.matrix(),
+        };
+
+        let mut res = Spectrum::of(mat, executor_handle)?.log_magnitude_centered();
+
+        let res_max = res.vals().iter().cloned().fold(0_f64, f64::max);
+        if res_max > 0_f64 {
+            let scale = self.bit_depth.max_value() / res_max;
+            for val in res.vals_mut() {
+                *val *= scale;
+            }
+        }
+
+        Ok(ImgLayer::new(res, layer.channel()))
+    }
+}
+
+impl StringFromTo for LogSpectrum {
+    fn params_to_string(&self) -> Option<String> {
+        None
+    }
+
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        if string.trim().is_empty() {
+            Ok(())
+        } else {
+            Err(MyError::new("У данного фильтра нет настроек".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_img, run_checked};
+
+    #[test]
+    fn flat_image_has_only_zero_frequency() {
+        let flat = create_img(8, 6, ColorDepth::L8, |_, _| 100_f64);
+
+        let spectrum = run_checked(&LogSpectrum::default(), &flat);
+        for pos in spectrum.get_area().iter_pixels() {
+            let expected = if pos == PixelPos::new(3, 4) {
+                255_f64
+            } else {
+                0_f64
+            };
+            assert!(
+                (spectrum.layer(0)[pos] - expected).abs() < 1e-6,
+                "{:?}",
+                pos
+            );
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod frequency_filter;
+mod log_spectrum;
+
+pub use frequency_filter::FrequencyFilter;
+pub use log_spectrum::LogSpectrum;
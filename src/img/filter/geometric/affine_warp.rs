@@ -0,0 +1,168 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::warp::{warp_img, warp_steps_num};
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::{LinesIter, WordsIter};
+
+// maps the source pixel (x = col, y = row) to
+// x' = a * x + b * y + c,
+// y' = d * x + e * y + f,
+// the result has the size of the source
+#[derive(Clone)]
+pub struct AffineWarp {
+    coeffs: [f64; 6],
+    interpolation: Interpolation,
+    extend_value: ExtendValue,
+}
+
+impl AffineWarp {
+    pub fn new(
+        coeffs: [f64; 6],
+        interpolation: Interpolation,
+        extend_value: ExtendValue,
+    ) -> Result<Self, MyError> {
+        if determinant(&coeffs).abs() < f64::EPSILON {
+            return Err(MyError::new(
+                "Матрица преобразования вырождена, его нельзя обратить".to_string(),
+            ));
+        }
+
+        Ok(AffineWarp {
+            coeffs,
+            interpolation,
+            extend_value,
+        })
+    }
+}
+
+fn determinant(coeffs: &[f64; 6]) -> f64 {
+    coeffs[0] * coeffs[4] - coeffs[1] * coeffs[3]
+}
+
+impl Filter for AffineWarp {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let [a, b, c, d, e, f] = self.coeffs;
+        let det = determinant(&self.coeffs);
+
+        let map_to_src = |row: f64, col: f64| {
+            let x = col - c;
+            let y = row - f;
+            ((a * y - d * x) / det, (e * x - b * y) / det)
+        };
+
+        warp_img(
+            img,
+            img.w(),
+            img.h(),
+            map_to_src,
+            self.interpolation,
+            self.extend_value,
+            executor_handle,
+        )
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        warp_steps_num(img, img.h())
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Аффинное преобразование ({})",
+            self.interpolation.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "AffineWarp".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for AffineWarp {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 4 {
+            return Err(MyError::new(
+                "Нужно ввести 2 строки матрицы 2x3 и параметры на следующих строках".to_string(),
+            ));
+        }
+
+        let mut coeffs = [0_f64; 6];
+        for row in 0..2 {
+            let mut words_iter = WordsIter::new(lines_iter.next_or_empty(), ",");
+            if words_iter.len() != 3 {
+                return Err(MyError::new(
+                    "В строке матрицы должно быть 3 числа через запятую".to_string(),
+                ));
+            }
+            for col in 0..3 {
+                match words_iter.next_or_empty().parse::<f64>() {
+                    Ok(value) => coeffs[row * 3 + col] = value,
+                    Err(_) => {
+                        return Err(MyError::new("Некорректный формат чисел".to_string()));
+                    }
+                }
+            }
+        }
+
+        let interpolation = Interpolation::try_from_string(lines_iter.next_or_empty())?;
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        *self = AffineWarp::new(coeffs, interpolation, extend_value)?;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let row_to_string = |row: &[f64]| format!("{}, {}, {}", row[0], row[1], row[2]);
+
+        let params_str = format!(
+            "{}\n{}\n{}\n{}",
+            row_to_string(&self.coeffs[0..3]),
+            row_to_string(&self.coeffs[3..6]),
+            self.interpolation.content_to_string(),
+            self.extend_value.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for AffineWarp {
+    fn default() -> Self {
+        AffineWarp::new(
+            [1_f64, 0_f64, 0_f64, 0_f64, 1_f64, 0_f64],
+            Interpolation::Bilinear,
+            ExtendValue::Given(0_f64),
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_gradient_img, run_checked};
+    use fltk::enums::ColorDepth;
+
+    #[test]
+    fn shift_moves_pixels_and_singular_matrix_is_rejected() {
+        let mut shift = AffineWarp::default();
+        shift
+            .try_set_from_string("1, 0, 2\n0, 1, 0\nInterpolation: nearest\nExt: 0")
+            .unwrap();
+
+        let img = create_gradient_img(5, 3, ColorDepth::Rgb8, 10, 1);
+        let shifted = run_checked(&shift, &img);
+        assert_eq!(shifted.layer(0)[PixelPos::new(1, 1)], 0_f64);
+        assert_eq!(shifted.layer(0)[PixelPos::new(1, 3)], 11_f64);
+
+        let singular = "1, 2, 0\n2, 4, 0\nInterpolation: nearest\nExt: 0";
+        assert!(shift.try_set_from_string(singular).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::warp::{warp_img, warp_steps_num};
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+
+#[derive(Clone)]
+pub struct Flip {
+    direction: FlipDirection,
+}
+
+impl Flip {
+    pub fn new(direction: FlipDirection) -> Self {
+        Flip { direction }
+    }
+}
+
+impl Filter for Flip {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let last_row = img.h() as f64 - 1_f64;
+        let last_col = img.w() as f64 - 1_f64;
+        let direction = self.direction;
+
+        let map_to_src = |row: f64, col: f64| match direction {
+            FlipDirection::Horizontal => (row, last_col - col),
+            FlipDirection::Vertical => (last_row - row, col),
+        };
+
+        // every pixel is mapped exactly onto a source pixel
+        warp_img(
+            img,
+            img.w(),
+            img.h(),
+            map_to_src,
+            Interpolation::Nearest,
+            ExtendValue::Closest,
+            executor_handle,
+        )
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        warp_steps_num(img, img.h())
+    }
+
+    fn get_description(&self) -> String {
+        format!("Отражение ({})", self.direction.get_description())
+    }
+
+    fn get_save_name(&self) -> String {
+        "Flip".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for Flip {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 1 {
+            return Err(MyError::new("Должна быть 1 строка".to_string()));
+        }
+
+        self.direction = FlipDirection::try_from_string(lines_iter.next_or_empty())?;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        Some(self.direction.content_to_string())
+    }
+}
+
+impl Default for Flip {
+    fn default() -> Self {
+        Flip::new(FlipDirection::Horizontal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_gradient_img, run_checked};
+    use fltk::enums::ColorDepth;
+
+    #[test]
+    fn vertical_flip_reverses_rows() {
+        let img = create_gradient_img(5, 3, ColorDepth::Rgb8, 10, 1);
+
+        let flipped = run_checked(&Flip::new(FlipDirection::Vertical), &img);
+        for pos in flipped.get_area().iter_pixels() {
+            let src = PixelPos::new(2 - pos.row, pos.col);
+            assert_eq!(flipped.layer(0)[pos], img.layer(0)[src], "{:?}", pos);
+        }
+    }
+}
@@ -0,0 +1,10 @@
+mod affine_warp;
+mod flip;
+mod resize;
+mod rotate;
+mod warp;
+
+pub use affine_warp::AffineWarp;
+pub use flip::Flip;
+pub use resize::Resize;
+pub use rotate::Rotate;
@@ -0,0 +1,155 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::warp::{warp_img, warp_steps_num};
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+
+#[derive(Clone)]
+pub struct Resize {
+    target: ResizeTarget,
+    interpolation: Interpolation,
+    extend_value: ExtendValue,
+}
+
+impl Resize {
+    pub fn new(
+        target: ResizeTarget,
+        interpolation: Interpolation,
+        extend_value: ExtendValue,
+    ) -> Self {
+        Resize {
+            target,
+            interpolation,
+            extend_value,
+        }
+    }
+}
+
+impl Filter for Resize {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let (res_w, res_h) = self.target.try_size_for(img.w(), img.h())?;
+
+        let scale_row = img.h() as f64 / res_h as f64;
+        let scale_col = img.w() as f64 / res_w as f64;
+
+        // pixel centers of the result and the source are aligned
+        let map_to_src =
+            |row: f64, col: f64| ((row + 0.5) * scale_row - 0.5, (col + 0.5) * scale_col - 0.5);
+
+        warp_img(
+            img,
+            res_w,
+            res_h,
+            map_to_src,
+            self.interpolation,
+            self.extend_value,
+            executor_handle,
+        )
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let (_, res_h) = self.target.size_for(img.w(), img.h());
+        warp_steps_num(img, res_h)
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Изменение размера ({}, {})",
+            self.target.get_description(),
+            self.interpolation.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "Resize".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for Resize {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 3 {
+            return Err(MyError::new("Должно быть 3 строки".to_string()));
+        }
+
+        let target = ResizeTarget::try_from_string(lines_iter.next_or_empty())?;
+        let interpolation = Interpolation::try_from_string(lines_iter.next_or_empty())?;
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        self.target = target;
+        self.interpolation = interpolation;
+        self.extend_value = extend_value;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}",
+            self.target.content_to_string(),
+            self.interpolation.content_to_string(),
+            self.extend_value.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for Resize {
+    fn default() -> Self {
+        Resize::new(
+            ResizeTarget::Scale(0.5),
+            Interpolation::Bilinear,
+            ExtendValue::Closest,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_gradient_img, run_checked};
+    use crate::processing::create_task_info_channel;
+    use fltk::enums::ColorDepth;
+
+    #[test]
+    fn resize_to_given_size() {
+        let filter = Resize::new(
+            ResizeTarget::Size {
+                width: 10,
+                height: 6,
+            },
+            Interpolation::Bilinear,
+            ExtendValue::Closest,
+        );
+
+        let img = create_gradient_img(5, 3, ColorDepth::Rgb8, 10, 1);
+        let resized = run_checked(&filter, &img);
+        assert_eq!((resized.w(), resized.h()), (10, 6));
+        assert_eq!(resized.layer(0)[PixelPos::new(0, 0)], 0_f64);
+        assert_eq!(resized.layer(0)[PixelPos::new(5, 9)], 24_f64);
+    }
+
+    #[test]
+    fn too_big_results_are_rejected() {
+        assert!(ResizeTarget::try_from_string("Size: 100000 x 100000").is_err());
+        assert!(ResizeTarget::try_from_string("Scale: 1000").is_err());
+        assert!(ResizeTarget::try_from_string("Size: 4000 x 6000").is_ok());
+
+        let img = Img::empty_with_size(200, 200, ColorDepth::L8);
+        let filter = Resize::new(
+            ResizeTarget::Scale(100_f64),
+            Interpolation::Nearest,
+            ExtendValue::Closest,
+        );
+        let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+        executor_handle.reset(filter.get_steps_num(&img));
+        assert!(filter.process(&img, &mut executor_handle).is_err());
+    }
+}
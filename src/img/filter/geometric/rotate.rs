@@ -0,0 +1,169 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::warp::{warp_img, warp_steps_num};
+use crate::my_err::MyError;
+use crate::processing::{ExecutorHandle, TaskStop};
+use crate::utils::LinesIter;
+
+// rotates counterclockwise about the image center,
+// the image size is kept unless the angle is an odd number of quarter turns
+#[derive(Clone)]
+pub struct Rotate {
+    angle: RotationAngle,
+    interpolation: Interpolation,
+    extend_value: ExtendValue,
+}
+
+impl Rotate {
+    pub fn new(
+        angle: RotationAngle,
+        interpolation: Interpolation,
+        extend_value: ExtendValue,
+    ) -> Self {
+        Rotate {
+            angle,
+            interpolation,
+            extend_value,
+        }
+    }
+
+    fn res_size(&self, img: &Img) -> (usize, usize) {
+        match self.angle.quarter_turns() {
+            Some(1) | Some(3) => (img.h(), img.w()),
+            _ => (img.w(), img.h()),
+        }
+    }
+
+    // exact for quarter turns so that they don't blur the image
+    fn cos_sin(&self) -> (f64, f64) {
+        match self.angle.quarter_turns() {
+            Some(0) => (1_f64, 0_f64),
+            Some(1) => (0_f64, 1_f64),
+            Some(2) => (-1_f64, 0_f64),
+            Some(3) => (0_f64, -1_f64),
+            _ => {
+                let rad = self.angle.degrees.to_radians();
+                (rad.cos(), rad.sin())
+            }
+        }
+    }
+}
+
+impl Filter for Rotate {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let (res_w, res_h) = self.res_size(img);
+        let (cos, sin) = self.cos_sin();
+
+        let src_center_row = (img.h() as f64 - 1_f64) / 2_f64;
+        let src_center_col = (img.w() as f64 - 1_f64) / 2_f64;
+        let res_center_row = (res_h as f64 - 1_f64) / 2_f64;
+        let res_center_col = (res_w as f64 - 1_f64) / 2_f64;
+
+        // rows go down, so the counterclockwise rotation of the source by the angle
+        // is the same rotation of the result back to the source
+        let map_to_src = |row: f64, col: f64| {
+            let d_row = row - res_center_row;
+            let d_col = col - res_center_col;
+            (
+                sin * d_col + cos * d_row + src_center_row,
+                cos * d_col - sin * d_row + src_center_col,
+            )
+        };
+
+        warp_img(
+            img,
+            res_w,
+            res_h,
+            map_to_src,
+            self.interpolation,
+            self.extend_value,
+            executor_handle,
+        )
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let (_, res_h) = self.res_size(img);
+        warp_steps_num(img, res_h)
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Поворот ({}°, {})",
+            self.angle.degrees,
+            self.interpolation.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "Rotate".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for Rotate {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 3 {
+            return Err(MyError::new("Должно быть 3 строки".to_string()));
+        }
+
+        let angle = RotationAngle::try_from_string(lines_iter.next_or_empty())?;
+        let interpolation = Interpolation::try_from_string(lines_iter.next_or_empty())?;
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        self.angle = angle;
+        self.interpolation = interpolation;
+        self.extend_value = extend_value;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}",
+            self.angle.content_to_string(),
+            self.interpolation.content_to_string(),
+            self.extend_value.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for Rotate {
+    fn default() -> Self {
+        Rotate::new(
+            RotationAngle::new(90_f64),
+            Interpolation::Bilinear,
+            ExtendValue::Given(0_f64),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_gradient_img, run_checked};
+    use fltk::enums::ColorDepth;
+
+    #[test]
+    fn quarter_turn_swaps_size_and_keeps_pixels() {
+        let img = create_gradient_img(5, 3, ColorDepth::Rgb8, 10, 1);
+        let filter = Rotate::new(
+            RotationAngle::new(90_f64),
+            Interpolation::Bicubic,
+            ExtendValue::Given(0_f64),
+        );
+
+        let rotated = run_checked(&filter, &img);
+        assert_eq!((rotated.w(), rotated.h()), (3, 5));
+        for pos in rotated.get_area().iter_pixels() {
+            let src = PixelPos::new(pos.col, 4 - pos.row);
+            assert_eq!(rotated.layer(0)[pos], img.layer(0)[src], "{:?}", pos);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use super::super::super::*;
+use crate::processing::{ExecutorHandle, TaskStop};
+use rayon::prelude::*;
+
+// the result pixel at (row, col) takes the source value at map_to_src(row, col),
+// completes an action for each row of each layer
+pub fn warp_img<M: Fn(f64, f64) -> (f64, f64) + Sync>(
+    img: &Img,
+    res_w: usize,
+    res_h: usize,
+    map_to_src: M,
+    interpolation: Interpolation,
+    extend_value: ExtendValue,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<Img, TaskStop> {
+    let max_value = img.bit_depth().max_value();
+
+    let mut res_layers = Vec::<ImgLayer>::with_capacity(img.d());
+
+    for layer in img.layers() {
+        // as in Img::extended, the area outside of the image is opaque
+        let extend_value = match layer.channel() {
+            ImgChannel::A => ExtendValue::Given(max_value),
+            _ => extend_value,
+        };
+
+        let mut res = Matrix2D::empty_with_size(res_w, res_h);
+        let mat = layer.matrix();
+
+        let handle: &ExecutorHandle = executor_handle;
+
+        res.vals_mut()
+            .par_chunks_mut(res_w)
+            .enumerate()
+            .try_for_each_init(
+                || handle.share(),
+                |worker_handle, (row, res_row)| {
+                    for (col, res_val) in res_row.iter_mut().enumerate() {
+                        let (src_row, src_col) = map_to_src(row as f64, col as f64);
+                        let val = sample(mat, src_row, src_col, interpolation, extend_value);
+                        *res_val = val.clamp(0_f64, max_value);
+                    }
+
+                    worker_handle.complete_action()
+                },
+            )?;
+
+        res_layers.push(ImgLayer::new(res, layer.channel()));
+    }
+
+    Ok(Img::from_layers_of_depth(
+        res_layers,
+        img.color_depth(),
+        img.bit_depth(),
+    ))
+}
+
+pub fn warp_steps_num(img: &Img, res_h: usize) -> usize {
+    img.d() * res_h
+}
+
+fn sample(
+    mat: &Matrix2D,
+    row: f64,
+    col: f64,
+    interpolation: Interpolation,
+    extend_value: ExtendValue,
+) -> f64 {
+    let val = |row: isize, col: isize| mat.value_extended(row, col, extend_value);
+
+    let row_0 = row.floor();
+    let col_0 = col.floor();
+    let (d_row, d_col) = (row - row_0, col - col_0);
+    let (row_0, col_0) = (row_0 as isize, col_0 as isize);
+
+    match interpolation {
+        Interpolation::Nearest => val(row.round() as isize, col.round() as isize),
+        Interpolation::Bilinear => {
+            let top = val(row_0, col_0) * (1_f64 - d_col) + val(row_0, col_0 + 1) * d_col;
+            let bottom =
+                val(row_0 + 1, col_0) * (1_f64 - d_col) + val(row_0 + 1, col_0 + 1) * d_col;
+            top * (1_f64 - d_row) + bottom * d_row
+        }
+        Interpolation::Bicubic => {
+            let row_weights = cubic_weights(d_row);
+            let col_weights = cubic_weights(d_col);
+
+            let mut sum = 0_f64;
+            for (i, row_weight) in row_weights.iter().enumerate() {
+                for (j, col_weight) in col_weights.iter().enumerate() {
+                    let val = val(row_0 - 1 + i as isize, col_0 - 1 + j as isize);
+                    sum += row_weight * col_weight * val;
+                }
+            }
+            sum
+        }
+    }
+}
+
+// Keys kernel with a = -0.5 for the 4 neighbours at offsets -1, 0, 1, 2
+fn cubic_weights(t: f64) -> [f64; 4] {
+    const A: f64 = -0.5;
+
+    let kernel = |x: f64| {
+        let x = x.abs();
+        if x <= 1_f64 {
+            (A + 2_f64) * x.powi(3) - (A + 3_f64) * x.powi(2) + 1_f64
+        } else if x < 2_f64 {
+            A * x.powi(3) - 5_f64 * A * x.powi(2) + 8_f64 * A * x - 4_f64 * A
+        } else {
+            0_f64
+        }
+    };
+
+    [
+        kernel(1_f64 + t),
+        kernel(t),
+        kernel(1_f64 - t),
+        kernel(2_f64 - t),
+    ]
+}
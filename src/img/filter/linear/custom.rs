@@ -7,6 +7,9 @@ use crate::processing::TaskStop;
 use crate::utils::{LinesIter, WordsIter};
 use fltk::enums::ColorDepth;
 
+// larger kernels are applied through the FFT, the direct sum gets too slow for them
+const FFT_MIN_COEFFS_COUNT: usize = 15 * 15 + 1;
+
 #[derive(Clone)]
 pub struct LinearCustom {
     width: usize,
@@ -40,6 +43,16 @@ impl LinearCustom {
             name: "Линейный фильтр".to_string(),
         }
     }
+
+    fn uses_fft(&self) -> bool {
+        self.coeffs.len() >= FFT_MIN_COEFFS_COUNT
+    }
+
+    fn kernel(&self) -> Matrix2D {
+        let mut kernel = Matrix2D::empty_with_size(self.width, self.height);
+        kernel.vals_mut().copy_from_slice(&self.coeffs);
+        kernel
+    }
 }
 
 impl WindowFilter for LinearCustom {
@@ -81,7 +94,11 @@ impl Filter for LinearCustom {
     }
 
     fn get_steps_num(&self, img: &Img) -> usize {
-        let rows_per_layer = img.h();
+        let rows_per_layer = if self.uses_fft() {
+            correlation_actions_num(img.w(), img.h(), self.width, self.height)
+        } else {
+            img.h()
+        };
         let layers_count = match img.color_depth() {
             ColorDepth::L8 => img.d(),
             ColorDepth::La8 => img.d() - 1,
@@ -215,6 +232,12 @@ impl ByLayer for LinearCustom {
         let result_mat = {
             match layer.channel() {
                 ImgChannel::A => layer.matrix().clone(),
+                _ if self.uses_fft() => correlate_fft(
+                    layer.matrix(),
+                    &self.kernel(),
+                    self.extend_value,
+                    executor_handle,
+                )?,
                 _ => process_with_window(layer.matrix(), self, executor_handle)?,
             }
         };
@@ -222,3 +245,29 @@ impl ByLayer for LinearCustom {
         Ok(ImgLayer::new(result_mat, layer.channel()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_gradient_img, process_serially, run_checked};
+
+    #[test]
+    fn large_kernel_through_fft_matches_direct_sum() {
+        let img = create_gradient_img(23, 18, ColorDepth::L8, 31, 17);
+
+        let coeffs: Vec<f64> = (0..17 * 16).map(|ind| ((ind * 7) % 5) as f64).collect();
+        let custom = LinearCustom::with_coeffs(
+            coeffs,
+            17,
+            16,
+            ExtendValue::Reflect,
+            NormalizeOption::Normalized,
+        );
+
+        let res = run_checked(&custom, &img);
+        let expected = process_serially(img.layer(0).matrix(), &custom);
+        for (res, expected) in res.layer(0).matrix().vals().iter().zip(expected.vals()) {
+            assert!((res - expected).abs() < 1e-6);
+        }
+    }
+}
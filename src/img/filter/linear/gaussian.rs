@@ -12,38 +12,55 @@ pub struct LinearGaussian {
     size: FilterWindowSize,
     extend_value: ExtendValue,
     coeffs: Vec<f64>,
+    line_coeffs: Vec<f64>,
     name: String,
 }
 
 impl LinearGaussian {
+    // sigma equals the window radius
     pub fn new(size: FilterWindowSize, extend_value: ExtendValue) -> Self {
+        let sigma = (size.width / 2) as f64;
+        Self::with_size_and_sigma(size, sigma, extend_value)
+    }
+
+    // the window covers 3 sigmas in each direction
+    pub fn with_sigma(sigma: Sigma, extend_value: ExtendValue) -> Self {
+        let r = ((3_f64 * sigma.value).ceil() as usize).max(1);
+        let size = FilterWindowSize::new(r * 2 + 1, r * 2 + 1);
+        Self::with_size_and_sigma(size, sigma.value, extend_value)
+    }
+
+    fn with_size_and_sigma(size: FilterWindowSize, sigma: f64, extend_value: ExtendValue) -> Self {
         assert_eq!(size.width % 2, 1);
         assert_eq!(size.width, size.height);
 
-        let coeffs = Self::count_coeffs(size);
+        let coeffs = Self::count_coeffs(size, sigma);
+        let line_coeffs = Self::count_line_coeffs(size, sigma);
 
         LinearGaussian {
             size,
             extend_value,
             coeffs,
+            line_coeffs,
             name: "Линейный фильтр (гауссовский)".to_string(),
         }
     }
 
-    fn count_coeffs(size: FilterWindowSize) -> Vec<f64> {
+    fn count_coeffs(size: FilterWindowSize, sigma: f64) -> Vec<f64> {
         let mut coeffs = Vec::<f64>::new();
         coeffs.resize(size.width * size.height, 0_f64);
 
         let r = size.width / 2;
         let one_over_pi: f64 = 1_f64 / 3.14159265359_f64;
-        let one_over_2_r_squared: f64 = 1_f64 / (2_f64 * f64::powi(r as f64, 2));
+        let one_over_2_r_squared: f64 = 1_f64 / (2_f64 * f64::powi(sigma, 2));
 
         for row in 0..size.width {
             for col in 0..size.width {
                 coeffs[row * size.width + col] = one_over_pi
                     * one_over_2_r_squared
                     * f64::exp(
-                        -(f64::powi(col as f64, 2) + f64::powi(row as f64, 2))
+                        -(f64::powi(col as f64 - r as f64, 2)
+                            + f64::powi(row as f64 - r as f64, 2))
                             * one_over_2_r_squared,
                     );
             }
@@ -57,6 +74,24 @@ impl LinearGaussian {
 
         coeffs
     }
+
+    // coeffs[row * w + col] == line_coeffs[row] * line_coeffs[col]
+    fn count_line_coeffs(size: FilterWindowSize, sigma: f64) -> Vec<f64> {
+        let r = size.width / 2;
+        let one_over_2_r_squared: f64 = 1_f64 / (2_f64 * f64::powi(sigma, 2));
+
+        let mut coeffs: Vec<f64> = (0..size.width)
+            .map(|i| f64::exp(-f64::powi(i as f64 - r as f64, 2) * one_over_2_r_squared))
+            .collect();
+
+        let sum: f64 = coeffs.iter().sum();
+
+        for c in coeffs.iter_mut() {
+            *c /= sum;
+        }
+
+        coeffs
+    }
 }
 
 impl Filter for LinearGaussian {
@@ -65,7 +100,8 @@ impl Filter for LinearGaussian {
     }
 
     fn get_steps_num(&self, img: &Img) -> usize {
-        let rows_per_layer = img.h();
+        let ext_rows = img.h() + self.h() / 2 * 2;
+        let cols = img.w();
         let layers_count = match img.color_depth() {
             ColorDepth::L8 => img.d(),
             ColorDepth::La8 => img.d() - 1,
@@ -73,7 +109,7 @@ impl Filter for LinearGaussian {
             ColorDepth::Rgba8 => img.d() - 1,
         };
 
-        layers_count * rows_per_layer
+        layers_count * (ext_rows + cols)
     }
 
     fn get_description(&self) -> String {
@@ -98,7 +134,7 @@ impl ByLayer for LinearGaussian {
     ) -> Result<ImgLayer, TaskStop> {
         let result_mat = match layer.channel() {
             ImgChannel::A => layer.matrix().clone(),
-            _ => process_with_window(layer.matrix(), self, executor_handle)?,
+            _ => process_separable(layer.matrix(), self, executor_handle)?,
         };
 
         Ok(ImgLayer::new(result_mat, layer.channel()))
@@ -136,6 +172,18 @@ impl WindowFilter for LinearGaussian {
     }
 }
 
+impl SeparableFilter for LinearGaussian {
+    fn process_line(&self, line: &[f64], res: &mut [f64]) {
+        for (ind, res_val) in res.iter_mut().enumerate() {
+            *res_val = line[ind..ind + self.line_coeffs.len()]
+                .iter()
+                .zip(self.line_coeffs.iter())
+                .map(|(v, c)| v * c)
+                .sum();
+        }
+    }
+}
+
 impl Default for LinearGaussian {
     fn default() -> Self {
         LinearGaussian::new(FilterWindowSize::new(5, 5), ExtendValue::Closest)
@@ -158,7 +206,8 @@ impl StringFromTo for LinearGaussian {
 
         self.size = size;
         self.extend_value = extend_value;
-        self.coeffs = Self::count_coeffs(size);
+        self.coeffs = Self::count_coeffs(size, (size.width / 2) as f64);
+        self.line_coeffs = Self::count_line_coeffs(size, (size.width / 2) as f64);
 
         Ok(())
     }
@@ -172,3 +221,41 @@ impl StringFromTo for LinearGaussian {
         Some(params_str)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_is_symmetric_and_peaks_at_center() {
+        let filters = [
+            LinearGaussian::new(FilterWindowSize::new(3, 3), ExtendValue::Closest),
+            LinearGaussian::new(FilterWindowSize::new(7, 7), ExtendValue::Closest),
+            LinearGaussian::with_sigma(Sigma::new(0.8), ExtendValue::Closest),
+        ];
+
+        for filter in filters.iter() {
+            let w = filter.w();
+            let r = w / 2;
+            let coeff = |row: usize, col: usize| filter.coeffs[row * w + col];
+
+            let sum: f64 = filter.coeffs.iter().sum();
+            assert!((sum - 1_f64).abs() < 1e-9, "{}", w);
+
+            for row in 0..w {
+                for col in 0..w {
+                    let c = coeff(row, col);
+                    assert!((c - coeff(col, row)).abs() < 1e-12, "{} {} {}", w, row, col);
+                    assert!((c - coeff(w - 1 - row, col)).abs() < 1e-12);
+                    assert!((c - coeff(row, w - 1 - col)).abs() < 1e-12);
+                    if (row, col) != (r, r) {
+                        assert!(c < coeff(r, r), "{} {} {}", w, row, col);
+                    }
+
+                    let separated = filter.line_coeffs[row] * filter.line_coeffs[col];
+                    assert!((c - separated).abs() < 1e-12);
+                }
+            }
+        }
+    }
+}
@@ -57,9 +57,8 @@ impl Filter for LinearMean {
     }
 
     fn get_steps_num(&self, img: &Img) -> usize {
-        let row_sums = img.w() + 1;
-        let col_sums = img.h() + 1;
-        let rows = img.h();
+        let ext_rows = img.h() + self.h() / 2 * 2;
+        let cols = img.w();
         let layers_count = match img.color_depth() {
             ColorDepth::L8 => img.d(),
             ColorDepth::La8 => img.d() - 1,
@@ -67,7 +66,7 @@ impl Filter for LinearMean {
             ColorDepth::Rgba8 => img.d() - 1,
         };
 
-        layers_count * (row_sums + col_sums + rows)
+        layers_count * (ext_rows + cols)
     }
 
     fn get_description(&self) -> String {
@@ -126,75 +125,27 @@ impl ByLayer for LinearMean {
         layer: &ImgLayer,
         executor_handle: &mut ExecutorHandle,
     ) -> Result<ImgLayer, TaskStop> {
-        let mat = match layer.channel() {
-            ImgChannel::A => return Ok(layer.clone()),
-            _ => layer.matrix().clone(),
+        let result_mat = match layer.channel() {
+            ImgChannel::A => layer.matrix().clone(),
+            _ => process_separable(layer.matrix(), self, executor_handle)?,
         };
 
-        let mut sum_res = mat.extended(ExtendValue::Given(0_f64), 0, 0, 1, 1);
-
-        // sum along rows
-        for row in 0..sum_res.h() {
-            let mut row_sum = 0_f64;
-            for col in 0..sum_res.w() {
-                let pos = PixelPos::new(row, col);
-                row_sum += sum_res[pos];
-                sum_res[pos] = row_sum;
-            }
-
-            executor_handle.complete_action()?;
-        }
-
-        // sum along cols
-        for col in 0..sum_res.w() {
-            let mut col_sum = 0_f64;
-            for row in 0..sum_res.h() {
-                let pos = PixelPos::new(row, col);
-                col_sum += sum_res[pos];
-                sum_res[pos] = col_sum;
-            }
-
-            executor_handle.complete_action()?;
-        }
-
-        let win_half = PixelPos::new(self.h() / 2, self.w() / 2);
-
-        // filter
-        let mat_sum_ext = sum_res.extended(
-            ExtendValue::Closest,
-            win_half.row,
-            win_half.col,
-            win_half.row,
-            win_half.col,
-        );
-
-        let mut mat_res = mat.clone();
-
-        let one = PixelPos::one();
-
-        let coeff = 1_f64 / (self.w() * self.h()) as f64;
-
-        for row in 0..mat_res.h() {
-            for col in 0..mat_res.w() {
-                let pos = PixelPos::new(row, col);
-                let ext_pos = pos + one + win_half;
+        Ok(ImgLayer::new(result_mat, layer.channel()))
+    }
+}
 
-                let sum_top_left = mat_sum_ext[ext_pos - win_half - one];
-                let sum_top_right =
-                    mat_sum_ext[ext_pos - win_half.row_vec() + win_half.col_vec() - one.row_vec()];
-                let sum_bottom_left =
-                    mat_sum_ext[ext_pos + win_half.row_vec() - win_half.col_vec() - one.col_vec()];
-                let sum_bottom_right = mat_sum_ext[ext_pos + win_half];
+impl SeparableFilter for LinearMean {
+    fn process_line(&self, line: &[f64], res: &mut [f64]) {
+        let kernel_len = line.len() - res.len() + 1;
+        let coeff = 1_f64 / kernel_len as f64;
 
-                let result =
-                    (sum_bottom_right - sum_top_right - sum_bottom_left + sum_top_left) * coeff;
-                mat_res[ext_pos - win_half - one] = result;
-            }
+        // running sum of the window
+        let mut sum: f64 = line[..kernel_len].iter().sum();
+        res[0] = sum * coeff;
 
-            executor_handle.complete_action()?;
+        for ind in 1..res.len() {
+            sum += line[ind + kernel_len - 1] - line[ind - 1];
+            res[ind] = sum * coeff;
         }
-
-        // create layer
-        Ok(ImgLayer::new(mat_res, layer.channel()))
     }
 }
@@ -1,7 +1,9 @@
 mod custom;
 mod gaussian;
 mod mean;
+mod unsharp_mask;
 
 pub use custom::LinearCustom;
 pub use gaussian::LinearGaussian;
 pub use mean::LinearMean;
+pub use unsharp_mask::UnsharpMask;
@@ -0,0 +1,276 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::super::*;
+use super::LinearGaussian;
+use crate::my_err::MyError;
+use crate::processing::TaskStop;
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// adds the difference between the image and its blurred copy multiplied by the amount,
+// the amounts above 1 give the high-boost filtering
+#[derive(Clone)]
+pub struct UnsharpMask {
+    sigma: Sigma,
+    amount: SharpenAmount,
+    threshold: SharpenThreshold,
+    channels: SharpenChannels,
+    gaussian_filter: LinearGaussian,
+    bit_depth: BitDepth,
+}
+
+impl UnsharpMask {
+    pub fn new(
+        sigma: Sigma,
+        amount: SharpenAmount,
+        threshold: SharpenThreshold,
+        channels: SharpenChannels,
+    ) -> Self {
+        UnsharpMask {
+            sigma,
+            amount,
+            threshold,
+            channels,
+            gaussian_filter: LinearGaussian::with_sigma(sigma, ExtendValue::Closest),
+            bit_depth: BitDepth::default(),
+        }
+    }
+
+    fn sharpens_luminance_only(&self, img: &Img) -> bool {
+        self.channels == SharpenChannels::Luminance
+            && matches!(img.color_depth(), ColorDepth::Rgb8 | ColorDepth::Rgba8)
+    }
+
+    fn layer_steps_num(&self, img: &Img) -> usize {
+        let ext_rows = img.h() + self.gaussian_filter.h() / 2 * 2;
+        // bluring and then sharpening of each row
+        ext_rows + img.w() + img.h()
+    }
+
+    fn process_luminance(
+        &self,
+        img: &Img,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Img, TaskStop> {
+        let color_space = img.color_space();
+        let mut img_ycbcr = img.try_convert_color_space(ColorSpace::YCbCr, executor_handle)?;
+
+        for layer in img_ycbcr.layers_mut().iter_mut() {
+            if layer.channel() == ImgChannel::Y {
+                *layer = self.process_layer(layer, executor_handle)?;
+            }
+        }
+
+        img_ycbcr.try_convert_color_space(color_space, executor_handle)
+    }
+}
+
+impl Filter for UnsharpMask {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = UnsharpMask {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+
+        if self.sharpens_luminance_only(img) {
+            filter.process_luminance(img, executor_handle)
+        } else {
+            process_each_layer(img, &filter, executor_handle)
+        }
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        if self.sharpens_luminance_only(img) {
+            // to YCbCr and back
+            let conversions_steps = match img.color_space() {
+                ColorSpace::YCbCr => 0,
+                _ => img.h() * 2,
+            };
+            return conversions_steps + self.layer_steps_num(img);
+        }
+
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        layers_count * self.layer_steps_num(img)
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Нерезкое маскирование (сигма {}, сила {}, порог {}, {})",
+            self.sigma.value,
+            self.amount.value,
+            self.threshold.value,
+            self.channels.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "UnsharpMask".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for UnsharpMask {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        if layer.channel() == ImgChannel::A {
+            return Ok(layer.clone());
+        }
+
+        let blured = self.gaussian_filter.process_layer(layer, executor_handle)?;
+
+        let max_value = self.bit_depth.max_value();
+        let threshold = self.threshold.value * max_value;
+
+        let mut res = layer.matrix().clone();
+        for row in 0..res.h() {
+            for col in 0..res.w() {
+                let pos = PixelPos::new(row, col);
+                let diff = res[pos] - blured[pos];
+                if diff.abs() >= threshold {
+                    res[pos] = (res[pos] + self.amount.value * diff).clamp(0_f64, max_value);
+                }
+            }
+
+            executor_handle.complete_action()?;
+        }
+
+        Ok(ImgLayer::new(res, layer.channel()))
+    }
+}
+
+impl StringFromTo for UnsharpMask {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 4 {
+            return Err(MyError::new(
+                "Нужно ввести сигму размытия, силу, порог и обрабатываемые каналы на отдельных строках".to_string(),
+            ));
+        }
+
+        let sigma = Sigma::try_from_string(lines_iter.next_or_empty())?;
+        let amount = SharpenAmount::try_from_string(lines_iter.next_or_empty())?;
+        let threshold = SharpenThreshold::try_from_string(lines_iter.next_or_empty())?;
+        let channels = SharpenChannels::try_from_string(lines_iter.next_or_empty())?;
+
+        *self = UnsharpMask::new(sigma, amount, threshold, channels);
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}\n{}",
+            self.sigma.content_to_string(),
+            self.amount.content_to_string(),
+            self.threshold.content_to_string(),
+            self.channels.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for UnsharpMask {
+    fn default() -> Self {
+        UnsharpMask::new(
+            Sigma::new(1_f64),
+            SharpenAmount::new(1_f64),
+            SharpenThreshold::new(0_f64),
+            SharpenChannels::All,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_step_img, run_checked};
+
+    fn process(filter: &mut UnsharpMask, params: &str, img: &Img) -> Img {
+        filter.try_set_from_string(params).unwrap();
+        run_checked(filter, img)
+    }
+
+    #[test]
+    fn unsharp_mask_sharpens_and_clamps() {
+        // the step is between the columns 7 and 8
+        let img = create_step_img(16, 6, ColorDepth::L8, |_| (50_f64, 200_f64));
+
+        let mut filter = UnsharpMask::default();
+        let res = process(
+            &mut filter,
+            "Sigma: 1\nAmount: 1\nThreshold: 0\nChannels: all",
+            &img,
+        );
+        assert!(res.layer(0)[PixelPos::new(2, 7)] < 50_f64);
+        assert!(res.layer(0)[PixelPos::new(2, 8)] > 200_f64);
+        assert_eq!(res.layer(0)[PixelPos::new(2, 0)], 50_f64);
+        assert_eq!(res.layer(0)[PixelPos::new(2, 15)], 200_f64);
+
+        let res = process(
+            &mut filter,
+            "Sigma: 1\nAmount: 10\nThreshold: 0\nChannels: all",
+            &img,
+        );
+        assert_eq!(res.layer(0)[PixelPos::new(2, 7)], 0_f64);
+        assert_eq!(res.layer(0)[PixelPos::new(2, 8)], 255_f64);
+
+        let res = process(
+            &mut filter,
+            "Sigma: 1\nAmount: 1\nThreshold: 0.9\nChannels: all",
+            &img,
+        );
+        assert!(res
+            .layer(0)
+            .matrix()
+            .has_the_same_values_as(img.layer(0).matrix()));
+    }
+
+    #[test]
+    fn unsharp_mask_of_luminance_keeps_colors() {
+        // the same step of a reddish color
+        let levels = [(100_f64, 200_f64), (50_f64, 100_f64), (40_f64, 80_f64)];
+        let img = create_step_img(16, 6, ColorDepth::Rgb8, |ch| levels[ch]);
+
+        let chroma_shift = |res: &Img| {
+            let init = img.converted_to_color_space(ColorSpace::YCbCr);
+            let res = res.converted_to_color_space(ColorSpace::YCbCr);
+            img.get_area()
+                .iter_pixels()
+                .map(|pos| {
+                    (init.layer(1)[pos] - res.layer(1)[pos]).abs()
+                        + (init.layer(2)[pos] - res.layer(2)[pos]).abs()
+                })
+                .fold(0_f64, f64::max)
+        };
+
+        let mut filter = UnsharpMask::default();
+        let res = process(
+            &mut filter,
+            "Sigma: 1\nAmount: 0.5\nThreshold: 0\nChannels: luminance",
+            &img,
+        );
+        assert!(chroma_shift(&res) < 1e-6);
+        assert!(res.layer(0)[PixelPos::new(2, 8)] > 200_f64);
+
+        let res = process(
+            &mut filter,
+            "Sigma: 1\nAmount: 0.5\nThreshold: 0\nChannels: all",
+            &img,
+        );
+        assert!(chroma_shift(&res) > 1_f64);
+    }
+}
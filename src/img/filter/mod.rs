@@ -1,16 +1,24 @@
+pub mod arithmetic;
 pub mod color_channel;
+pub mod edge;
 pub mod filter_option;
 pub mod filter_trait;
+pub mod frequency;
+pub mod geometric;
 pub mod linear;
 pub mod non_linear;
 pub mod utils;
 
-use self::filter_trait::WindowFilter;
+#[cfg(test)]
+mod test_utils;
+
+use self::filter_trait::{SeparableFilter, WindowFilter};
 use crate::{
     img::{Img, ImgLayer, Matrix2D},
     my_err::MyError,
     processing::{ExecutorHandle, TaskStop},
 };
+use rayon::prelude::*;
 
 pub type FilterBase = Box<dyn self::filter_trait::Filter>;
 
@@ -22,15 +30,46 @@ impl From<AddStep> for FilterBase {
             AddStep::LinMean => Box::new(LinearMean::default()) as FilterBase,
             AddStep::LinGauss => Box::new(LinearGaussian::default()) as FilterBase,
             AddStep::Median => Box::new(MedianFilter::default()) as FilterBase,
+            AddStep::Gamma => Box::new(PointTransform::gamma()) as FilterBase,
+            AddStep::ContrastBrightness => {
+                Box::new(PointTransform::contrast_brightness()) as FilterBase
+            }
+            AddStep::Levels => Box::new(PointTransform::levels()) as FilterBase,
+            AddStep::Invert => Box::new(PointTransform::invert()) as FilterBase,
+            AddStep::Curve => Box::new(PointTransform::curve()) as FilterBase,
+            AddStep::Combine(op) => Box::new(Combine::with_op(op)) as FilterBase,
+            AddStep::UnsharpMask => Box::new(UnsharpMask::default()) as FilterBase,
+            AddStep::Bilateral => Box::new(Bilateral::default()) as FilterBase,
+            AddStep::NonLocalMeans => Box::new(NonLocalMeans::default()) as FilterBase,
             AddStep::HistogramLocalContrast => {
                 Box::new(HistogramLocalContrast::default()) as FilterBase
             }
             AddStep::CutBrightness => Box::new(CutBrightness::default()) as FilterBase,
             AddStep::HistogramEqualizer => Box::new(EqualizeHist::default()) as FilterBase,
+            AddStep::Clahe => Box::new(Clahe::default()) as FilterBase,
             AddStep::Rgb2Gray => Box::new(Rgb2Gray::default()) as FilterBase,
+            AddStep::ConvertColorSpace(color_space) => {
+                Box::new(ConvertColorSpace::new(color_space)) as FilterBase
+            }
             AddStep::NeutralizeChannel => Box::new(NeutralizeChannel::default()) as FilterBase,
             AddStep::ExtractChannel => Box::new(ExtractChannel::default()) as FilterBase,
             AddStep::CannyEdgeDetection => Box::new(CannyEdgeDetection::default()) as FilterBase,
+            AddStep::Morphology(op) => Box::new(Morphology::with_op(op)) as FilterBase,
+            AddStep::Threshold => Box::new(Threshold::default()) as FilterBase,
+            AddStep::Resize => Box::new(Resize::default()) as FilterBase,
+            AddStep::Rotate => Box::new(Rotate::default()) as FilterBase,
+            AddStep::Flip => Box::new(Flip::default()) as FilterBase,
+            AddStep::AffineWarp => Box::new(AffineWarp::default()) as FilterBase,
+            AddStep::FrequencyFilter(band) => Box::new(FrequencyFilter::new(
+                band,
+                FrequencyResponse::Butterworth { order: 2 },
+            )) as FilterBase,
+            AddStep::LogSpectrum => Box::new(LogSpectrum::default()) as FilterBase,
+            AddStep::Gradient(operator) => {
+                Box::new(Gradient::with_operator(operator)) as FilterBase
+            }
+            AddStep::Laplacian => Box::new(Laplacian::default()) as FilterBase,
+            AddStep::LaplacianOfGaussian => Box::new(Laplacian::of_gaussian()) as FilterBase,
         }
     }
 }
@@ -68,6 +107,8 @@ impl Iterator for FilterIterator {
     }
 }
 
+// rows are processed on the rayon thread pool, each output pixel is computed
+// exactly as in a serial walk so the result doesn't depend on the threads count
 fn process_with_window<T: WindowFilter>(
     init: &Matrix2D,
     filter: &T,
@@ -78,39 +119,78 @@ fn process_with_window<T: WindowFilter>(
 
     let mut res = Matrix2D::empty_size_of(init);
 
-    let mut pixel_buf = Vec::<f64>::new();
-    pixel_buf.resize(filter.w() * filter.h(), 0_f64);
+    let layer_ext = init.extended_for_window_filter(filter);
+
+    let handle: &ExecutorHandle = executor_handle;
+
+    res.vals_mut()
+        .par_chunks_mut(init.w())
+        .enumerate()
+        .try_for_each_init(
+            || {
+                let pixel_buf = vec![0_f64; filter.w() * filter.h()];
+                (handle.share(), pixel_buf)
+            },
+            |(worker_handle, pixel_buf), (row, res_row)| {
+                for (col, res_val) in res_row.iter_mut().enumerate() {
+                    let pos_im = PixelPos::new(row, col);
+
+                    for pos_w in filter.get_iter() {
+                        let buf_ind: usize = pos_w.row * filter.w() + pos_w.col;
+                        pixel_buf[buf_ind] = layer_ext[pos_im + pos_w];
+                    }
 
-    let fil_half_size = PixelPos::new(filter.h() / 2, filter.w() / 2);
+                    *res_val = filter.process_window(&mut pixel_buf[..]);
+                }
 
+                worker_handle.complete_action()
+            },
+        )?;
+
+    Ok(res)
+}
+
+fn process_separable<T: SeparableFilter>(
+    init: &Matrix2D,
+    filter: &T,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<Matrix2D, TaskStop> {
     let layer_ext = init.extended_for_window_filter(filter);
 
-    // for row in fil_half_size,
-    //     PixelPos::new(init.h(), init.w()) + fil_half_size)
-    for row in fil_half_size.row..init.h() + fil_half_size.row {
-        for col in fil_half_size.col..init.w() + fil_half_size.col {
-            let pos_im = PixelPos::new(row, col);
+    let rows_res = process_lines(&layer_ext, init.w(), filter, executor_handle)?;
+    let cols_res = process_lines(&rows_res.transposed(), init.h(), filter, executor_handle)?;
 
-            for pos_w in filter.get_iter() {
-                let buf_ind: usize = pos_w.row * filter.w() + pos_w.col;
-                let pix_pos: PixelPos = pos_im + pos_w - fil_half_size;
-                pixel_buf[buf_ind] = layer_ext[pix_pos];
-            }
+    Ok(cols_res.transposed())
+}
 
-            let filter_result: f64 = filter.process_window(&mut pixel_buf[..]);
+// completes an action for each row of 'mat'
+fn process_lines<T: SeparableFilter>(
+    mat: &Matrix2D,
+    res_w: usize,
+    filter: &T,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<Matrix2D, TaskStop> {
+    let mut res = Matrix2D::empty_with_size(res_w, mat.h());
 
-            res[pos_im - fil_half_size] = filter_result;
-        }
+    let handle: &ExecutorHandle = executor_handle;
 
-        executor_handle.complete_action()?;
-    }
+    res.vals_mut()
+        .par_chunks_mut(res_w)
+        .zip(mat.vals().par_chunks(mat.w()))
+        .try_for_each_init(
+            || handle.share(),
+            |worker_handle, (res_row, row)| {
+                filter.process_line(row, res_row);
+                worker_handle.complete_action()
+            },
+        )?;
 
     Ok(res)
 }
 
 // AnyFilter : ByLayer -> filter<>() -> filter_layers<>() -> process_layer<>()
 
-trait ByLayer {
+trait ByLayer: Sync {
     fn process_layer(
         &self,
         layer: &ImgLayer,
@@ -123,17 +203,28 @@ fn process_each_layer<F: ByLayer>(
     filter: &F,
     executor_handle: &mut ExecutorHandle,
 ) -> Result<Img, TaskStop> {
-    let mut res_layers = Vec::<ImgLayer>::with_capacity(img.d());
-
-    for layer in img.layers().iter() {
-        let res_layer = filter.process_layer(layer, executor_handle)?;
-        res_layers.push(res_layer);
-    }
-
-    Ok(Img::from_layers(res_layers, img.color_depth()))
+    let handle: &ExecutorHandle = executor_handle;
+
+    let res_layers = img
+        .layers()
+        .par_iter()
+        .map_init(
+            || handle.share(),
+            |worker_handle, layer| filter.process_layer(layer, worker_handle),
+        )
+        .collect::<Result<Vec<ImgLayer>, TaskStop>>()?;
+
+    Ok(Img::from_layers_of_depth(
+        res_layers,
+        img.color_depth(),
+        img.bit_depth(),
+    ))
 }
 
-use self::{color_channel::*, linear::*, non_linear::*};
+use self::{
+    arithmetic::*, color_channel::*, edge::*, filter_option::FrequencyResponse, frequency::*,
+    geometric::*, linear::*, non_linear::*,
+};
 
 use super::PixelPos;
 pub fn try_parce_filter(save_name: &str, content: &str) -> Result<FilterBase, MyError> {
@@ -142,13 +233,35 @@ pub fn try_parce_filter(save_name: &str, content: &str) -> Result<FilterBase, My
         "LinearMean" => Box::new(LinearMean::default()) as FilterBase,
         "LinearGaussian" => Box::new(LinearGaussian::default()) as FilterBase,
         "MedianFilter" => Box::new(MedianFilter::default()) as FilterBase,
+        "Gamma" => Box::new(PointTransform::gamma()) as FilterBase,
+        "ContrastBrightness" => Box::new(PointTransform::contrast_brightness()) as FilterBase,
+        "Levels" => Box::new(PointTransform::levels()) as FilterBase,
+        "Invert" => Box::new(PointTransform::invert()) as FilterBase,
+        "Curve" => Box::new(PointTransform::curve()) as FilterBase,
+        "Combine" => Box::new(Combine::default()) as FilterBase,
+        "UnsharpMask" => Box::new(UnsharpMask::default()) as FilterBase,
+        "Bilateral" => Box::new(Bilateral::default()) as FilterBase,
+        "NonLocalMeans" => Box::new(NonLocalMeans::default()) as FilterBase,
         "HistogramLocalContrast" => Box::new(HistogramLocalContrast::default()) as FilterBase,
         "CutBrightness" => Box::new(CutBrightness::default()) as FilterBase,
         "EqualizeHist" => Box::new(EqualizeHist::default()) as FilterBase,
+        "Clahe" => Box::new(Clahe::default()) as FilterBase,
         "Rgb2Gray" => Box::new(Rgb2Gray::default()) as FilterBase,
+        "ConvertColorSpace" => Box::new(ConvertColorSpace::default()) as FilterBase,
         "NeutralizeChannel" => Box::new(NeutralizeChannel::default()) as FilterBase,
         "ExtractChannel" => Box::new(ExtractChannel::default()) as FilterBase,
         "CannyEdgeDetection" => Box::new(CannyEdgeDetection::default()) as FilterBase,
+        "Morphology" => Box::new(Morphology::default()) as FilterBase,
+        "Threshold" => Box::new(Threshold::default()) as FilterBase,
+        "Resize" => Box::new(Resize::default()) as FilterBase,
+        "Rotate" => Box::new(Rotate::default()) as FilterBase,
+        "Flip" => Box::new(Flip::default()) as FilterBase,
+        "AffineWarp" => Box::new(AffineWarp::default()) as FilterBase,
+        "FrequencyFilter" => Box::new(FrequencyFilter::default()) as FilterBase,
+        "LogSpectrum" => Box::new(LogSpectrum::default()) as FilterBase,
+        "Gradient" => Box::new(Gradient::default()) as FilterBase,
+        "Laplacian" => Box::new(Laplacian::default()) as FilterBase,
+        "LaplacianOfGaussian" => Box::new(Laplacian::of_gaussian()) as FilterBase,
         _ => {
             return Err(MyError::new(format!(
                 "Не удалось загрузить фильтр '{}'",
@@ -164,12 +277,99 @@ pub fn try_parce_filter(save_name: &str, content: &str) -> Result<FilterBase, My
 mod tests {
     use crate::{
         img::{
-            filter::{color_channel::*, linear::*, non_linear::*, FilterBase},
-            Img,
+            filter::{
+                color_channel::*,
+                filter_option::{ExtendValue, FilterWindowSize, MorphOp, ThresholdMethod},
+                geometric::*,
+                linear::*,
+                non_linear::*,
+                process_separable, process_with_window,
+                test_utils::process_serially,
+                FilterBase,
+            },
+            BitDepth, Img, Matrix2D, PixelPos,
         },
-        processing::create_task_info_channel,
+        processing::{create_task_info_channel, ExecutorHandle, TaskStop},
     };
 
+    #[test]
+    fn parallel_result_matches_serial() {
+        let mut init = Matrix2D::empty_with_size(67, 41);
+        for pos in init.area().iter_pixels() {
+            init[pos] = ((pos.row * 31 + pos.col * 17) % 256) as f64;
+        }
+        init[PixelPos::new(40, 66)] = 1000_f64;
+
+        let check = |filter: &dyn Fn(&mut ExecutorHandle) -> Result<Matrix2D, TaskStop>,
+                     expected: Matrix2D| {
+            let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+            executor_handle.reset(init.h());
+            let res = filter(&mut executor_handle).unwrap();
+            executor_handle.assert_all_actions_completed();
+            assert_eq!(res.vals(), expected.vals());
+        };
+
+        let median = MedianFilter::default();
+        check(
+            &|handle| process_with_window(&init, &median, handle),
+            process_serially(&init, &median),
+        );
+
+        let gauss = LinearGaussian::default();
+        check(
+            &|handle| process_with_window(&init, &gauss, handle),
+            process_serially(&init, &gauss),
+        );
+    }
+
+    #[test]
+    fn separable_result_matches_window() {
+        let mut init = Matrix2D::empty_with_size(37, 23);
+        for pos in init.area().iter_pixels() {
+            init[pos] = ((pos.row * 31 + pos.col * 17) % 256) as f64;
+        }
+
+        let check = |filter: &dyn Fn(&mut ExecutorHandle) -> Result<Matrix2D, TaskStop>,
+                     steps_num: usize,
+                     expected: Matrix2D| {
+            let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+            executor_handle.reset(steps_num);
+            let res = filter(&mut executor_handle).unwrap();
+            executor_handle.assert_all_actions_completed();
+            assert!(res
+                .vals()
+                .iter()
+                .zip(expected.vals().iter())
+                .all(|(a, b)| (a - b).abs() < 1e-9));
+        };
+
+        let gauss = LinearGaussian::new(FilterWindowSize::new(7, 7), ExtendValue::Closest);
+        check(
+            &|handle| process_separable(&init, &gauss, handle),
+            init.h() + 6 + init.w(),
+            process_serially(&init, &gauss),
+        );
+
+        let mean = LinearMean::new(FilterWindowSize::new(5, 5), ExtendValue::Given(7_f64));
+        check(
+            &|handle| process_separable(&init, &mean, handle),
+            init.h() + 4 + init.w(),
+            process_serially(&init, &mean),
+        );
+    }
+
+    #[test]
+    fn parallel_processing_can_be_halted() {
+        let init = Matrix2D::empty_with_size(30, 30);
+
+        let (mut executor_handle, delegator_handle) = create_task_info_channel();
+        executor_handle.reset(init.h());
+        delegator_handle.halt_task();
+
+        let res = process_with_window(&init, &MedianFilter::default(), &mut executor_handle);
+        assert!(matches!(res, Err(TaskStop::Halted)));
+    }
+
     #[test]
     fn all_actions_are_completed() {
         let filters: Vec<FilterBase> = vec![
@@ -184,6 +384,18 @@ mod tests {
             Box::new(ExtractChannel::default()) as FilterBase,
             Box::new(NeutralizeChannel::default()) as FilterBase,
             Box::new(Rgb2Gray::default()) as FilterBase,
+            Box::new(Morphology::with_op(MorphOp::BlackTopHat)) as FilterBase,
+            Box::new(Threshold::default()) as FilterBase,
+            Box::new(Threshold::new(ThresholdMethod::AdaptiveGaussian {
+                block_size: 7,
+                offset: 2_f64,
+            })) as FilterBase,
+            Box::new(Resize::default()) as FilterBase,
+            Box::new(Rotate::default()) as FilterBase,
+            Box::new(Flip::default()) as FilterBase,
+            Box::new(AffineWarp::default()) as FilterBase,
+            Box::new(ConvertColorSpace::default()) as FilterBase,
+            Box::new(Clahe::default()) as FilterBase,
         ];
 
         let img = Img::empty_with_size(100, 100, fltk::enums::ColorDepth::Rgba8);
@@ -196,4 +408,36 @@ mod tests {
             println!("{} is ok", filter.get_description());
         }
     }
+
+    #[test]
+    fn wide_bit_depth_is_kept() {
+        let filters: Vec<FilterBase> = vec![
+            Box::new(LinearGaussian::default()) as FilterBase,
+            Box::new(MedianFilter::default()) as FilterBase,
+            Box::new(CutBrightness::default()) as FilterBase,
+            Box::new(EqualizeHist::default()) as FilterBase,
+            Box::new(Rgb2Gray::default()) as FilterBase,
+        ];
+
+        let mut img = Img::empty_with_size(20, 10, fltk::enums::ColorDepth::Rgb8);
+        for layer in img.layers_mut() {
+            for pos in layer.get_area().iter_pixels() {
+                layer[pos] = (pos.row * 20 + pos.col) as f64;
+            }
+        }
+        let img = img.converted_to_depth(BitDepth::U16);
+
+        for filter in filters.iter() {
+            let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+            executor_handle.reset(filter.get_steps_num(&img));
+            let res = filter.process(&img, &mut executor_handle).unwrap();
+
+            let descr = filter.get_description();
+            assert_eq!(res.bit_depth(), BitDepth::U16, "{}", descr);
+
+            let vals = res.layer(0).matrix().vals();
+            let max = vals.iter().fold(0_f64, |m, v| m.max(*v));
+            assert!(max > 255_f64, "{}: {}", descr, max);
+        }
+    }
 }
@@ -0,0 +1,192 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::super::*;
+use crate::my_err::MyError;
+use crate::processing::TaskStop;
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// each neighbour is weighted by its distance and by the difference of its value
+// from the center one, so the edges are kept
+#[derive(Clone)]
+pub struct Bilateral {
+    sigma: Sigma,
+    range_sigma: RangeSigma,
+    extend_value: ExtendValue,
+    size: FilterWindowSize,
+    spatial_weights: Vec<f64>,
+    bit_depth: BitDepth,
+}
+
+impl Bilateral {
+    pub fn new(sigma: Sigma, range_sigma: RangeSigma, extend_value: ExtendValue) -> Self {
+        // the window covers 2 sigmas in each direction
+        let r = ((2_f64 * sigma.value).ceil() as usize).max(1);
+        let size = FilterWindowSize::new(r * 2 + 1, r * 2 + 1);
+
+        let one_over_2_sigma_squared = 1_f64 / (2_f64 * sigma.value.powi(2));
+        let spatial_weights = PixelsArea::with_size(size.height, size.width)
+            .iter_pixels()
+            .map(|pos| {
+                let dist_squared =
+                    (pos.row as f64 - r as f64).powi(2) + (pos.col as f64 - r as f64).powi(2);
+                (-dist_squared * one_over_2_sigma_squared).exp()
+            })
+            .collect();
+
+        Bilateral {
+            sigma,
+            range_sigma,
+            extend_value,
+            size,
+            spatial_weights,
+            bit_depth: BitDepth::default(),
+        }
+    }
+}
+
+impl WindowFilter for Bilateral {
+    fn process_window(&self, window_buffer: &mut [f64]) -> f64 {
+        let center = window_buffer[window_buffer.len() / 2];
+        let range_sigma = self.range_sigma.value * self.bit_depth.max_value();
+        let one_over_2_range_sigma_squared = 1_f64 / (2_f64 * range_sigma.powi(2));
+
+        let mut sum = 0_f64;
+        let mut weights_sum = 0_f64;
+        for (val, spatial_weight) in window_buffer.iter().zip(self.spatial_weights.iter()) {
+            let weight =
+                spatial_weight * (-(val - center).powi(2) * one_over_2_range_sigma_squared).exp();
+            sum += val * weight;
+            weights_sum += weight;
+        }
+
+        sum / weights_sum
+    }
+
+    fn w(&self) -> usize {
+        self.size.width
+    }
+
+    fn h(&self) -> usize {
+        self.size.height
+    }
+
+    fn get_extend_value(&self) -> ExtendValue {
+        self.extend_value
+    }
+
+    fn get_iter(&self) -> FilterIterator {
+        FilterIterator {
+            width: self.w(),
+            height: self.h(),
+            cur_pos: PixelPos::default(),
+        }
+    }
+}
+
+impl Filter for Bilateral {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = Bilateral {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let rows_per_layer = img.h();
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        layers_count * rows_per_layer
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Билатеральный фильтр (сигма {}, сигма яркости {})",
+            self.sigma.value, self.range_sigma.value
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "Bilateral".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for Bilateral {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let result_mat = match layer.channel() {
+            ImgChannel::A => layer.matrix().clone(),
+            _ => process_with_window(layer.matrix(), self, executor_handle)?,
+        };
+
+        Ok(ImgLayer::new(result_mat, layer.channel()))
+    }
+}
+
+impl StringFromTo for Bilateral {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 3 {
+            return Err(MyError::new(
+                "Нужно ввести сигму расстояния, сигму яркости и способ дополнения краев на отдельных строках".to_string(),
+            ));
+        }
+
+        let sigma = Sigma::try_from_string(lines_iter.next_or_empty())?;
+        let range_sigma = RangeSigma::try_from_string(lines_iter.next_or_empty())?;
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        *self = Bilateral::new(sigma, range_sigma, extend_value);
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}",
+            self.sigma.content_to_string(),
+            self.range_sigma.content_to_string(),
+            self.extend_value.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for Bilateral {
+    fn default() -> Self {
+        Bilateral::new(
+            Sigma::new(2_f64),
+            RangeSigma::new(0.1),
+            ExtendValue::Closest,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::non_linear::check_denoiser;
+
+    #[test]
+    fn bilateral_keeps_edges_and_reduces_noise() {
+        check_denoiser(
+            &mut Bilateral::default(),
+            "Sigma: 1.5\nRange sigma: 0.1\nExt: reflect",
+        );
+    }
+}
@@ -4,11 +4,20 @@ use super::super::FilterBase;
 use super::super::*;
 use crate::my_err::MyError;
 use crate::processing::TaskStop;
+use crate::utils::LinesIter;
 use fltk::enums::ColorDepth;
 
+// share of the pixels below the automatic high threshold and the ratio of the thresholds
+const AUTO_NON_EDGE_SHARE: f64 = 0.7;
+const AUTO_LOW_TO_HIGH: f64 = 0.4;
+const AUTO_HIST_BINS: usize = 256;
+
 #[derive(Clone)]
 pub struct CannyEdgeDetection {
     name: String,
+    sigma: Sigma,
+    operator: GradientOperator,
+    thresholds: CannyThresholds,
     gaussian_filter: super::super::LinearGaussian,
     rgb2gray_filter: super::super::Rgb2Gray,
     dx_filter: super::super::LinearCustom,
@@ -16,35 +25,150 @@ pub struct CannyEdgeDetection {
 }
 
 impl CannyEdgeDetection {
-    pub fn new() -> Self {
-        let g_sz = FilterWindowSize::new(5, 5);
-        let g_ext_val = ExtendValue::Closest;
-
-        let dx_filter_coeffs: Vec<f64> = vec![-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0];
-        let dy_filter_coeffs: Vec<f64> = vec![1.0, 2.0, 1.0, 0.0, 0.0, 0.0, -1.0, -2.0, -1.0];
-
-        CannyEdgeDetection {
-            name: "Детектор краев Канни".to_string(),
-            gaussian_filter: super::super::LinearGaussian::new(g_sz, g_ext_val),
-            rgb2gray_filter: super::super::Rgb2Gray::default(),
-            dx_filter: super::super::LinearCustom::with_coeffs(
-                dx_filter_coeffs,
+    pub fn new(sigma: Sigma, operator: GradientOperator, thresholds: CannyThresholds) -> Self {
+        let derivative_filter = |coeffs: Vec<f64>| {
+            super::super::LinearCustom::with_coeffs(
+                coeffs,
                 3,
                 3,
                 ExtendValue::Closest,
                 NormalizeOption::NotNormalized,
-            ),
-            dy_filter: super::super::LinearCustom::with_coeffs(
-                dy_filter_coeffs,
-                3,
-                3,
-                ExtendValue::Closest,
-                NormalizeOption::NotNormalized,
-            ),
+            )
+        };
+
+        CannyEdgeDetection {
+            name: "Детектор краев Канни".to_string(),
+            sigma,
+            operator,
+            thresholds,
+            gaussian_filter: super::super::LinearGaussian::with_sigma(sigma, ExtendValue::Closest),
+            rgb2gray_filter: super::super::Rgb2Gray::default(),
+            dx_filter: derivative_filter(operator.x_kernel()),
+            dy_filter: derivative_filter(operator.y_kernel()),
+        }
+    }
+
+    // (low, high) relative to the max gradient magnitude
+    fn count_thresholds(&self, grad: &Matrix2D, grad_max: f64) -> (f64, f64) {
+        match self.thresholds {
+            CannyThresholds::Given { low, high } => (low, high),
+            CannyThresholds::Auto => {
+                let mut hist = [0_usize; AUTO_HIST_BINS];
+                for val in grad.vals() {
+                    let bin = (val / grad_max * (AUTO_HIST_BINS - 1) as f64) as usize;
+                    hist[bin.min(AUTO_HIST_BINS - 1)] += 1;
+                }
+
+                let non_edges_count = (grad.vals().len() as f64 * AUTO_NON_EDGE_SHARE) as usize;
+                let mut sum = 0_usize;
+                let high_bin = hist
+                    .iter()
+                    .position(|count| {
+                        sum += count;
+                        sum > non_edges_count
+                    })
+                    .unwrap_or(AUTO_HIST_BINS - 1);
+
+                let high = (high_bin + 1) as f64 / AUTO_HIST_BINS as f64;
+                (high * AUTO_LOW_TO_HIGH, high)
+            }
         }
     }
 }
 
+// the magnitude is kept if it is a local maximum along the gradient direction
+fn suppress_non_max(
+    grad: &Matrix2D,
+    dx: &Matrix2D,
+    dy: &Matrix2D,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<Matrix2D, TaskStop> {
+    const PI_OVER_8: f64 = std::f64::consts::PI / 8.0;
+
+    let grad_at = |row: isize, col: isize| -> f64 {
+        if row < 0 || col < 0 || row > grad.max_row() as isize || col > grad.max_col() as isize {
+            0_f64
+        } else {
+            grad[PixelPos::new(row as usize, col as usize)]
+        }
+    };
+
+    let mut res = Matrix2D::empty_size_of(grad);
+
+    for row in 0..grad.h() {
+        for col in 0..grad.w() {
+            let pos = PixelPos::new(row, col);
+
+            // the direction is taken modulo pi, rows go down
+            let angle = dy[pos].atan2(dx[pos]).rem_euclid(std::f64::consts::PI);
+            let (d_row, d_col) = if !(PI_OVER_8..7.0 * PI_OVER_8).contains(&angle) {
+                (0, 1)
+            } else if angle < 3.0 * PI_OVER_8 {
+                (1, 1)
+            } else if angle < 5.0 * PI_OVER_8 {
+                (1, 0)
+            } else {
+                (1, -1)
+            };
+
+            let (row, col) = (row as isize, col as isize);
+            let forward = grad_at(row + d_row, col + d_col);
+            let backward = grad_at(row - d_row, col - d_col);
+
+            if grad[pos] > forward && grad[pos] >= backward {
+                res[pos] = grad[pos];
+            }
+        }
+
+        executor_handle.complete_action()?;
+    }
+
+    Ok(res)
+}
+
+// the weak pixels connected to the strong ones through the weak ones are edges too
+fn link_by_hysteresis(
+    suppressed: &Matrix2D,
+    low: f64,
+    high: f64,
+    edge_value: f64,
+    executor_handle: &mut ExecutorHandle,
+) -> Result<Matrix2D, TaskStop> {
+    let mut res = Matrix2D::empty_size_of(suppressed);
+    let mut stack = Vec::<PixelPos>::new();
+
+    for row in 0..suppressed.h() {
+        for col in 0..suppressed.w() {
+            let pos = PixelPos::new(row, col);
+            if suppressed[pos] > 0_f64 && suppressed[pos] >= high {
+                res[pos] = edge_value;
+                stack.push(pos);
+            }
+        }
+
+        executor_handle.complete_action()?;
+    }
+
+    while let Some(pos) = stack.pop() {
+        let rows = pos.row.saturating_sub(1)..=(pos.row + 1).min(suppressed.max_row());
+        for row in rows {
+            let cols = pos.col.saturating_sub(1)..=(pos.col + 1).min(suppressed.max_col());
+            for col in cols {
+                let neighbour = PixelPos::new(row, col);
+                if res[neighbour] == 0_f64
+                    && suppressed[neighbour] > 0_f64
+                    && suppressed[neighbour] >= low
+                {
+                    res[neighbour] = edge_value;
+                    stack.push(neighbour);
+                }
+            }
+        }
+    }
+
+    Ok(res)
+}
+
 impl Filter for CannyEdgeDetection {
     fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
         let grayed: Img;
@@ -77,172 +201,37 @@ impl Filter for CannyEdgeDetection {
             .dy_filter
             .process_layer(&layer_blured, executor_handle)?;
 
-        // gradient
-        let grad: Matrix2D = {
-            let mut grad = Matrix2D::generate(
-                layer_blured
-                    .get_area()
-                    .iter_pixels()
-                    .track_progress(executor_handle),
-                |pos| (dx[pos].powi(2) + dy[pos].powi(2)).sqrt(),
-            )?;
-
-            let g_max: f64 = grad.get_max(executor_handle)?;
-
-            grad.scalar_transform_self_area(
-				grad.area().iter_pixels().track_progress(executor_handle),
-                |val, _| {
-                    *val = *val / g_max * 255.0;
-                },
-            )?;
-
-            grad
-        };
-
-        // angles
-        let angles = Matrix2D::generate(
+        // gradient magnitude
+        let grad = Matrix2D::generate(
             layer_blured
                 .get_area()
                 .iter_pixels()
                 .track_progress(executor_handle),
-            |pos| {
-                // top left: -3pi/4
-                // top right: -1pi/4
-                // bottom left: 3pi/4
-                // bottom right: 1pi/4
-                dy[pos].atan2(dx[pos])
-            },
+            |pos| dx[pos].hypot(dy[pos]),
         )?;
 
-        // non-max supression
-        let mat_non_max_supressed: Matrix2D = {
-            let generate_fcn = |pos: PixelPos| -> f64 {
-                if pos.row == 0
-                    || pos.col == 0
-                    || pos.row == grad.max_row()
-                    || pos.col == grad.max_col()
-                {
-                    return 0.0;
-                }
-
-                const PI_OVER_8: f64 = std::f64::consts::PI / 8.0;
-
-                let angle_ref = &angles[pos];
-                let angle_between =
-                    |min: f64, max_ex: f64| -> bool { min <= *angle_ref && *angle_ref < max_ex };
-
-                let (pos1, pos2) = if angle_between(-7.0 * PI_OVER_8, -5.0 * PI_OVER_8) {
-                    // top left
-                    (pos.upper_lefter(), pos.downer_righter())
-                } else if angle_between(-5.0 * PI_OVER_8, -3.0 * PI_OVER_8) {
-                    // top
-                    (pos.upper(), pos.downer())
-                } else if angle_between(-3.0 * PI_OVER_8, -1.0 * PI_OVER_8) {
-                    // top right
-                    (pos.upper_righter(), pos.downer_lefter())
-                } else if angle_between(-1.0 * PI_OVER_8, 1.0 * PI_OVER_8) {
-                    // right
-                    (pos.lefter(), pos.righter())
-                } else if angle_between(1.0 * PI_OVER_8, 3.0 * PI_OVER_8) {
-                    // down right
-                    (pos.downer_righter(), pos.upper_lefter())
-                } else if angle_between(3.0 * PI_OVER_8, 5.0 * PI_OVER_8) {
-                    // down
-                    (pos.downer(), pos.upper())
-                } else if angle_between(5.0 * PI_OVER_8, 7.0 * PI_OVER_8) {
-                    // down left
-                    (pos.downer_lefter(), pos.upper_righter())
-                } else {
-                    // left
-                    (pos.lefter(), pos.righter())
-                };
-
-                if grad[pos1] > grad[pos] || grad[pos2] > grad[pos] {
-                    0.0
-                } else {
-                    grad[pos]
-                }
-            };
-
-            Matrix2D::generate(
-                layer_blured
-                    .get_area()
-                    .iter_pixels()
-                    .track_progress(executor_handle),
-                generate_fcn,
-            )?
-        };
+        let suppressed = suppress_non_max(&grad, dx.matrix(), dy.matrix(), executor_handle)?;
 
         // double thesholding and hysteresis
-        let mat_hysteresis: Matrix2D = {
-            let max_pix_value: f64 = mat_non_max_supressed.get_max(executor_handle)?;
-            let high_tr = 0.09 * max_pix_value;
-            let low_tr = 0.05 * high_tr;
-
-            const WHITE: f64 = 255.0;
-            const BLACK: f64 = 0.0;
-
-            let generate_fcn = |pos: PixelPos| -> f64 {
-                if pos.row == 0
-                    || pos.col == 0
-                    || pos.row == grad.max_row()
-                    || pos.col == grad.max_col()
-                {
-                    return BLACK;
-                }
-
-                let is_non_relevant =
-                    |pos: PixelPos| -> bool { mat_non_max_supressed[pos] <= low_tr };
-                let is_weak = |pos: PixelPos| -> bool {
-                    low_tr <= mat_non_max_supressed[pos] && mat_non_max_supressed[pos] <= high_tr
-                };
-                let has_strong_neigbour = |pos: PixelPos| -> bool {
-                    let pixels_around: [f64; 8] = [
-                        mat_non_max_supressed[pos.upper()],
-                        mat_non_max_supressed[pos.upper_righter()],
-                        mat_non_max_supressed[pos.righter()],
-                        mat_non_max_supressed[pos.downer_righter()],
-                        mat_non_max_supressed[pos.downer()],
-                        mat_non_max_supressed[pos.downer_lefter()],
-                        mat_non_max_supressed[pos.lefter()],
-                        mat_non_max_supressed[pos.upper_lefter()],
-                    ];
-                    pixels_around.iter().any(|p| *p >= high_tr)
-                };
-
-                if is_non_relevant(pos) {
-                    BLACK
-                } else if is_weak(pos) {
-                    if has_strong_neigbour(pos) {
-                        WHITE
-                    } else {
-                        BLACK
-                    }
-                } else {
-                    // is_strong
-                    WHITE
-                }
-            };
-
-            Matrix2D::generate(
-                layer_blured
-                    .get_area()
-                    .iter_pixels()
-                    .track_progress(executor_handle),
-                generate_fcn,
-            )?
-        };
+        let grad_max = grad.vals().iter().cloned().fold(f64::EPSILON, f64::max);
+        let (low, high) = self.count_thresholds(&grad, grad_max);
+        let edges = link_by_hysteresis(
+            &suppressed,
+            low * grad_max,
+            high * grad_max,
+            img.bit_depth().max_value(),
+            executor_handle,
+        )?;
 
         // creating result
-        let layer_l = ImgLayer::new(mat_hysteresis, ImgChannel::L);
+        let layer_l = ImgLayer::new(edges, ImgChannel::L);
         let layer_a: ImgLayer = {
             let mut layer_a = Matrix2D::empty_size_of(layer_l.matrix());
-            for pos in layer_a.area().iter_pixels() {
-                layer_a[pos] = 255.0;
-            }
+            layer_a.set_rect(layer_a.area(), img.bit_depth().max_value());
             ImgLayer::new(layer_a, ImgChannel::A)
         };
-        let img_res = Img::from_layers(vec![layer_l, layer_a], ColorDepth::La8);
+        let img_res =
+            Img::from_layers_of_depth(vec![layer_l, layer_a], ColorDepth::La8, img.bit_depth());
 
         Ok(img_res)
     }
@@ -258,7 +247,7 @@ impl Filter for CannyEdgeDetection {
             }
 
              // to make blured
-            + rows_count
+            + rows_count + self.gaussian_filter.h() / 2 * 2 + img.w()
 
             // for dx
             + rows_count
@@ -267,22 +256,25 @@ impl Filter for CannyEdgeDetection {
             + rows_count
 
             // for grad
-            + 3 * rows_count
-
-            // for angles
             + rows_count
 
             // for non-max supression
             + rows_count
 
             // for hysteresis
-            + 2 * rows_count;
+            + rows_count;
 
         count
     }
 
     fn get_description(&self) -> String {
-        format!("{} {}", self.name, self.gaussian_filter.get_description())
+        format!(
+            "{} (сигма {}, {}, {})",
+            self.name,
+            self.sigma.value,
+            self.operator.get_description(),
+            self.thresholds.get_description()
+        )
     }
 
     fn get_save_name(&self) -> String {
@@ -296,20 +288,107 @@ impl Filter for CannyEdgeDetection {
 
 impl StringFromTo for CannyEdgeDetection {
     fn params_to_string(&self) -> Option<String> {
-        None
+        let params_str = format!(
+            "{}\n{}\n{}",
+            self.sigma.content_to_string(),
+            self.operator.content_to_string(),
+            self.thresholds.content_to_string()
+        );
+        Some(params_str)
     }
 
     fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        // the projects saved before the parameters were added
         if string.trim().is_empty() {
-            Ok(())
-        } else {
-            Err(MyError::new("У данного фильтра нет настроек".to_string()))
+            *self = CannyEdgeDetection::default();
+            return Ok(());
+        }
+
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 3 {
+            return Err(MyError::new(
+                "Нужно ввести сигму размытия, оператор градиента и пороги на отдельных строках"
+                    .to_string(),
+            ));
         }
+
+        let sigma = Sigma::try_from_string(lines_iter.next_or_empty())?;
+        let operator = GradientOperator::try_from_string(lines_iter.next_or_empty())?;
+        let thresholds = CannyThresholds::try_from_string(lines_iter.next_or_empty())?;
+
+        *self = CannyEdgeDetection::new(sigma, operator, thresholds);
+
+        Ok(())
     }
 }
 
 impl Default for CannyEdgeDetection {
     fn default() -> Self {
-        CannyEdgeDetection::new()
+        CannyEdgeDetection::new(
+            Sigma::new(1.4),
+            GradientOperator::Sobel,
+            CannyThresholds::Auto,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_img, run_checked};
+
+    #[test]
+    fn canny_finds_closed_contour_of_square() {
+        let square = PixelsArea::new(PixelPos::new(10, 10), PixelPos::new(19, 19));
+        let img = create_img(30, 30, ColorDepth::L8, |_, pos| {
+            if square.contains(pos) {
+                200_f64
+            } else {
+                0_f64
+            }
+        });
+
+        let mut filter = CannyEdgeDetection::default();
+        for params in [
+            "Sigma: 1.4\nOperator: sobel\nThresholds: auto",
+            "Sigma: 1\nOperator: scharr\nThresholds: 0.1 0.3",
+            "Sigma: 2\nOperator: prewitt\nThresholds: 0.2 0.5",
+        ]
+        .iter()
+        {
+            filter.try_set_from_string(params).unwrap();
+
+            let res = run_checked(&filter, &img);
+            let edges = res.layer(0);
+
+            // every row and column crossing the square has edges on both sides of it only
+            for ind in 12..18 {
+                let row: Vec<usize> = (0..30)
+                    .filter(|col| edges[PixelPos::new(ind, *col)] > 0_f64)
+                    .collect();
+                let col: Vec<usize> = (0..30)
+                    .filter(|row| edges[PixelPos::new(*row, ind)] > 0_f64)
+                    .collect();
+                assert_eq!(row.len(), 2, "{} {:?}", params, row);
+                assert_eq!(col.len(), 2, "{} {:?}", params, col);
+                assert!(row[0] >= 8 && row[0] <= 11 && row[1] >= 18 && row[1] <= 21);
+            }
+            assert!(edges
+                .get_area()
+                .iter_pixels()
+                .all(|pos| edges[pos] == 0_f64 || edges[pos] == 255_f64));
+        }
+
+        assert!(filter
+            .try_set_from_string("Sigma: 1\nOperator: sobel\nThresholds: 0.5 0.2")
+            .is_err());
+        assert!(filter
+            .try_set_from_string("Sigma: 1\nOperator: roberts\nThresholds: auto")
+            .is_err());
+        filter.try_set_from_string("").unwrap();
+        assert_eq!(
+            filter.params_to_string(),
+            CannyEdgeDetection::default().params_to_string()
+        );
     }
 }
@@ -13,6 +13,7 @@ pub struct HistogramLocalContrast {
     extend_value: ExtendValue,
     mean_filter: LinearMean,
     a_values: ARange,
+    bit_depth: BitDepth,
     name: String,
 }
 
@@ -23,6 +24,7 @@ impl HistogramLocalContrast {
             extend_value: ext_value,
             mean_filter: LinearMean::new(FilterWindowSize::new(3, 3), ExtendValue::Given(0_f64)),
             a_values,
+            bit_depth: BitDepth::U8,
             name: "Локальный контраст (гистограмма)".to_string(),
         }
     }
@@ -37,13 +39,18 @@ impl HistogramLocalContrast {
 
 impl Filter for HistogramLocalContrast {
     fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
-        process_each_layer(img, self, executor_handle)
+        let filter = HistogramLocalContrast {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
     }
 
     fn get_steps_num(&self, img: &Img) -> usize {
         let fil_size_half = self.w() / 2;
-        let mean_filter =
-            img.h() + fil_size_half * 2 + 1 + img.w() + fil_size_half * 2 + 1 + (img.h() + 2);
+        let ext_h = img.h() + fil_size_half * 2;
+        let ext_w = img.w() + fil_size_half * 2;
+        let mean_filter = ext_h + self.mean_filter.h() / 2 * 2 + ext_w;
 
         let count_hists = img.h() + 2;
 
@@ -79,10 +86,10 @@ impl Filter for HistogramLocalContrast {
 
 impl WindowFilter for HistogramLocalContrast {
     fn process_window(&self, window_buffer: &mut [f64]) -> f64 {
-        //count histogram bins
+        //count histogram bins, wider ranges are binned into 256 levels as well
         let mut hist_counts: [u32; 256_usize] = [0; 256_usize];
         for v in &window_buffer[..] {
-            hist_counts[(*v as u8) as usize] += 1;
+            hist_counts[self.bit_depth.to_u8(*v) as usize] += 1;
         }
 
         //count min and max
@@ -298,8 +305,8 @@ impl ByLayer for HistogramLocalContrast {
                 if val < 0_f64 {
                     val = 0_f64;
                 }
-                if val > 255_f64 {
-                    val = 255_f64;
+                if val > self.bit_depth.max_value() {
+                    val = self.bit_depth.max_value();
                 }
 
                 val
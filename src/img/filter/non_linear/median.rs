@@ -11,72 +11,119 @@ use fltk::enums::ColorDepth;
 pub struct MedianFilter {
     size: FilterWindowSize,
     extend_value: ExtendValue,
+    percentile: Percentile,
+    bit_depth: BitDepth,
     name: String,
 }
 
 impl MedianFilter {
     pub fn new(size: FilterWindowSize, extend_value: ExtendValue) -> Self {
+        Self::with_percentile(size, extend_value, Percentile::new(50_f64))
+    }
+
+    // 0 gives the min filter and 100 gives the max one
+    pub fn with_percentile(
+        size: FilterWindowSize,
+        extend_value: ExtendValue,
+        percentile: Percentile,
+    ) -> Self {
+        assert_eq!(size.width % 2, 1);
+        assert_eq!(size.height % 2, 1);
+
         MedianFilter {
             size,
             extend_value,
+            percentile,
+            bit_depth: BitDepth::U8,
             name: "Медианный фильтр".to_string(),
         }
     }
-}
 
-impl WindowFilter for MedianFilter {
-    /*fn process_window(&self, window_buffer: &mut [f64]) -> f64 {
-        /*
-            * Algorithm from N. Wirth's book, implementation by N. Devillard.
-            * This code in public domain.
-            */
-        let mut outer_beg: usize = 0;
-        let mut outer_end: usize = window_buffer.len() - 1;
-        let mut inner_beg: usize;
-        let mut inner_end: usize;
-        let med_ind: usize = window_buffer.len() / 2;
-        let mut median: f64;
-
-        while outer_beg < outer_end {
-            median = window_buffer[med_ind];
-            inner_beg = outer_beg;
-            inner_end = outer_end;
-
-            loop {
-                while window_buffer[inner_beg] < median { inner_beg += 1; }
-                while median < window_buffer[inner_end] { inner_end -= 1; }
-
-                if inner_beg <= inner_end {
-                    window_buffer.swap(inner_beg, inner_end);
-                    inner_beg += 1; inner_end -= 1;
-                }
-
-                if inner_beg > inner_end { break; }
+    // Huang's algorithm: the histogram of the window is updated by a column
+    // when the window moves along the row
+    fn process_row(
+        &self,
+        levels_ext: &Matrix2D,
+        row: usize,
+        hist: &mut SlidingHist,
+        res_row: &mut [f64],
+    ) {
+        let rank = self.percentile.rank_of(self.w() * self.h());
+
+        let col_levels = |col: usize| {
+            (row..row + self.h()).map(move |row_w| levels_ext[PixelPos::new(row_w, col)] as usize)
+        };
+
+        for col in 0..self.w() {
+            col_levels(col).for_each(|l| hist.add(l));
+        }
+
+        for (col, res_val) in res_row.iter_mut().enumerate() {
+            if col > 0 {
+                col_levels(col - 1).for_each(|l| hist.remove(l));
+                col_levels(col + self.w() - 1).for_each(|l| hist.add(l));
             }
 
-            if inner_end < med_ind { outer_beg = inner_beg; }
-            if med_ind < inner_beg { outer_end = inner_end; }
+            *res_val = self.bit_depth.value_of_level(hist.level_of_rank(rank));
         }
 
-        window_buffer[med_ind]
-    }*/
+        // leave the histogram empty for the next row
+        let last_col = res_row.len() - 1;
+        for col in last_col..last_col + self.w() {
+            col_levels(col).for_each(|l| hist.remove(l));
+        }
+    }
+}
 
-    fn process_window(&self, window_buffer: &mut [f64]) -> f64 {
-        let mut hist_buffer = [0_usize; 256];
+struct SlidingHist {
+    counts: Vec<usize>,
+    // the previous result, it is close to the next one for neighbour windows
+    level: usize,
+    // count of the values with levels less than 'level'
+    below: usize,
+}
 
-        for val in window_buffer.iter() {
-            let ind = (*val as u8) as usize;
-            hist_buffer[ind] += 1_usize;
+impl SlidingHist {
+    fn new(levels_count: usize) -> Self {
+        SlidingHist {
+            counts: vec![0; levels_count],
+            level: 0,
+            below: 0,
         }
+    }
 
-        let mut values_until_median = window_buffer.len() / 2;
-        let mut bin_ind = 0_usize;
-        while bin_ind < hist_buffer.len() && values_until_median > hist_buffer[bin_ind] {
-            values_until_median -= hist_buffer[bin_ind];
-            bin_ind += 1;
+    fn add(&mut self, level: usize) {
+        self.counts[level] += 1;
+        if level < self.level {
+            self.below += 1;
         }
+    }
 
-        bin_ind as f64
+    fn remove(&mut self, level: usize) {
+        self.counts[level] -= 1;
+        if level < self.level {
+            self.below -= 1;
+        }
+    }
+
+    fn level_of_rank(&mut self, rank: usize) -> usize {
+        while self.below + self.counts[self.level] <= rank {
+            self.below += self.counts[self.level];
+            self.level += 1;
+        }
+        while self.below > rank {
+            self.level -= 1;
+            self.below -= self.counts[self.level];
+        }
+        self.level
+    }
+}
+
+impl WindowFilter for MedianFilter {
+    fn process_window(&self, window_buffer: &mut [f64]) -> f64 {
+        let rank: usize = self.percentile.rank_of(window_buffer.len());
+        let (_, val, _) = window_buffer.select_nth_unstable_by(rank, |a, b| a.total_cmp(b));
+        *val
     }
 
     fn w(&self) -> usize {
@@ -102,7 +149,11 @@ impl WindowFilter for MedianFilter {
 
 impl Filter for MedianFilter {
     fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
-        process_each_layer(img, self, executor_handle)
+        let filter = MedianFilter {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
     }
 
     fn get_steps_num(&self, img: &Img) -> usize {
@@ -118,7 +169,16 @@ impl Filter for MedianFilter {
     }
 
     fn get_description(&self) -> String {
-        format!("{} {}x{}", &self.name, self.h(), self.w())
+        if (self.percentile.value - 50_f64).abs() <= f64::EPSILON {
+            format!("{} {}x{}", &self.name, self.h(), self.w())
+        } else {
+            format!(
+                "Ранговый фильтр ({}%) {}x{}",
+                self.percentile.value,
+                self.h(),
+                self.w()
+            )
+        }
     }
 
     fn get_save_name(&self) -> String {
@@ -134,28 +194,35 @@ impl Filter for MedianFilter {
 impl StringFromTo for MedianFilter {
     fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
         let mut lines_iter = LinesIter::new(string);
-        if lines_iter.len() != 2 {
-            return Err(MyError::new("Должно быть 2 строки".to_string()));
+        let lines_count = lines_iter.len();
+        // the percentile line is absent in the projects saved before it was added
+        if lines_count != 2 && lines_count != 3 {
+            return Err(MyError::new("Должно быть 3 строки".to_string()));
         }
 
-        let size = FilterWindowSize::try_from_string(lines_iter.next_or_empty())?
-            .check_size_be_3()?
-            .check_w_equals_h()?
-            .check_w_h_odd()?;
+        let size =
+            FilterWindowSize::try_from_string(lines_iter.next_or_empty())?.check_w_h_odd()?;
 
         let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
 
+        let percentile = match lines_count {
+            3 => Percentile::try_from_string(lines_iter.next_or_empty())?,
+            _ => Percentile::new(50_f64),
+        };
+
         self.size = size;
         self.extend_value = extend_value;
+        self.percentile = percentile;
 
         Ok(())
     }
 
     fn params_to_string(&self) -> Option<String> {
         let params_str = format!(
-            "{}\n{}",
+            "{}\n{}\n{}",
             self.size.content_to_string(),
-            self.extend_value.content_to_string()
+            self.extend_value.content_to_string(),
+            self.percentile.content_to_string()
         );
         Some(params_str)
     }
@@ -173,13 +240,107 @@ impl ByLayer for MedianFilter {
         layer: &ImgLayer,
         executor_handle: &mut ExecutorHandle,
     ) -> Result<ImgLayer, TaskStop> {
-        let result_mat = {
-            match layer.channel() {
-                ImgChannel::A => layer.matrix().clone(),
-                _ => process_with_window(layer.matrix(), self, executor_handle)?,
+        let mat = match layer.channel() {
+            ImgChannel::A => return Ok(layer.clone()),
+            // the histogram levels would quantize the float values
+            _ if self.bit_depth == BitDepth::F32 => {
+                let result_mat = process_with_window(layer.matrix(), self, executor_handle)?;
+                return Ok(ImgLayer::new(result_mat, layer.channel()));
             }
+            _ => layer.matrix(),
         };
 
+        // values are ranked by their histogram levels
+        let mut levels_ext = mat.extended_for_window_filter(self);
+        for val in levels_ext.vals_mut() {
+            *val = self.bit_depth.level_of(*val) as f64;
+        }
+
+        let mut result_mat = Matrix2D::empty_size_of(mat);
+
+        let handle: &ExecutorHandle = executor_handle;
+
+        result_mat
+            .vals_mut()
+            .par_chunks_mut(mat.w())
+            .enumerate()
+            .try_for_each_init(
+                || {
+                    let hist = SlidingHist::new(self.bit_depth.levels_count());
+                    (handle.share(), hist)
+                },
+                |(worker_handle, hist), (row, res_row)| {
+                    self.process_row(&levels_ext, row, hist, res_row);
+                    worker_handle.complete_action()
+                },
+            )?;
+
         Ok(ImgLayer::new(result_mat, layer.channel()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_img, process_serially, run_checked};
+    use crate::processing::create_task_info_channel;
+
+    #[test]
+    fn median_keeps_float_precision() {
+        // the neighbour values fall into the same one of 65536 levels
+        let img = create_img(9, 7, ColorDepth::L8, |_, pos| {
+            0.5 + ((pos.row * 5 + pos.col * 3) % 7) as f64 * 1e-7
+        });
+        let img = Img::from_layers_of_depth(img.layers().to_vec(), ColorDepth::L8, BitDepth::F32);
+
+        let filter = MedianFilter::default();
+        let res = run_checked(&filter, &img);
+
+        let expected = process_serially(img.layer(0).matrix(), &filter);
+        assert_eq!(res.layer(0).matrix().vals(), expected.vals());
+    }
+
+    #[test]
+    fn rank_filter_matches_selection() {
+        let mut init = Matrix2D::empty_with_size(29, 19);
+        for pos in init.area().iter_pixels() {
+            init[pos] = ((pos.row * 31 + pos.col * 17) % 256) as f64;
+        }
+        let layer = ImgLayer::new(init.clone(), ImgChannel::L);
+
+        for percentile in [0_f64, 30_f64, 50_f64, 100_f64] {
+            let filter = MedianFilter::with_percentile(
+                FilterWindowSize::new(7, 3),
+                ExtendValue::Given(100_f64),
+                Percentile::new(percentile),
+            );
+
+            let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+            executor_handle.reset(init.h());
+            let res = filter.process_layer(&layer, &mut executor_handle).unwrap();
+            executor_handle.assert_all_actions_completed();
+
+            let expected = process_serially(&init, &filter);
+            assert_eq!(res.matrix().vals(), expected.vals(), "{}", percentile);
+        }
+    }
+
+    #[test]
+    fn median_params_without_percentile_are_loaded() {
+        let mut filter = MedianFilter::default();
+        filter.try_set_from_string("5 x 9\nExt: near").unwrap();
+        assert_eq!(filter.get_description(), "Медианный фильтр 5x9");
+
+        filter
+            .try_set_from_string("5 x 9\nExt: near\nPercentile: 100")
+            .unwrap();
+        assert_eq!(
+            filter.params_to_string().unwrap(),
+            "5 x 9\nExt: near\nPercentile: 100"
+        );
+
+        assert!(filter
+            .try_set_from_string("5 x 9\nExt: near\nPercentile: 101")
+            .is_err());
+    }
+}
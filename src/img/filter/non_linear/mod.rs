@@ -1,7 +1,58 @@
+mod bilateral;
 mod canny_edge_detection;
 mod histogram_local_contrast;
 mod median;
+mod morphology;
+mod non_local_means;
 
+pub use bilateral::Bilateral;
 pub use canny_edge_detection::CannyEdgeDetection;
 pub use histogram_local_contrast::HistogramLocalContrast;
 pub use median::MedianFilter;
+pub use morphology::Morphology;
+pub use non_local_means::NonLocalMeans;
+
+// a denoiser must keep a noisy step sharp while reducing its noise at least twice,
+// the alpha channel is left as it is
+#[cfg(test)]
+fn check_denoiser(filter: &mut dyn super::filter_trait::Filter, params: &str) {
+    use super::test_utils::{create_step_img, run_checked};
+    use crate::img::{Img, PixelPos};
+    use fltk::enums::ColorDepth;
+
+    let step = create_step_img(16, 12, ColorDepth::La8, |layer_num| match layer_num {
+        0 => (50_f64, 200_f64),
+        _ => (100_f64, 100_f64),
+    });
+
+    // the same step with a small noise
+    let mut img = step.clone();
+    for pos in step.get_area().iter_pixels() {
+        img.layer_mut(0)[pos] += ((pos.row * 7 + pos.col * 13) % 5) as f64 * 4_f64 - 8_f64;
+    }
+
+    // mean absolute deviation from the noiseless step
+    let deviation = |img: &Img| {
+        let sum: f64 = img
+            .get_area()
+            .iter_pixels()
+            .map(|pos| (img.layer(0)[pos] - step.layer(0)[pos]).abs())
+            .sum();
+        sum / (img.w() * img.h()) as f64
+    };
+
+    filter.try_set_from_string(params).unwrap();
+    let res = run_checked(filter, &img);
+
+    assert!(deviation(&res) < deviation(&img) / 2_f64, "{}", params);
+    for row in 0..img.h() {
+        assert!(res.layer(0)[PixelPos::new(row, 7)] < 70_f64);
+        assert!(res.layer(0)[PixelPos::new(row, 8)] > 180_f64);
+    }
+    assert!(res
+        .layer(1)
+        .matrix()
+        .vals()
+        .iter()
+        .all(|val| *val == 100_f64));
+}
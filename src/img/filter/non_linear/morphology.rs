@@ -0,0 +1,324 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::super::*;
+use crate::my_err::MyError;
+use crate::processing::TaskStop;
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+use rayon::prelude::*;
+
+#[derive(Clone)]
+pub struct Morphology {
+    op: MorphOp,
+    element: StructElement,
+    extend_value: ExtendValue,
+}
+
+impl Morphology {
+    pub fn new(op: MorphOp, element: StructElement, extend_value: ExtendValue) -> Self {
+        Morphology {
+            op,
+            element,
+            extend_value,
+        }
+    }
+
+    pub fn with_op(op: MorphOp) -> Self {
+        Morphology {
+            op,
+            ..Default::default()
+        }
+    }
+
+    fn passes_count(&self) -> usize {
+        match self.op {
+            MorphOp::Erode | MorphOp::Dilate => 1,
+            MorphOp::Open
+            | MorphOp::Close
+            | MorphOp::Gradient
+            | MorphOp::WhiteTopHat
+            | MorphOp::BlackTopHat => 2,
+        }
+    }
+
+    // positions of the element pixels in the window of size (h / 2 * 2 + 1) x (w / 2 * 2 + 1),
+    // dilation uses the element reflected about its origin
+    fn window_offsets(&self, dilate: bool) -> Vec<PixelPos> {
+        let half = PixelPos::new(self.element.h() / 2, self.element.w() / 2);
+
+        let mut offsets = Vec::<PixelPos>::new();
+        for row in 0..self.element.h() {
+            for col in 0..self.element.w() {
+                if !self.element.contains(row, col) {
+                    continue;
+                }
+                let pos = PixelPos::new(row, col);
+                offsets.push(if dilate { half + half - pos } else { pos });
+            }
+        }
+
+        offsets
+    }
+
+    // min (erosion) or max (dilation) over the element, completes an action for each row
+    fn min_max_pass(
+        &self,
+        mat: &Matrix2D,
+        dilate: bool,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Matrix2D, TaskStop> {
+        let half = PixelPos::new(self.element.h() / 2, self.element.w() / 2);
+        let mat_ext = mat.extended(self.extend_value, half.col, half.row, half.col, half.row);
+
+        let offsets = self.window_offsets(dilate);
+
+        let mut res = Matrix2D::empty_size_of(mat);
+
+        let handle: &ExecutorHandle = executor_handle;
+
+        res.vals_mut()
+            .par_chunks_mut(mat.w())
+            .enumerate()
+            .try_for_each_init(
+                || handle.share(),
+                |worker_handle, (row, res_row)| {
+                    for (col, res_val) in res_row.iter_mut().enumerate() {
+                        let pos = PixelPos::new(row, col);
+                        let vals = offsets.iter().map(|offset| mat_ext[pos + *offset]);
+                        *res_val = if dilate {
+                            vals.fold(f64::MIN, f64::max)
+                        } else {
+                            vals.fold(f64::MAX, f64::min)
+                        };
+                    }
+
+                    worker_handle.complete_action()
+                },
+            )?;
+
+        Ok(res)
+    }
+}
+
+fn difference(minuend: &Matrix2D, subtrahend: &Matrix2D) -> Matrix2D {
+    let mut res = minuend.clone();
+    for (val, sub) in res.vals_mut().iter_mut().zip(subtrahend.vals().iter()) {
+        *val -= sub;
+    }
+    res
+}
+
+impl Filter for Morphology {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        process_each_layer(img, self, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let rows_per_layer = img.h() * self.passes_count();
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        layers_count * rows_per_layer
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "{} ({})",
+            self.op.get_description(),
+            self.element.get_description()
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "Morphology".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl StringFromTo for Morphology {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() < 4 {
+            return Err(MyError::new(
+                "Нужно ввести операцию, структурный элемент и граничные условия на следующих строках"
+                    .to_string(),
+            ));
+        }
+
+        let element_lines_count = lines_iter.len() - 2;
+
+        let op = MorphOp::try_from_string(lines_iter.next_or_empty())?;
+
+        let mut element_str = String::new();
+        for _ in 0..element_lines_count {
+            element_str.push_str(lines_iter.next_or_empty());
+            element_str.push('\n');
+        }
+        let element = StructElement::try_from_string(&element_str)?;
+
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        self.op = op;
+        self.element = element;
+        self.extend_value = extend_value;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}",
+            self.op.content_to_string(),
+            self.element.content_to_string(),
+            self.extend_value.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for Morphology {
+    fn default() -> Self {
+        Morphology::new(
+            MorphOp::Erode,
+            StructElement::new(StructElementShape::Rect, FilterWindowSize::new(3, 3)),
+            ExtendValue::Closest,
+        )
+    }
+}
+
+impl ByLayer for Morphology {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let mat = match layer.channel() {
+            ImgChannel::A => return Ok(layer.clone()),
+            _ => layer.matrix(),
+        };
+
+        let result_mat = match self.op {
+            MorphOp::Erode => self.min_max_pass(mat, false, executor_handle)?,
+            MorphOp::Dilate => self.min_max_pass(mat, true, executor_handle)?,
+            MorphOp::Open => {
+                let eroded = self.min_max_pass(mat, false, executor_handle)?;
+                self.min_max_pass(&eroded, true, executor_handle)?
+            }
+            MorphOp::Close => {
+                let dilated = self.min_max_pass(mat, true, executor_handle)?;
+                self.min_max_pass(&dilated, false, executor_handle)?
+            }
+            MorphOp::Gradient => {
+                let dilated = self.min_max_pass(mat, true, executor_handle)?;
+                let eroded = self.min_max_pass(mat, false, executor_handle)?;
+                difference(&dilated, &eroded)
+            }
+            MorphOp::WhiteTopHat => {
+                let eroded = self.min_max_pass(mat, false, executor_handle)?;
+                let opened = self.min_max_pass(&eroded, true, executor_handle)?;
+                difference(mat, &opened)
+            }
+            MorphOp::BlackTopHat => {
+                let dilated = self.min_max_pass(mat, true, executor_handle)?;
+                let closed = self.min_max_pass(&dilated, false, executor_handle)?;
+                difference(&closed, mat)
+            }
+        };
+
+        Ok(ImgLayer::new(result_mat, layer.channel()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::test_utils::{create_img, run_checked};
+
+    #[test]
+    fn morphology_removes_small_details() {
+        let square = PixelsArea::new(PixelPos::new(3, 3), PixelPos::new(5, 5));
+        let detail = PixelPos::new(7, 1);
+        let img = create_img(9, 9, ColorDepth::L8, |_, pos| {
+            if square.contains(pos) || pos == detail {
+                255_f64
+            } else {
+                0_f64
+            }
+        });
+
+        let process = |op: MorphOp| {
+            let element = StructElement::new(StructElementShape::Rect, FilterWindowSize::new(3, 3));
+            let filter = Morphology::new(op, element, ExtendValue::Closest);
+            run_checked(&filter, &img).layer(0).matrix().clone()
+        };
+
+        let eroded = process(MorphOp::Erode);
+        for pos in img.get_area().iter_pixels() {
+            let is_center = pos == PixelPos::new(4, 4);
+            assert_eq!(eroded[pos], if is_center { 255_f64 } else { 0_f64 });
+        }
+
+        let opened = process(MorphOp::Open);
+        let top_hat = process(MorphOp::WhiteTopHat);
+        for pos in img.get_area().iter_pixels() {
+            let in_square = square.contains(pos);
+            assert_eq!(opened[pos], if in_square { 255_f64 } else { 0_f64 });
+            let is_detail = pos == detail;
+            assert_eq!(top_hat[pos], if is_detail { 255_f64 } else { 0_f64 });
+        }
+
+        let gradient = process(MorphOp::Gradient);
+        assert_eq!(gradient[PixelPos::new(4, 4)], 0_f64);
+        assert_eq!(gradient[PixelPos::new(3, 3)], 255_f64);
+        assert_eq!(gradient[PixelPos::new(2, 2)], 255_f64);
+    }
+
+    #[test]
+    fn morphology_params_with_custom_element() {
+        let params = "Op: dilate\nElement: custom\n0, 1\n1, 1\nExt: 0";
+
+        let mut filter = Morphology::default();
+        filter.try_set_from_string(params).unwrap();
+        assert_eq!(filter.get_description(), "Дилатация (своя матрица 2x2)");
+
+        // a single pixel grows into the shape of the element
+        let img = create_img(5, 5, ColorDepth::L8, |_, pos| {
+            if pos == PixelPos::new(2, 2) {
+                255_f64
+            } else {
+                0_f64
+            }
+        });
+        let res = run_checked(&filter, &img);
+        let bright = res
+            .get_area()
+            .iter_pixels()
+            .filter(|pos| res.layer(0)[*pos] == 255_f64)
+            .count();
+        assert_eq!(bright, 3);
+
+        filter
+            .try_set_from_string("Op: open\nElement: disk\n5 x 7\nExt: near")
+            .unwrap();
+        assert_eq!(filter.get_description(), "Размыкание (круг 5x7)");
+
+        assert!(filter
+            .try_set_from_string("Op: dilate\nElement: custom\n0, 2\nExt: 0")
+            .is_err());
+        assert!(filter
+            .try_set_from_string("Op: dilate\nElement: custom\n0, 0\nExt: 0")
+            .is_err());
+        assert!(filter
+            .try_set_from_string("Op: dilate\nElement: rect\n4 x 3\nExt: 0")
+            .is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use super::super::super::*;
+use super::super::filter_trait::*;
+use super::super::FilterBase;
+use super::super::*;
+use crate::my_err::MyError;
+use crate::processing::TaskStop;
+use crate::utils::LinesIter;
+use fltk::enums::ColorDepth;
+
+// each pixel of the search window is weighted by the similarity of its patch
+// to the patch of the center pixel;
+// the filter window holds the patches of all the pixels of the search window
+#[derive(Clone)]
+pub struct NonLocalMeans {
+    patch: FilterWindowSize,
+    search: FilterWindowSize,
+    strength: DenoiseStrength,
+    extend_value: ExtendValue,
+    bit_depth: BitDepth,
+}
+
+impl NonLocalMeans {
+    pub fn new(
+        patch: FilterWindowSize,
+        search: FilterWindowSize,
+        strength: DenoiseStrength,
+        extend_value: ExtendValue,
+    ) -> Self {
+        assert_eq!(patch.width % 2, 1);
+        assert_eq!(search.width % 2, 1);
+        assert_eq!(patch.width, patch.height);
+        assert_eq!(search.width, search.height);
+
+        NonLocalMeans {
+            patch,
+            search,
+            strength,
+            extend_value,
+            bit_depth: BitDepth::default(),
+        }
+    }
+}
+
+impl WindowFilter for NonLocalMeans {
+    fn process_window(&self, window_buffer: &mut [f64]) -> f64 {
+        let side = self.w();
+        let patch = self.patch.width;
+        let search = self.search.width;
+        let center = search / 2;
+
+        let h = self.strength.value * self.bit_depth.max_value();
+        let one_over_h_squared_by_count = 1_f64 / (h.powi(2) * (patch * patch) as f64);
+
+        let mut sum = 0_f64;
+        let mut weights_sum = 0_f64;
+        for row in 0..search {
+            for col in 0..search {
+                let mut dist_squared = 0_f64;
+                for patch_row in 0..patch {
+                    let center_start = (center + patch_row) * side + center;
+                    let other_start = (row + patch_row) * side + col;
+
+                    let center_line = &window_buffer[center_start..center_start + patch];
+                    let other_line = &window_buffer[other_start..other_start + patch];
+                    dist_squared += center_line
+                        .iter()
+                        .zip(other_line.iter())
+                        .map(|(a, b)| (a - b).powi(2))
+                        .sum::<f64>();
+                }
+
+                let weight = (-dist_squared * one_over_h_squared_by_count).exp();
+                sum += window_buffer[(row + patch / 2) * side + col + patch / 2] * weight;
+                weights_sum += weight;
+            }
+        }
+
+        sum / weights_sum
+    }
+
+    fn w(&self) -> usize {
+        self.search.width + self.patch.width - 1
+    }
+
+    fn h(&self) -> usize {
+        self.search.height + self.patch.height - 1
+    }
+
+    fn get_extend_value(&self) -> ExtendValue {
+        self.extend_value
+    }
+
+    fn get_iter(&self) -> FilterIterator {
+        FilterIterator {
+            width: self.w(),
+            height: self.h(),
+            cur_pos: PixelPos::default(),
+        }
+    }
+}
+
+impl Filter for NonLocalMeans {
+    fn process(&self, img: &Img, executor_handle: &mut ExecutorHandle) -> Result<Img, TaskStop> {
+        let filter = NonLocalMeans {
+            bit_depth: img.bit_depth(),
+            ..self.clone()
+        };
+        process_each_layer(img, &filter, executor_handle)
+    }
+
+    fn get_steps_num(&self, img: &Img) -> usize {
+        let rows_per_layer = img.h();
+        let layers_count = match img.color_depth() {
+            ColorDepth::L8 => img.d(),
+            ColorDepth::La8 => img.d() - 1,
+            ColorDepth::Rgb8 => img.d(),
+            ColorDepth::Rgba8 => img.d() - 1,
+        };
+
+        layers_count * rows_per_layer
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Нелокальные средние (фрагмент {}x{}, поиск {}x{}, сила {})",
+            self.patch.height,
+            self.patch.width,
+            self.search.height,
+            self.search.width,
+            self.strength.value
+        )
+    }
+
+    fn get_save_name(&self) -> String {
+        "NonLocalMeans".to_string()
+    }
+
+    fn get_copy(&self) -> FilterBase {
+        let copy = self.clone();
+        Box::new(copy) as FilterBase
+    }
+}
+
+impl ByLayer for NonLocalMeans {
+    fn process_layer(
+        &self,
+        layer: &ImgLayer,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<ImgLayer, TaskStop> {
+        let result_mat = match layer.channel() {
+            ImgChannel::A => layer.matrix().clone(),
+            _ => process_with_window(layer.matrix(), self, executor_handle)?,
+        };
+
+        Ok(ImgLayer::new(result_mat, layer.channel()))
+    }
+}
+
+impl StringFromTo for NonLocalMeans {
+    fn try_set_from_string(&mut self, string: &str) -> Result<(), MyError> {
+        let mut lines_iter = LinesIter::new(string);
+        if lines_iter.len() != 4 {
+            return Err(MyError::new(
+                "Нужно ввести размер фрагмента, размер окна поиска, силу фильтрации и способ дополнения краев на отдельных строках".to_string(),
+            ));
+        }
+
+        let patch = FilterWindowSize::try_from_string(lines_iter.next_or_empty())?
+            .check_w_equals_h()?
+            .check_w_h_odd()?;
+        let search = FilterWindowSize::try_from_string(lines_iter.next_or_empty())?
+            .check_size_be_3()?
+            .check_w_equals_h()?
+            .check_w_h_odd()?;
+        let strength = DenoiseStrength::try_from_string(lines_iter.next_or_empty())?;
+        let extend_value = ExtendValue::try_from_string(lines_iter.next_or_empty())?;
+
+        self.patch = patch;
+        self.search = search;
+        self.strength = strength;
+        self.extend_value = extend_value;
+
+        Ok(())
+    }
+
+    fn params_to_string(&self) -> Option<String> {
+        let params_str = format!(
+            "{}\n{}\n{}\n{}",
+            self.patch.content_to_string(),
+            self.search.content_to_string(),
+            self.strength.content_to_string(),
+            self.extend_value.content_to_string()
+        );
+        Some(params_str)
+    }
+}
+
+impl Default for NonLocalMeans {
+    fn default() -> Self {
+        NonLocalMeans::new(
+            FilterWindowSize::new(3, 3),
+            FilterWindowSize::new(11, 11),
+            DenoiseStrength::new(0.05),
+            ExtendValue::Reflect101,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img::filter::non_linear::check_denoiser;
+
+    #[test]
+    fn non_local_means_keeps_edges_and_reduces_noise() {
+        let mut filter = NonLocalMeans::default();
+        check_denoiser(&mut filter, "3 x 3\n7 x 7\nStrength: 0.05\nExt: reflect101");
+
+        assert!(filter
+            .try_set_from_string("3 x 3\n4 x 4\nStrength: 0.05\nExt: near")
+            .is_err());
+        assert!(filter
+            .try_set_from_string("3 x 3\n7 x 7\nStrength: 0\nExt: near")
+            .is_err());
+    }
+}
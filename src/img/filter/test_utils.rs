@@ -0,0 +1,80 @@
+use super::filter_trait::{Filter, WindowFilter};
+use crate::{
+    img::{Img, Matrix2D, PixelPos},
+    processing::create_task_info_channel,
+};
+use fltk::enums::ColorDepth;
+
+// processes the image like the background worker does
+// and checks that the filter reported all the steps it had announced
+pub fn run_checked(filter: &dyn Filter, img: &Img) -> Img {
+    let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+    executor_handle.reset(filter.get_steps_num(img));
+    let res = filter.process(img, &mut executor_handle).unwrap();
+    executor_handle.assert_all_actions_completed();
+    res
+}
+
+// the value of every pixel is given by its layer number and position
+pub fn create_img(
+    w: usize,
+    h: usize,
+    color_depth: ColorDepth,
+    val: impl Fn(usize, PixelPos) -> f64,
+) -> Img {
+    let mut img = Img::empty_with_size(w, h, color_depth);
+    for (layer_num, layer) in img.layers_mut().iter_mut().enumerate() {
+        for pos in layer.get_area().iter_pixels() {
+            layer[pos] = val(layer_num, pos);
+        }
+    }
+    img
+}
+
+// a vertical step between the columns w / 2 - 1 and w / 2,
+// the levels on its left and right sides are given for each layer
+pub fn create_step_img(
+    w: usize,
+    h: usize,
+    color_depth: ColorDepth,
+    levels: impl Fn(usize) -> (f64, f64),
+) -> Img {
+    create_img(w, h, color_depth, |layer_num, pos| {
+        let (left, right) = levels(layer_num);
+        if pos.col < w / 2 {
+            left
+        } else {
+            right
+        }
+    })
+}
+
+// the values grow by the given steps along the rows and the columns, wrapping at 256,
+// so a pixel of a small image shows where it came from
+pub fn create_gradient_img(
+    w: usize,
+    h: usize,
+    color_depth: ColorDepth,
+    row_step: usize,
+    col_step: usize,
+) -> Img {
+    create_img(w, h, color_depth, |_, pos| {
+        ((pos.row * row_step + pos.col * col_step) % 256) as f64
+    })
+}
+
+// the window filter applied pixel by pixel, without any of the optimizations
+pub fn process_serially<T: WindowFilter>(init: &Matrix2D, filter: &T) -> Matrix2D {
+    let mut res = Matrix2D::empty_size_of(init);
+    let mut pixel_buf = vec![0_f64; filter.w() * filter.h()];
+    let layer_ext = init.extended_for_window_filter(filter);
+
+    for pos in init.area().iter_pixels() {
+        for pos_w in filter.get_iter() {
+            pixel_buf[pos_w.row * filter.w() + pos_w.col] = layer_ext[pos + pos_w];
+        }
+        res[pos] = filter.process_window(&mut pixel_buf[..]);
+    }
+
+    res
+}
@@ -1,14 +1,22 @@
-use crate::img::{Matrix2D, PIXEL_VALUES_COUNT};
+use crate::img::{BitDepth, Matrix2D, PixelsArea};
 
-pub type HistBuf = [f64; PIXEL_VALUES_COUNT];
+pub type HistBuf = Vec<f64>;
 
-pub fn count_histogram(layer: &Matrix2D, buffer: &mut HistBuf) {
-    for elem in buffer.iter_mut() {
-        *elem = 0_f64;
-    }
+pub fn count_histogram(layer: &Matrix2D, bit_depth: BitDepth, buffer: &mut HistBuf) {
+    count_area_histogram(layer, layer.area(), bit_depth, buffer);
+}
+
+pub fn count_area_histogram(
+    layer: &Matrix2D,
+    area: PixelsArea,
+    bit_depth: BitDepth,
+    buffer: &mut HistBuf,
+) {
+    buffer.clear();
+    buffer.resize(bit_depth.levels_count(), 0_f64);
 
-    for pos in layer.area().iter_pixels() {
-        let pix_value = layer[pos] as u8 as usize;
-        buffer[pix_value] += 1.0;
+    for pos in area.iter_pixels() {
+        let pix_level = bit_depth.level_of(layer[pos]);
+        buffer[pix_level] += 1.0;
     }
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::processing::{ExecutorHandle, TaskStop};
 
 #[derive(Clone)]
 pub struct Img {
@@ -6,10 +7,19 @@ pub struct Img {
     height: usize,
     layers: Vec<ImgLayer>,
     color_depth: ColorDepth,
+    bit_depth: BitDepth,
 }
 
 impl Img {
     pub fn from_layers(layers: Vec<ImgLayer>, color_depth: ColorDepth) -> Self {
+        Self::from_layers_of_depth(layers, color_depth, BitDepth::U8)
+    }
+
+    pub fn from_layers_of_depth(
+        layers: Vec<ImgLayer>,
+        color_depth: ColorDepth,
+        bit_depth: BitDepth,
+    ) -> Self {
         let assert_layer_exists = |ch: ImgChannel| {
             layers
                 .iter()
@@ -29,18 +39,26 @@ impl Img {
                 assert_layer_exists(ImgChannel::A);
             }
             ColorDepth::Rgb8 => {
-                assert_eq!(layers.len(), 3, "there must be 3 layers: R, G, B");
-
-                assert_layer_exists(ImgChannel::R);
-                assert_layer_exists(ImgChannel::G);
-                assert_layer_exists(ImgChannel::B);
+                assert_eq!(
+                    layers.len(),
+                    3,
+                    "there must be 3 layers: R, G, B or the ones of another color space"
+                );
+
+                for ch in Self::color_space_of(&layers).channels().iter() {
+                    assert_layer_exists(*ch);
+                }
             }
             ColorDepth::Rgba8 => {
-                assert_eq!(layers.len(), 4, "there must be 4 layers: R, G, B, A");
-
-                assert_layer_exists(ImgChannel::R);
-                assert_layer_exists(ImgChannel::G);
-                assert_layer_exists(ImgChannel::B);
+                assert_eq!(
+                    layers.len(),
+                    4,
+                    "there must be 4 layers: R, G, B, A or the ones of another color space and A"
+                );
+
+                for ch in Self::color_space_of(&layers).channels().iter() {
+                    assert_layer_exists(*ch);
+                }
                 assert_layer_exists(ImgChannel::A);
             }
         }
@@ -56,6 +74,7 @@ impl Img {
             height,
             layers,
             color_depth,
+            bit_depth,
         }
     }
 
@@ -118,11 +137,23 @@ impl Img {
             height,
             layers,
             color_depth,
+            bit_depth: BitDepth::U8,
         }
     }
 
     pub fn empty_size_of(other: &Img) -> Self {
-        Self::empty_with_size(other.width, other.height, other.color_depth)
+        let layers: Vec<ImgLayer> = other
+            .layers()
+            .iter()
+            .map(|layer| {
+                ImgLayer::new(
+                    Matrix2D::empty_with_size(other.width, other.height),
+                    layer.channel(),
+                )
+            })
+            .collect();
+
+        Img::from_layers_of_depth(layers, other.color_depth, other.bit_depth)
     }
 
     pub fn from_pixels(
@@ -132,11 +163,40 @@ impl Img {
         pixels: Vec<u8>,
     ) -> Self {
         let pixels_f: Vec<f64> = pixels.iter().map(|v| *v as f64).collect();
+        Self::from_vals(width, height, color_depth, BitDepth::U8, pixels_f)
+    }
 
+    pub fn from_pixels_u16(
+        width: usize,
+        height: usize,
+        color_depth: ColorDepth,
+        pixels: Vec<u16>,
+    ) -> Self {
+        let pixels_f: Vec<f64> = pixels.iter().map(|v| *v as f64).collect();
+        Self::from_vals(width, height, color_depth, BitDepth::U16, pixels_f)
+    }
+
+    pub fn from_pixels_f32(
+        width: usize,
+        height: usize,
+        color_depth: ColorDepth,
+        pixels: Vec<f32>,
+    ) -> Self {
+        let pixels_f: Vec<f64> = pixels.iter().map(|v| *v as f64).collect();
+        Self::from_vals(width, height, color_depth, BitDepth::F32, pixels_f)
+    }
+
+    fn from_vals(
+        width: usize,
+        height: usize,
+        color_depth: ColorDepth,
+        bit_depth: BitDepth,
+        pixels_f: Vec<f64>,
+    ) -> Self {
         let layers_count = color_depth as u8 as usize;
         assert_eq!(
             width * height * layers_count,
-            pixels.len(),
+            pixels_f.len(),
             "values count doesn't satisfy color depth: {} pixels for {:?}x{}x{}",
             pixels_f.len(),
             color_depth,
@@ -145,6 +205,7 @@ impl Img {
         );
 
         let mut img = Img::empty_with_size(width, height, color_depth);
+        img.bit_depth = bit_depth;
 
         for pixel_num in 0..pixels_f.len() {
             let layer_num = pixel_num % layers_count;
@@ -167,14 +228,123 @@ impl Img {
     pub fn color_depth(&self) -> ColorDepth {
         self.color_depth
     }
+    pub fn bit_depth(&self) -> BitDepth {
+        self.bit_depth
+    }
+    // gray images have no other color spaces, they are reported as RGB ones
+    pub fn color_space(&self) -> ColorSpace {
+        Self::color_space_of(&self.layers)
+    }
+
+    fn color_space_of(layers: &[ImgLayer]) -> ColorSpace {
+        let channels: Vec<ImgChannel> = layers.iter().map(|l| l.channel()).collect();
+        ColorSpace::of_channels(&channels).unwrap_or(ColorSpace::Rgb)
+    }
 
     pub fn get_description(&self) -> String {
-        format!(
+        let descr = format!(
             "Изображение {} (строк) x {} (столбцов) x {} (каналов)",
             self.h(),
             self.w(),
             self.d()
-        )
+        );
+
+        let descr = match self.color_space() {
+            ColorSpace::Rgb => descr,
+            color_space => format!("{}, {}", descr, color_space.get_description()),
+        };
+
+        match self.bit_depth {
+            BitDepth::U8 => descr,
+            BitDepth::U16 | BitDepth::F32 => {
+                format!("{}, {}", descr, self.bit_depth.get_description())
+            }
+        }
+    }
+
+    pub fn converted_to_color_space(&self, color_space: ColorSpace) -> Img {
+        self.convert_color_space(color_space, || Ok(()))
+            .expect("conversion without progress tracking can't be stopped")
+    }
+
+    // completes an action for each row if the color space is changed
+    pub fn try_convert_color_space(
+        &self,
+        color_space: ColorSpace,
+        executor_handle: &mut ExecutorHandle,
+    ) -> Result<Img, TaskStop> {
+        self.convert_color_space(color_space, || executor_handle.complete_action())
+    }
+
+    fn convert_color_space<F: FnMut() -> Result<(), TaskStop>>(
+        &self,
+        to: ColorSpace,
+        mut complete_row: F,
+    ) -> Result<Img, TaskStop> {
+        let from = self.color_space();
+        let is_gray = matches!(self.color_depth, ColorDepth::L8 | ColorDepth::La8);
+        if is_gray || from == to {
+            return Ok(self.clone());
+        }
+
+        let max_value = self.bit_depth.max_value();
+
+        let src_channels = from.channels();
+        let src: Vec<&Matrix2D> = src_channels
+            .iter()
+            .map(|ch| self.layer_by_channel(*ch).unwrap().matrix())
+            .collect();
+
+        let mut res: Vec<Matrix2D> = (0..3)
+            .map(|_| Matrix2D::empty_with_size(self.w(), self.h()))
+            .collect();
+
+        for row in 0..self.h() {
+            for col in 0..self.w() {
+                let pos = PixelPos::new(row, col);
+
+                let vals = [
+                    src[0][pos] / max_value,
+                    src[1][pos] / max_value,
+                    src[2][pos] / max_value,
+                ];
+                let converted = to.convert_from_rgb(from.convert_to_rgb(vals));
+
+                for (mat, val) in res.iter_mut().zip(converted.iter()) {
+                    mat[pos] = val * max_value;
+                }
+            }
+
+            complete_row()?;
+        }
+
+        let mut layers: Vec<ImgLayer> = res
+            .into_iter()
+            .zip(to.channels().iter())
+            .map(|(mat, ch)| ImgLayer::new(mat, *ch))
+            .collect();
+        if let Some(layer_a) = self.layer_by_channel(ImgChannel::A) {
+            layers.push(layer_a.clone());
+        }
+
+        Ok(Img::from_layers_of_depth(
+            layers,
+            self.color_depth,
+            self.bit_depth,
+        ))
+    }
+
+    pub fn converted_to_depth(&self, bit_depth: BitDepth) -> Img {
+        let mut img = self.clone();
+        img.bit_depth = bit_depth;
+
+        for layer in img.layers_mut() {
+            for val in layer.matrix_mut().vals_mut() {
+                *val = self.bit_depth.convert_value(*val, bit_depth);
+            }
+        }
+
+        img
     }
 
     pub fn layers<'own>(&'own self) -> &'own Vec<ImgLayer> {
@@ -199,15 +369,19 @@ impl Img {
             "crop area must be inside of the img area"
         );
 
-        let mut img = Img::empty_with_size(area.w(), area.h(), self.color_depth());
-
-        for pos in area.iter_pixels() {
-            for ch_num in 0..self.d() {
-                img.layer_mut(ch_num)[pos - area.top_left()] = self.layer(ch_num)[pos];
-            }
-        }
+        let layers: Vec<ImgLayer> = self
+            .layers()
+            .iter()
+            .map(|layer| {
+                let mut mat = Matrix2D::empty_with_size(area.w(), area.h());
+                for pos in area.iter_pixels() {
+                    mat[pos - area.top_left()] = layer[pos];
+                }
+                ImgLayer::new(mat, layer.channel())
+            })
+            .collect();
 
-        img
+        Img::from_layers_of_depth(layers, self.color_depth, self.bit_depth)
     }
 
     pub fn get_area(&self) -> PixelsArea {
@@ -215,12 +389,18 @@ impl Img {
     }
 
     pub fn get_drawable_copy(&self) -> image::RgbImage {
+        if self.color_space() != ColorSpace::Rgb {
+            return self
+                .converted_to_color_space(ColorSpace::Rgb)
+                .get_drawable_copy();
+        }
+
         let mut all_pixels = Vec::<u8>::with_capacity(self.w() * self.h() * self.d());
 
         let layer_length = self.w() * self.h();
         for pix_num in 0..layer_length {
             for layer in self.layers().iter() {
-                all_pixels.push(layer[pix_num] as u8);
+                all_pixels.push(self.bit_depth.to_u8(layer[pix_num]));
             }
         }
 
@@ -252,7 +432,7 @@ impl Img {
                         left + layer.w() + right,
                         top + layer.h() + bottom,
                     );
-                    ext_mat.set_rect(ext_mat.area(), 255_f64);
+                    ext_mat.set_rect(ext_mat.area(), self.bit_depth.max_value());
                     ImgLayer::new(ext_mat, layer.channel())
                 }
                 _ => {
@@ -264,10 +444,39 @@ impl Img {
             ext_layers.push(ext_layer);
         }
 
-        Img::from_layers(ext_layers, self.color_depth())
+        Img::from_layers_of_depth(ext_layers, self.color_depth(), self.bit_depth)
+    }
+
+    pub fn try_load(path: &str) -> Result<Img, MyError> {
+        decoder::decode_file(path)
     }
 
     pub fn try_save(&self, path: &str) -> Result<(), MyError> {
+        let format = ImgFormat::from_path(path).unwrap_or(ImgFormat::Jpeg);
+        self.try_save_as(path, format)
+    }
+
+    pub fn try_save_as(&self, path: &str, format: ImgFormat) -> Result<(), MyError> {
+        // only the raw format keeps the other color spaces
+        if format != ImgFormat::Raw && self.color_space() != ColorSpace::Rgb {
+            return self
+                .converted_to_color_space(ColorSpace::Rgb)
+                .try_save_as(path, format);
+        }
+
+        match format {
+            ImgFormat::Jpeg => self.save_jpeg(path),
+            ImgFormat::Png => self.save_png(path),
+            ImgFormat::Raw => self.save_raw(path),
+        }
+    }
+
+    fn save_raw(&self, path: &str) -> Result<(), MyError> {
+        std::fs::write(path, decoder::encode_raw(self))?;
+        Ok(())
+    }
+
+    fn save_jpeg(&self, path: &str) -> Result<(), MyError> {
         use jpeg_encoder::{ColorType, Encoder};
 
         let (pixels, color_type): (Vec<u8>, ColorType) = match self.color_depth() {
@@ -277,7 +486,7 @@ impl Img {
                     .matrix()
                     .vals()
                     .iter()
-                    .map(|p| *p as u8)
+                    .map(|p| self.bit_depth.to_u8(*p))
                     .collect();
 
                 (vals, ColorType::Luma)
@@ -290,9 +499,9 @@ impl Img {
                 let b = self.layer(2).matrix().vals();
 
                 for pix_num in 0..self.w() * self.h() {
-                    vals.push(r[pix_num] as u8);
-                    vals.push(g[pix_num] as u8);
-                    vals.push(b[pix_num] as u8);
+                    vals.push(self.bit_depth.to_u8(r[pix_num]));
+                    vals.push(self.bit_depth.to_u8(g[pix_num]));
+                    vals.push(self.bit_depth.to_u8(b[pix_num]));
                 }
 
                 assert_eq!(vals.len(), self.w() * self.h() * 3);
@@ -306,12 +515,64 @@ impl Img {
 
         Ok(())
     }
+
+    fn save_png(&self, path: &str) -> Result<(), MyError> {
+        use png::{BitDepth as PngBitDepth, ColorType, Encoder};
+
+        let color_type = match self.color_depth() {
+            ColorDepth::L8 => ColorType::Grayscale,
+            ColorDepth::La8 => ColorType::GrayscaleAlpha,
+            ColorDepth::Rgb8 => ColorType::Rgb,
+            ColorDepth::Rgba8 => ColorType::Rgba,
+        };
+
+        let layers_vals: Vec<&[f64]> = self
+            .layers()
+            .iter()
+            .map(|l| l.matrix().vals().as_slice())
+            .collect();
+
+        // only 8 bit images are quantized to 8 bits
+        let png_bit_depth = match self.bit_depth {
+            BitDepth::U8 => PngBitDepth::Eight,
+            BitDepth::U16 | BitDepth::F32 => PngBitDepth::Sixteen,
+        };
+
+        let mut vals = Vec::<u8>::with_capacity(self.w() * self.h() * layers_vals.len() * 2);
+        for pix_num in 0..self.w() * self.h() {
+            for layer_vals in layers_vals.iter() {
+                match png_bit_depth {
+                    PngBitDepth::Eight => vals.push(self.bit_depth.to_u8(layer_vals[pix_num])),
+                    _ => vals.extend_from_slice(
+                        &self.bit_depth.to_u16(layer_vals[pix_num]).to_be_bytes(),
+                    ),
+                }
+            }
+        }
+
+        let file = std::fs::File::create(path)?;
+        let mut encoder = Encoder::new(
+            std::io::BufWriter::new(file),
+            self.w() as u32,
+            self.h() as u32,
+        );
+        encoder.set_color(color_type);
+        encoder.set_depth(png_bit_depth);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&vals)?;
+        writer.finish()?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Img;
-    use crate::img::{filter::filter_option::ImgChannel, ImgLayer, Matrix2D, PixelPos, PixelsArea};
+    use crate::img::{
+        filter::filter_option::ImgChannel, ColorSpace, ImgLayer, Matrix2D, PixelPos, PixelsArea,
+    };
     use fltk::{enums::ColorDepth, prelude::ImageExt};
 
     #[allow(non_snake_case)]
@@ -491,6 +752,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_layers_ctor_accepts_other_color_spaces() {
+        let img = Img::from_layers(
+            vec![
+                create_layer(3, 2, ImgChannel::H),
+                create_layer(3, 2, ImgChannel::S),
+                create_layer(3, 2, ImgChannel::Lt),
+                create_layer(3, 2, ImgChannel::A),
+            ],
+            ColorDepth::Rgba8,
+        );
+        assert_eq!(img.color_space(), ColorSpace::Hsl);
+
+        let cropped = img.get_cropped_copy(PixelsArea::with_size(1, 2));
+        assert_eq!(cropped.color_space(), ColorSpace::Hsl);
+        assert_eq!(cropped.layer(2).channel(), ImgChannel::Lt);
+
+        assert_eq!(Img::empty_size_of(&img).color_space(), ColorSpace::Hsl);
+    }
+
+    #[test]
+    #[should_panic(expected = "couldn't find layer")]
+    fn from_layers_ctor_panics_if_color_spaces_are_mixed() {
+        let _img = Img::from_layers(
+            vec![
+                create_layer(3, 3, ImgChannel::H),
+                create_layer(3, 3, ImgChannel::S),
+                create_layer(3, 3, ImgChannel::B),
+            ],
+            ColorDepth::Rgb8,
+        );
+    }
+
     fn create_layer(w: usize, h: usize, ch: ImgChannel) -> ImgLayer {
         ImgLayer::new(Matrix2D::empty_with_size(w, h), ch)
     }
@@ -841,7 +1135,7 @@ mod tests {
                         .expect(&format!("Couldn't find A channel"));
                     assert!(layer_a
                         .matrix()
-                        .pixels()
+                        .vals()
                         .iter()
                         .all(|p| (p - 255.0).abs() <= std::f64::EPSILON));
                 }
@@ -867,7 +1161,7 @@ mod tests {
                         .expect(&format!("Couldn't find A channel"));
                     assert!(layer_a
                         .matrix()
-                        .pixels()
+                        .vals()
                         .iter()
                         .all(|p| (p - 255.0).abs() <= std::f64::EPSILON));
                 }
@@ -875,7 +1169,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn save_png_keeps_alpha() {
+        let mut layers: Vec<ImgLayer> = Vec::new();
+        for (ch_num, ch) in [ImgChannel::R, ImgChannel::G, ImgChannel::B, ImgChannel::A]
+            .iter()
+            .enumerate()
+        {
+            let mut mat = Matrix2D::empty_with_size(3, 2);
+            for pos in mat.area().iter_pixels() {
+                mat[pos] = (ch_num * 60 + pos.row * 3 + pos.col) as f64;
+            }
+            layers.push(ImgLayer::new(mat, *ch));
+        }
+        let img = Img::from_layers(layers, ColorDepth::Rgba8);
+
+        let path = std::env::temp_dir().join("image_processing_save_png_keeps_alpha.png");
+        img.try_save(path.to_str().unwrap()).unwrap();
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!((info.width, info.height), (3, 2));
+        for pix_num in 0..6 {
+            for ch_num in 0..4 {
+                assert_eq!(buf[pix_num * 4 + ch_num] as usize, ch_num * 60 + pix_num);
+            }
+        }
+    }
+
     fn assert_all_pixels_are_0(matrix: &Matrix2D) {
-        assert!(matrix.pixels().iter().all(|p| p.abs() <= std::f64::EPSILON));
+        assert!(matrix.vals().iter().all(|p| p.abs() <= std::f64::EPSILON));
     }
 }
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImgFormat {
+    Jpeg,
+    Png,
+    // lossless, keeps the values as they are, used to cache results
+    Raw,
+}
+
+impl ImgFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        Self::from_extension(&Path::new(path).extension()?.to_string_lossy())
+    }
+
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(ImgFormat::Jpeg),
+            "png" => Some(ImgFormat::Png),
+            "ipraw" => Some(ImgFormat::Raw),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImgFormat::Jpeg => "jpg",
+            ImgFormat::Png => "png",
+            ImgFormat::Raw => "ipraw",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImgFormat;
+
+    #[test]
+    fn from_path() {
+        assert_eq!(ImgFormat::from_path("a/b.jpg"), Some(ImgFormat::Jpeg));
+        assert_eq!(ImgFormat::from_path("a/b.JPEG"), Some(ImgFormat::Jpeg));
+        assert_eq!(ImgFormat::from_path("a/b.png"), Some(ImgFormat::Png));
+        assert_eq!(ImgFormat::from_path("a/b.ipraw"), Some(ImgFormat::Raw));
+        assert_eq!(ImgFormat::from_path("a/b.bmp"), None);
+        assert_eq!(ImgFormat::from_path("a/b"), None);
+        assert_eq!(ImgFormat::from_extension("PNG"), Some(ImgFormat::Png));
+    }
+}
@@ -89,6 +89,10 @@ impl Matrix2D {
         &self.vals
     }
 
+    pub fn vals_mut(&mut self) -> &mut [f64] {
+        &mut self.vals
+    }
+
     pub fn get_max(&self, executor_handle: &mut ExecutorHandle) -> Result<f64, TaskStop> {
         let mut max = self.vals[0];
 
@@ -115,6 +119,18 @@ impl Matrix2D {
         self.extended(filter.get_extend_value(), left, top, right, bottom)
     }
 
+    // value at the position that may be outside of the matrix
+    pub fn value_extended(&self, row: isize, col: isize, with: ExtendValue) -> f64 {
+        let src_row = with.source_index(row, self.h());
+        let src_col = with.source_index(col, self.w());
+
+        match (src_row, src_col, with) {
+            (Some(row), Some(col), _) => self[PixelPos::new(row, col)],
+            (_, _, ExtendValue::Given(val)) => val,
+            _ => unreachable!("only the given value has no source pixel"),
+        }
+    }
+
     pub fn extended(
         &self,
         with: ExtendValue,
@@ -126,116 +142,24 @@ impl Matrix2D {
         let mut mat_ext =
             Matrix2D::empty_with_size(left + self.w() + right, top + self.h() + bottom);
 
-        let origin = PixelPos::new(0, 0);
-
-        let margin_left = PixelPos::new(0, left);
-        let margin_top = PixelPos::new(top, 0);
-        let margin_right = PixelPos::new(0, right);
-        let margin_bottom = PixelPos::new(bottom, 0);
+        if let ExtendValue::Given(val) = with {
+            mat_ext.set_rect(mat_ext.area(), val);
+        }
 
-        let rect_left = PixelPos::new(self.h(), left);
-        let rect_top = PixelPos::new(top, self.w());
-        let rect_right = PixelPos::new(self.h(), right);
-        let rect_bottom = PixelPos::new(bottom, self.w());
+        // source of every column of the extended matrix, the same for all rows
+        let src_cols: Vec<Option<usize>> = (0..mat_ext.w())
+            .map(|col| with.source_index(col as isize - left as isize, self.w()))
+            .collect();
 
-        let mat_size = PixelPos::new(self.h(), self.w());
+        for row in 0..mat_ext.h() {
+            let src_row = match with.source_index(row as isize - top as isize, self.h()) {
+                Some(src_row) => src_row,
+                None => continue,
+            };
 
-        // ------------------------------------ top ------------------------------------
-        if top > 0 {
-            // top left
-            if left > 0 {
-                let top_left_area = PixelsArea::with_size(top, left);
-                match with {
-                    ExtendValue::Closest => mat_ext.set_rect(top_left_area, self[origin]),
-                    ExtendValue::Given(val) => mat_ext.set_rect(top_left_area, val),
-                }
-            }
-            // top middle
-            let top_moddle_area = PixelsArea::with_size(top, self.w()).with_pos(0, left);
-            match with {
-                ExtendValue::Closest => {
-                    for pos in top_moddle_area.iter_pixels() {
-                        mat_ext[pos] = self[pos.with_row(0) - margin_left];
-                    }
-                }
-                ExtendValue::Given(val) => mat_ext.set_rect(top_moddle_area, val),
-            }
-            // top right
-            if right > 0 {
-                let top_right_area = PixelsArea::with_size(top, right).with_pos(0, left + self.w());
-                match with {
-                    ExtendValue::Closest => {
-                        mat_ext.set_rect(top_right_area, self[PixelPos::new(0, self.w() - 1)])
-                    }
-                    ExtendValue::Given(val) => mat_ext.set_rect(top_right_area, val),
-                }
-            }
-        }
-        // ------------------------------------ middle ------------------------------------
-        // middle left
-        if left > 0 {
-            let middle_left_area = PixelsArea::with_size(self.h(), left).with_pos(top, 0);
-            match with {
-                ExtendValue::Closest => {
-                    for pos in middle_left_area.iter_pixels() {
-                        mat_ext[pos] = self[pos.with_col(0) - margin_top];
-                    }
-                }
-                ExtendValue::Given(val) => mat_ext.set_rect(middle_left_area, val),
-            }
-        }
-        // middle middle
-        let middle_middle_area = PixelsArea::size_of(self).with_pos(top, left);
-        for pos in middle_middle_area.iter_pixels() {
-            mat_ext[pos] = self[pos - PixelPos::new(top, left)];
-        }
-        // middle right
-        if right > 0 {
-            let middle_right_area =
-                PixelsArea::with_size(self.h(), right).with_pos(top, left + self.w());
-            match with {
-                ExtendValue::Closest => {
-                    for pos in middle_right_area.iter_pixels() {
-                        mat_ext[pos] = self[pos.with_col(self.w() - 1) - margin_top];
-                    }
-                }
-                ExtendValue::Given(val) => mat_ext.set_rect(middle_right_area, val),
-            }
-        }
-
-        // ------------------------------------ bottom ------------------------------------
-        if bottom > 0 {
-            // bottom left
-            if left > 0 {
-                let bottom_left_area =
-                    PixelsArea::with_size(bottom, left).with_pos(top + self.h(), 0);
-                match with {
-                    ExtendValue::Closest => {
-                        mat_ext.set_rect(bottom_left_area, self[PixelPos::new(self.h() - 1, 0)])
-                    }
-                    ExtendValue::Given(val) => mat_ext.set_rect(bottom_left_area, val),
-                }
-            }
-            // bottom middle
-            let bottom_middle_area =
-                PixelsArea::with_size(bottom, self.w()).with_pos(top + self.h(), left);
-            match with {
-                ExtendValue::Closest => {
-                    for pos in bottom_middle_area.iter_pixels() {
-                        mat_ext[pos] = self[pos.with_row(self.h() - 1) - margin_left];
-                    }
-                }
-                ExtendValue::Given(val) => mat_ext.set_rect(bottom_middle_area, val),
-            }
-            // bottom right
-            if right > 0 {
-                let bottom_right_area =
-                    PixelsArea::with_size(bottom, right).with_pos(top + self.h(), left + self.w());
-                match with {
-                    ExtendValue::Closest => {
-                        mat_ext.set_rect(bottom_right_area, self[self.size_vec() - PixelPos::one()])
-                    }
-                    ExtendValue::Given(val) => mat_ext.set_rect(bottom_right_area, val),
+            for (col, src_col) in src_cols.iter().enumerate() {
+                if let Some(src_col) = src_col {
+                    mat_ext[PixelPos::new(row, col)] = self[PixelPos::new(src_row, *src_col)];
                 }
             }
         }
@@ -249,6 +173,14 @@ impl Matrix2D {
         }
     }
 
+    pub fn transposed(&self) -> Matrix2D {
+        let mut res = Matrix2D::empty_with_size(self.h(), self.w());
+        for pos in self.area().iter_pixels() {
+            res[PixelPos::new(pos.col, pos.row)] = self[pos];
+        }
+        res
+    }
+
     pub fn has_the_same_values_as(&self, other: &Matrix2D) -> bool {
         if self.w() != other.w() {
             return false;
@@ -310,7 +242,7 @@ impl IndexMut<usize> for Matrix2D {
 #[cfg(test)]
 mod tests {
     use super::Matrix2D;
-    use crate::img::{PixelPos, PixelsArea};
+    use crate::img::{filter::filter_option::ExtendValue, PixelPos, PixelsArea};
 
     #[test]
     fn ctor_empty_with_size() {
@@ -445,7 +377,35 @@ mod tests {
 
 	#[test]
 	fn extended() {
-		unimplemented!()
+		let mut m = Matrix2D::empty_with_size(3, 2);
+		for (ind, val) in m.vals_mut().iter_mut().enumerate() {
+			*val = ind as f64;
+		}
+
+		// 0 1 2
+		// 3 4 5
+		let row_of = |mat: &Matrix2D, row: usize| -> Vec<f64> {
+			(0..mat.w()).map(|col| mat[PixelPos::new(row, col)]).collect()
+		};
+
+		let ext = m.extended(ExtendValue::Closest, 2, 1, 2, 0);
+		assert_eq!((ext.w(), ext.h()), (7, 3));
+		assert_eq!(row_of(&ext, 0), vec![0.0, 0.0, 0.0, 1.0, 2.0, 2.0, 2.0]);
+		assert_eq!(row_of(&ext, 2), vec![3.0, 3.0, 3.0, 4.0, 5.0, 5.0, 5.0]);
+
+		let ext = m.extended(ExtendValue::Given(9.0), 2, 1, 2, 0);
+		assert_eq!(row_of(&ext, 0), vec![9.0; 7]);
+		assert_eq!(row_of(&ext, 1), vec![9.0, 9.0, 0.0, 1.0, 2.0, 9.0, 9.0]);
+
+		let ext = m.extended(ExtendValue::Reflect, 2, 1, 2, 0);
+		assert_eq!(row_of(&ext, 0), vec![1.0, 0.0, 0.0, 1.0, 2.0, 2.0, 1.0]);
+
+		let ext = m.extended(ExtendValue::Reflect101, 2, 1, 2, 0);
+		assert_eq!(row_of(&ext, 0), vec![5.0, 4.0, 3.0, 4.0, 5.0, 4.0, 3.0]);
+
+		let ext = m.extended(ExtendValue::Wrap, 4, 3, 0, 0);
+		assert_eq!(row_of(&ext, 0), vec![5.0, 3.0, 4.0, 5.0, 3.0, 4.0, 5.0]);
+		assert_eq!(row_of(&ext, 1), vec![2.0, 0.0, 1.0, 2.0, 0.0, 1.0, 2.0]);
 	}
 
 	#[test]
@@ -7,13 +7,22 @@ use fltk::{
 };
 use std::ops::{Index, IndexMut};
 
+mod bit_depth;
+mod color_space;
+pub mod decoder;
+mod fft;
 pub mod filter;
 mod img;
+mod img_format;
 mod img_layer;
 mod iterators;
 mod matrix2d;
 
+pub use bit_depth::BitDepth;
+pub use color_space::ColorSpace;
+pub use fft::{correlate_fft, correlation_actions_num, Spectrum};
 pub use img::Img;
+pub use img_format::ImgFormat;
 pub use img_layer::ImgLayer;
 pub use iterators::*;
 pub use matrix2d::Matrix2D;
@@ -1,10 +1,17 @@
 use image_processing::{
+    img::ImgFormat,
     my_err::MyError,
     my_ui::{line::ProcessingLine, Alignable},
+    processing::run_chain,
 };
 use std::{cell::RefCell, rc::Rc};
 
 fn main() -> Result<(), MyError> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return run_from_args(&args);
+    }
+
     use fltk::{
         app::{App, Scheme},
         enums::Damage,
@@ -61,3 +68,37 @@ fn main() -> Result<(), MyError> {
 
     Ok(())
 }
+
+fn run_from_args(args: &[String]) -> Result<(), MyError> {
+    // the result is saved in JPEG unless the format is given
+    let (format, paths) = match args {
+        [command, option, ext, paths @ ..] if command == "run" && option == "--format" => {
+            match ImgFormat::from_extension(ext) {
+                Some(format) => (format, paths),
+                None => {
+                    return Err(MyError::new(format!(
+                        "Неизвестный формат результата '{}'",
+                        ext
+                    )))
+                }
+            }
+        }
+        [command, paths @ ..] if command == "run" => (ImgFormat::Jpeg, paths),
+        _ => return Err(usage_error()),
+    };
+
+    match paths {
+        [project_path, img_path, output_dir] => {
+            run_chain(project_path, Some(img_path), output_dir, format)
+        }
+        [project_path, output_dir] => run_chain(project_path, None, output_dir, format),
+        _ => Err(usage_error()),
+    }
+}
+
+fn usage_error() -> MyError {
+    MyError::new(
+        "Использование: image_processing run [--format jpg|png|ipraw] <файл проекта> [изображение] <папка результатов>"
+            .to_string(),
+    )
+}
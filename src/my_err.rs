@@ -48,3 +48,11 @@ impl From<EncodingError> for MyError {
         }
     }
 }
+
+impl From<png::EncodingError> for MyError {
+    fn from(err: png::EncodingError) -> Self {
+        MyError {
+            msg: err.to_string(),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use super::embedded_images::AssetItem;
+use super::{message::*, step::ProcessingStep, PADDING};
+use crate::{
+    img::PixelsArea,
+    my_ui::{
+        container::{MyColumn, MyRow},
+        usual::{MyButton, MyLabel},
+        Alignable,
+    },
+    processing::MAIN_BRANCH,
+};
+use fltk::{
+    app::Sender,
+    group,
+    prelude::{GroupExt, WidgetExt},
+};
+
+pub struct ProcessingBranch {
+    branch_num: usize,
+    tx: Sender<Msg>,
+    steps_widgets: Vec<ProcessingStep>,
+
+    main_column: MyColumn,
+    btns_row: MyRow,
+    btn_select: MyButton,
+    // the main chain can't be removed
+    btn_delete: Option<MyButton>,
+    lbl_descr: MyLabel,
+    scroll_area: group::Scroll,
+    scroll_pack: group::Pack,
+}
+
+impl ProcessingBranch {
+    pub fn new(w: i32, h: i32, branch_num: usize, tx: Sender<Msg>) -> Self {
+        let mut main_column = MyColumn::new(w, h);
+
+        let mut btns_row = MyRow::new(w);
+
+        let mut btn_select = MyButton::with_label("Выбрать");
+        btn_select
+            .widget_mut()
+            .set_tooltip("Добавлять новые шаги в эту ветвь");
+        btn_select.set_emit(tx, Msg::Branch(Branch::Select { branch_num }));
+
+        let btn_delete = if branch_num == MAIN_BRANCH {
+            None
+        } else {
+            let mut btn = MyButton::with_img_and_tooltip(AssetItem::DeleteStep, "Удалить ветвь");
+            btn.set_emit(tx, Msg::Branch(Branch::Delete { branch_num }));
+            Some(btn)
+        };
+
+        btns_row.end();
+
+        let lbl_descr = MyLabel::new("", w);
+
+        let scroll_area = group::Scroll::default().with_size(w, main_column.height_left());
+
+        let scroll_pack = group::Pack::default().with_size(w - PADDING, h);
+
+        scroll_pack.end();
+        scroll_area.end();
+        main_column.end();
+
+        ProcessingBranch {
+            branch_num,
+            tx,
+            steps_widgets: Vec::<ProcessingStep>::new(),
+            main_column,
+            btns_row,
+            btn_select,
+            btn_delete,
+            lbl_descr,
+            scroll_area,
+            scroll_pack,
+        }
+    }
+
+    pub fn remove_self_from(&mut self, pack: &mut group::Pack) {
+        pack.remove(self.main_column.widget_mut());
+    }
+
+    pub fn set_descr(&mut self, descr: &str, is_current: bool) {
+        if is_current {
+            self.lbl_descr
+                .set_text(&format!("{} (новые шаги здесь)", descr));
+        } else {
+            self.lbl_descr.set_text(descr);
+        }
+    }
+
+    pub fn get_steps_count(&self) -> usize {
+        self.steps_widgets.len()
+    }
+
+    pub fn step_mut(&mut self, step_num: usize) -> &mut ProcessingStep {
+        &mut self.steps_widgets[step_num]
+    }
+
+    pub fn get_selection_rect(&self, step_num: usize) -> Option<PixelsArea> {
+        self.steps_widgets[step_num].get_selection_rect()
+    }
+
+    // adds or removes the widgets at the end, the kept ones are updated by the caller
+    pub fn set_steps_count(&mut self, steps_count: usize, step_h: i32) {
+        self.scroll_pack.begin();
+
+        while self.steps_widgets.len() > steps_count {
+            let mut step = self.steps_widgets.pop().unwrap();
+            step.remove_self_from(&mut self.scroll_pack);
+
+            self.scroll_pack
+                .set_size(self.scroll_pack.w(), self.scroll_pack.h() - step_h);
+        }
+
+        while self.steps_widgets.len() < steps_count {
+            self.scroll_pack
+                .set_size(self.scroll_pack.w(), self.scroll_pack.h() + step_h);
+
+            let step_num = self.steps_widgets.len();
+            let new_step =
+                ProcessingStep::new(self.w(), step_h, self.branch_num, step_num, self.tx);
+            self.steps_widgets.push(new_step);
+        }
+
+        self.scroll_pack.end();
+    }
+
+    pub fn clear_displayed_results_from(&mut self, step_num: usize) {
+        for step in self.steps_widgets[step_num..].iter_mut() {
+            step.clear_displayed_result();
+        }
+    }
+
+    pub fn set_buttons_active(&mut self, active: bool) {
+        self.btn_select.set_active(active);
+        if let Some(ref mut btn_delete) = self.btn_delete {
+            btn_delete.set_active(active);
+        }
+        for step in self.steps_widgets.iter_mut() {
+            step.set_buttons_active(active);
+        }
+    }
+
+    pub fn redraw(&mut self) {
+        self.scroll_pack.redraw();
+        self.scroll_area.redraw();
+    }
+}
+
+impl Alignable for ProcessingBranch {
+    fn resize(&mut self, w: i32, h: i32) {
+        self.main_column.resize(w, h);
+        self.btns_row.resize(w, self.btns_row.h());
+        self.lbl_descr.resize(w, self.lbl_descr.h());
+
+        let scroll_y = self.btns_row.h() + self.lbl_descr.h();
+        self.scroll_area.set_size(w, h - scroll_y);
+
+        self.scroll_pack.set_size(w - PADDING, self.scroll_pack.h());
+
+        for step in self.steps_widgets.iter_mut() {
+            step.resize(w, h);
+        }
+    }
+
+    fn x(&self) -> i32 {
+        self.main_column.x()
+    }
+
+    fn y(&self) -> i32 {
+        self.main_column.y()
+    }
+
+    fn w(&self) -> i32 {
+        self.main_column.w()
+    }
+
+    fn h(&self) -> i32 {
+        self.main_column.h()
+    }
+}
@@ -222,6 +222,23 @@ impl MyImgPresenter {
         });
     }
 
+    pub fn set_selection_rect(&mut self, area: PixelsArea) {
+        let tx = match self.tx_resized {
+            Some(ref tx) => tx,
+            None => return,
+        };
+
+        let tl = Pos::new(area.top_left().col as i32, area.top_left().row as i32);
+        let br = Pos::new(
+            area.bottom_right().col as i32,
+            area.bottom_right().row as i32,
+        );
+        tx.send(ImgPresMsg::SelectionSet { tl, br }).unwrap();
+
+        self.btn_toggle_selection.set_toggle(true);
+        self.frame_img.redraw();
+    }
+
     pub fn get_selection_rect(&self) -> Option<PixelsArea> {
         if self.btn_toggle_selection.is_toggled() {
             let presenter_rc_mut = self
@@ -320,6 +337,9 @@ impl ImgPresRect {
             ImgPresMsg::SelectionOff => {
                 self.selection_rect = None;
             }
+            ImgPresMsg::SelectionSet { tl, br } => {
+                self.selection_rect = Some(SelectionRect::of_pixels(&self.scale_rect, tl, br));
+            }
             ImgPresMsg::ComponentResized => {
                 let view_size = view_area.size();
                 self.scale_rect.fit_scale(view_size);
@@ -411,6 +431,16 @@ impl SelectionRect {
         }
     }
 
+    fn of_pixels(scale_rect: &ScalableRect, tl: Pos, br: Pos) -> Self {
+        let tl = scale_rect.pixel_to_self(tl);
+        let br = scale_rect.pixel_to_self(br);
+
+        SelectionRect {
+            inner: DraggableRect::new(tl.x, tl.y, br.x - tl.x, br.y - tl.y),
+            drag_pos: None,
+        }
+    }
+
     fn x(&self) -> i32 {
         self.inner.x()
     }
@@ -512,5 +542,6 @@ enum ImgPresMsg {
     Fit,
     SeletionOn,
     SelectionOff,
+    SelectionSet { tl: Pos, br: Pos },
     ComponentResized,
 }
@@ -1,8 +1,14 @@
 use super::embedded_images::AssetItem;
-use super::{message::*, small_dlg::*, step::ProcessingStep, PADDING};
+use super::{branch::ProcessingBranch, message::*, small_dlg::*};
 use crate::processing::*;
 use crate::{
-    img::{filter::FilterBase, PixelsArea},
+    img::{
+        filter::{
+            filter_option::{CombineOp, FrequencyBand, GradientOperator, MorphOp},
+            FilterBase,
+        },
+        ColorSpace, ImgFormat, PixelsArea,
+    },
     my_err::MyError,
     my_ui::{
         container::*,
@@ -19,8 +25,9 @@ use chrono::{
 };
 use fltk::{
     app::{self, Receiver, Sender},
-    dialog, group,
-    prelude::{GroupExt, WidgetExt},
+    dialog,
+    enums::Shortcut,
+    prelude::GroupExt,
 };
 use std::usize;
 
@@ -29,15 +36,19 @@ enum CurrentTask {
     Importing,
     Loading,
     Processing {
+        branch_num: usize,
         step_num: usize,
         process_until_end: bool,
     },
     Saving,
     Exporting,
+    BatchProcessing,
 }
 
 pub struct ProcessingLine {
-    steps_widgets: Vec<ProcessingStep>,
+    branches_widgets: Vec<ProcessingBranch>,
+    // the branch the new steps are added to
+    current_branch: usize,
     tx_ui: Sender<Msg>,
     rx_ui: Receiver<Msg>,
     bw: BackgroundWorker,
@@ -49,8 +60,10 @@ pub struct ProcessingLine {
 
     btns_row: MyRow,
     btn_project: MyMenuButton,
+    btn_edit: MyMenuButton,
     btn_import: MyMenuButton,
     btn_add_step: MyMenuButton,
+    btn_branches: MyMenuButton,
     btn_export: MyMenuButton,
     btn_halt_processing: MyButton,
 
@@ -59,9 +72,7 @@ pub struct ProcessingLine {
     lbl_init_img: MyLabel,
     img_presenter: MyImgPresenter,
 
-    processing_col: MyColumn,
-    scroll_area: group::Scroll,
-    scroll_pack: group::Pack,
+    branches_row: MyRow,
 }
 
 impl ProcessingLine {
@@ -77,6 +88,35 @@ impl ProcessingLine {
         let mut btn_project = MyMenuButton::with_label("Проект");
         btn_project.add_emit("Зарузить", tx_ui, Msg::Project(Project::LoadProject));
         btn_project.add_emit("Сохранить как", tx_ui, Msg::Project(Project::SaveProject));
+        btn_project.add_emit(
+            "Сохранить как пакет с результатами",
+            tx_ui,
+            Msg::Project(Project::SaveBundle),
+        );
+        btn_project.add_emit(
+            "Пакетная обработка папки в JPEG",
+            tx_ui,
+            Msg::Project(Project::BatchProcess(ImgFormat::Jpeg)),
+        );
+        btn_project.add_emit(
+            "Пакетная обработка папки в PNG",
+            tx_ui,
+            Msg::Project(Project::BatchProcess(ImgFormat::Png)),
+        );
+
+        let mut btn_edit = MyMenuButton::with_label("Правка");
+        btn_edit.add_emit_with_shortcut(
+            "Отменить",
+            Shortcut::Ctrl | 'z',
+            tx_ui,
+            Msg::StepOp(StepOp::Undo),
+        );
+        btn_edit.add_emit_with_shortcut(
+            "Повторить",
+            Shortcut::Ctrl | 'y',
+            tx_ui,
+            Msg::StepOp(StepOp::Redo),
+        );
 
         let mut btn_import = MyMenuButton::with_img_and_tooltip(AssetItem::Import, "Импорт");
         btn_import.add_emit(
@@ -97,6 +137,33 @@ impl ProcessingLine {
             tx_ui,
             Msg::StepOp(StepOp::AddStep(AddStep::Rgb2Gray)),
         );
+        btn_add_step.add_emit(
+            "Цветовое пространство/RGB",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::ConvertColorSpace(ColorSpace::Rgb))),
+        );
+        btn_add_step.add_emit(
+            "Цветовое пространство/HSV",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::ConvertColorSpace(ColorSpace::Hsv))),
+        );
+        btn_add_step.add_emit(
+            "Цветовое пространство/HSL",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::ConvertColorSpace(ColorSpace::Hsl))),
+        );
+        btn_add_step.add_emit(
+            "Цветовое пространство/YCbCr",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::ConvertColorSpace(
+                ColorSpace::YCbCr,
+            ))),
+        );
+        btn_add_step.add_emit(
+            "Цветовое пространство/L*a*b*",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::ConvertColorSpace(ColorSpace::Lab))),
+        );
         btn_add_step.add_emit(
             "Линейный фильтр (усредняющий)",
             tx_ui,
@@ -117,6 +184,63 @@ impl ProcessingLine {
             tx_ui,
             Msg::StepOp(StepOp::AddStep(AddStep::Median)),
         );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Сложение",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Add))),
+        );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Вычитание",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Subtract))),
+        );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Умножение",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Multiply))),
+        );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Модуль разности",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Difference))),
+        );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Минимум",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Min))),
+        );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Максимум",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Max))),
+        );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Смешивание",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Blend {
+                alpha: 0.5,
+            }))),
+        );
+        btn_add_step.add_emit(
+            "Операции с другим шагом/Маска",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Combine(CombineOp::Mask))),
+        );
+        btn_add_step.add_emit(
+            "Нерезкое маскирование",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::UnsharpMask)),
+        );
+        btn_add_step.add_emit(
+            "Шумоподавление/Билатеральный фильтр",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Bilateral)),
+        );
+        btn_add_step.add_emit(
+            "Шумоподавление/Нелокальные средние",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::NonLocalMeans)),
+        );
         btn_add_step.add_emit(
             "Локальный контраст (гистограмма)",
             tx_ui,
@@ -127,11 +251,41 @@ impl ProcessingLine {
             tx_ui,
             Msg::StepOp(StepOp::AddStep(AddStep::CutBrightness)),
         );
+        btn_add_step.add_emit(
+            "Тональная коррекция/Гамма-коррекция",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Gamma)),
+        );
+        btn_add_step.add_emit(
+            "Тональная коррекция/Контраст и яркость",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::ContrastBrightness)),
+        );
+        btn_add_step.add_emit(
+            "Тональная коррекция/Уровни",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Levels)),
+        );
+        btn_add_step.add_emit(
+            "Тональная коррекция/Инверсия",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Invert)),
+        );
+        btn_add_step.add_emit(
+            "Тональная коррекция/Кривая",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Curve)),
+        );
         btn_add_step.add_emit(
             "Эквализация гистограммы",
             tx_ui,
             Msg::StepOp(StepOp::AddStep(AddStep::HistogramEqualizer)),
         );
+        btn_add_step.add_emit(
+            "Адаптивная эквализация (CLAHE)",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Clahe)),
+        );
         btn_add_step.add_emit(
             "Убрать канал",
             tx_ui,
@@ -147,9 +301,141 @@ impl ProcessingLine {
             tx_ui,
             Msg::StepOp(StepOp::AddStep(AddStep::CannyEdgeDetection)),
         );
+        btn_add_step.add_emit(
+            "Края и градиенты/Градиент (Собель)",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Gradient(GradientOperator::Sobel))),
+        );
+        btn_add_step.add_emit(
+            "Края и градиенты/Градиент (Шарр)",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Gradient(GradientOperator::Scharr))),
+        );
+        btn_add_step.add_emit(
+            "Края и градиенты/Градиент (Превитт)",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Gradient(
+                GradientOperator::Prewitt,
+            ))),
+        );
+        btn_add_step.add_emit(
+            "Края и градиенты/Лапласиан",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Laplacian)),
+        );
+        btn_add_step.add_emit(
+            "Края и градиенты/Лапласиан гауссиана (LoG)",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::LaplacianOfGaussian)),
+        );
+        btn_add_step.add_emit(
+            "Бинаризация",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Threshold)),
+        );
+        btn_add_step.add_emit(
+            "Морфология/Эрозия",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Morphology(MorphOp::Erode))),
+        );
+        btn_add_step.add_emit(
+            "Морфология/Дилатация",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Morphology(MorphOp::Dilate))),
+        );
+        btn_add_step.add_emit(
+            "Морфология/Размыкание",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Morphology(MorphOp::Open))),
+        );
+        btn_add_step.add_emit(
+            "Морфология/Замыкание",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Morphology(MorphOp::Close))),
+        );
+        btn_add_step.add_emit(
+            "Морфология/Морфологический градиент",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Morphology(MorphOp::Gradient))),
+        );
+        btn_add_step.add_emit(
+            "Морфология/Белый цилиндр",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Morphology(MorphOp::WhiteTopHat))),
+        );
+        btn_add_step.add_emit(
+            "Морфология/Черный цилиндр",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Morphology(MorphOp::BlackTopHat))),
+        );
+        btn_add_step.add_emit(
+            "Геометрия/Изменение размера",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Resize)),
+        );
+        btn_add_step.add_emit(
+            "Геометрия/Поворот",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Rotate)),
+        );
+        btn_add_step.add_emit(
+            "Геометрия/Отражение",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::Flip)),
+        );
+        btn_add_step.add_emit(
+            "Геометрия/Аффинное преобразование",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::AffineWarp)),
+        );
+        btn_add_step.add_emit(
+            "Частотная область/Фильтр нижних частот",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::FrequencyFilter(
+                FrequencyBand::LowPass { cutoff: 0.1 },
+            ))),
+        );
+        btn_add_step.add_emit(
+            "Частотная область/Фильтр верхних частот",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::FrequencyFilter(
+                FrequencyBand::HighPass { cutoff: 0.1 },
+            ))),
+        );
+        btn_add_step.add_emit(
+            "Частотная область/Полосовой фильтр",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::FrequencyFilter(
+                FrequencyBand::BandPass {
+                    low: 0.05,
+                    high: 0.2,
+                },
+            ))),
+        );
+        btn_add_step.add_emit(
+            "Частотная область/Спектр (логарифм амплитуды)",
+            tx_ui,
+            Msg::StepOp(StepOp::AddStep(AddStep::LogSpectrum)),
+        );
+
+        let mut btn_branches = MyMenuButton::with_label("Ветви");
+        btn_branches.add_emit(
+            "Новая ветвь от исходного изображения",
+            tx_ui,
+            Msg::Branch(Branch::Add { fork_step: None }),
+        );
 
         let mut btn_export = MyMenuButton::with_img_and_tooltip(AssetItem::Export, "Экспорт");
-        btn_export.add_emit("Сохранить результаты", tx_ui, Msg::Project(Project::Export));
+        btn_export.add_emit(
+            "Сохранить результаты в JPEG",
+            tx_ui,
+            Msg::Project(Project::Export(ImgFormat::Jpeg)),
+        );
+        btn_export.add_emit(
+            "Сохранить результаты в PNG",
+            tx_ui,
+            Msg::Project(Project::Export(ImgFormat::Png)),
+        );
 
         let mut btn_halt_processing =
             MyButton::with_img_and_tooltip(AssetItem::HaltProcessing, "Прервать обработку");
@@ -168,17 +454,9 @@ impl ProcessingLine {
 
         init_img_col.end();
 
-        let mut processing_col = MyColumn::new(w / 2, h - btns_row.h());
-
-        let scroll_area = group::Scroll::default().with_pos(x, y).with_size(w / 2, h);
-
-        let scroll_pack = group::Pack::default()
-            .with_pos(x, y)
-            .with_size(w / 2 - PADDING, h);
-
-        scroll_pack.end();
-        scroll_area.end();
-        processing_col.end();
+        // the branches are shown side by side, the widgets are added on syncing
+        let mut branches_row = MyRow::new(w / 2);
+        branches_row.end();
 
         main_row.end();
 
@@ -186,7 +464,8 @@ impl ProcessingLine {
         let background_worker = BackgroundWorker::new(executor_handle);
 
         let mut line = ProcessingLine {
-            steps_widgets: Vec::<ProcessingStep>::new(),
+            branches_widgets: Vec::<ProcessingBranch>::new(),
+            current_branch: MAIN_BRANCH,
             tx_ui,
             rx_ui,
             bw: background_worker,
@@ -200,19 +479,19 @@ impl ProcessingLine {
 
             btns_row,
             btn_project,
+            btn_edit,
             btn_import,
             btn_add_step,
+            btn_branches,
             btn_export,
             btn_halt_processing,
 
             lbl_init_img,
             total_progress_bar,
-            processing_col,
-            scroll_area,
-            scroll_pack,
+            branches_row,
         };
 
-        line.resize(w, h);
+        line.sync_branches_widgets(MAIN_BRANCH);
 
         line
     }
@@ -223,6 +502,7 @@ impl ProcessingLine {
                 Msg::Project(msg) => self.process_project_msg(msg),
                 Msg::StepOp(msg) => self.process_step_op_msg(msg, app),
                 Msg::Proc(msg) => self.process_proc_msg(msg),
+                Msg::Branch(msg) => self.process_branch_msg(msg),
             } {
                 show_err_msg(self.get_center_pos(), err);
             }
@@ -234,24 +514,41 @@ impl ProcessingLine {
     fn process_project_msg(&mut self, msg: Project) -> Result<(), MyError> {
         match msg {
             Project::Import(import_type) => self.process_project_import_msg(import_type),
-            Project::SaveProject => self.process_project_save_msg(),
+            Project::SaveProject => self.process_project_save_msg(false),
+            Project::SaveBundle => self.process_project_save_msg(true),
             Project::LoadProject => self.process_project_load_msg(),
-            Project::Export => self.process_project_start_export_msg(),
+            Project::Export(format) => self.process_project_start_export_msg(format),
+            Project::BatchProcess(format) => self.process_project_start_batch_msg(format),
         }
     }
 
     fn process_step_op_msg(&mut self, msg: StepOp, app: app::App) -> Result<(), MyError> {
         let res = match msg {
             StepOp::AddStep(msg) => self.process_step_op_add_step_msg(msg, app),
-            StepOp::Edit { step_num } => self.process_step_op_edit_step_msg(step_num, app),
-            StepOp::Delete { step_num } => self.process_step_op_remove_step_msg(step_num),
+            StepOp::Edit {
+                branch_num,
+                step_num,
+            } => self.process_step_op_edit_step_msg(branch_num, step_num, app),
+            StepOp::Delete {
+                branch_num,
+                step_num,
+            } => self.process_step_op_remove_step_msg(branch_num, step_num),
+            StepOp::EditNotes {
+                branch_num,
+                step_num,
+            } => self.process_step_op_edit_notes_msg(branch_num, step_num),
+            StepOp::Undo => self.process_step_op_undo_msg(),
+            StepOp::Redo => self.process_step_op_redo_msg(),
             StepOp::Move {
+                branch_num,
                 step_num,
                 direction,
-            } => self.process_step_op_reorder_step_msg(step_num, direction),
+            } => self.process_step_op_reorder_step_msg(branch_num, step_num, direction),
         };
 
-        self.scroll_area.redraw();
+        for branch in self.branches_widgets.iter_mut() {
+            branch.redraw();
+        }
 
         res
     }
@@ -259,21 +556,79 @@ impl ProcessingLine {
     fn process_proc_msg(&mut self, msg: Proc) -> Result<(), MyError> {
         match msg {
             Proc::StartStepsChain {
+                branch_num,
                 step_num,
                 process_until_end,
-            } => self.process_proc_start_chain_msg(step_num, process_until_end),
+            } => self.process_proc_start_chain_msg(branch_num, step_num, process_until_end),
             Proc::HaltStepsChain => self.process_proc_halt_msg(),
         }
     }
 
+    fn process_branch_msg(&mut self, msg: Branch) -> Result<(), MyError> {
+        match msg {
+            Branch::Add { fork_step } => self.process_branch_add_msg(fork_step),
+            Branch::Select { branch_num } => {
+                self.current_branch = branch_num;
+                self.update_branches_descr();
+                Ok(())
+            }
+            Branch::Delete { branch_num } => self.process_branch_delete_msg(branch_num),
+        }
+    }
+
+    fn process_branch_add_msg(&mut self, fork_step: Option<usize>) -> Result<(), MyError> {
+        let branch_num: usize = self.branches_widgets.len();
+
+        let center = self.get_center_pos();
+        let default_name = format!("Ветвь {}", branch_num);
+        let name: String = match dialog::input(center.x, center.y, "Название ветви", &default_name)
+        {
+            Some(name) => name.trim().to_string(),
+            None => return Ok(()),
+        };
+
+        if name.is_empty() {
+            return Err(MyError::new(
+                "Название ветви не может быть пустым".to_string(),
+            ));
+        }
+
+        self.current_branch = self.bw.locked().add_branch(name, fork_step);
+
+        self.sync_branches_widgets(branch_num);
+
+        Ok(())
+    }
+
+    fn process_branch_delete_msg(&mut self, branch_num: usize) -> Result<(), MyError> {
+        if !confirm_with_dlg(
+            self.get_center_pos(),
+            "Удалить ветвь со всеми ее шагами? Отменить удаление и предыдущие действия будет нельзя",
+        ) {
+            return Ok(());
+        }
+
+        self.bw.locked().remove_branch(branch_num);
+
+        if self.current_branch == branch_num {
+            self.current_branch = MAIN_BRANCH;
+        } else if self.current_branch > branch_num {
+            self.current_branch -= 1;
+        }
+
+        self.sync_branches_widgets(branch_num);
+
+        Ok(())
+    }
+
     fn process_project_import_msg(&mut self, import_type: ImportType) -> Result<(), MyError> {
         if self.bw.locked().has_initial_img() {
             if confirm_with_dlg(
                 self.get_center_pos(),
                 "Для открытия нового изображения нужно удалить предыдущие результаты. Продолжить?",
             ) {
-                for step in self.steps_widgets.iter_mut() {
-                    step.clear_displayed_result();
+                for branch in self.branches_widgets.iter_mut() {
+                    branch.clear_displayed_results_from(0);
                 }
             } else {
                 return Ok(());
@@ -308,9 +663,9 @@ impl ProcessingLine {
         Ok(())
     }
 
-    fn process_project_save_msg(&mut self) -> Result<(), MyError> {
+    fn process_project_save_msg(&mut self, with_results: bool) -> Result<(), MyError> {
         // check if there are any steps
-        if self.steps_widgets.len() == 0 {
+        if !self.has_steps() {
             return Err(MyError::new(
                 "В проекте нет шагов для сохранения".to_string(),
             ));
@@ -343,29 +698,33 @@ impl ProcessingLine {
 
         self.set_task_and_freeze_ui(CurrentTask::Saving, "Сохранение проекта");
 
+        let crop_area: Option<PixelsArea> = self.img_presenter.get_selection_rect();
+
         self.bw.start_task(TaskSetup::SaveProject {
             file_path: proj_path.to_string(),
+            crop_area,
+            with_results,
         });
 
         Ok(())
     }
 
     fn process_project_load_msg(&mut self) -> Result<(), MyError> {
-        if self.steps_widgets.len() > 0 {
+        if self.has_steps() {
             if confirm_with_dlg(
                 self.get_center_pos(),
                 "Есть несохраненный проект. Открыть вместо него?",
             ) {
-                while self.steps_widgets.len() > 0 {
-                    self.process_step_op_remove_step_msg(0)?;
-                }
+                self.bw.locked().clear_steps();
+                self.current_branch = MAIN_BRANCH;
+                self.sync_branches_widgets(MAIN_BRANCH);
             } else {
                 return Ok(());
             }
         }
 
         let mut dlg = dialog::FileDialog::new(dialog::FileDialogType::BrowseFile);
-        dlg.set_filter(&format!("*.{}", PROJECT_EXT));
+        dlg.set_filter(&format!("*.{{{},{}}}", PROJECT_EXT, LEGACY_PROJECT_EXT));
         dlg.set_title("Загрузка проекта");
         dlg.show();
 
@@ -393,7 +752,7 @@ impl ProcessingLine {
         Ok(())
     }
 
-    fn process_project_start_export_msg(&mut self) -> Result<(), MyError> {
+    fn process_project_start_export_msg(&mut self, format: ImgFormat) -> Result<(), MyError> {
         match self.bw.locked().check_if_can_export() {
             StartResultsSavingResult::NoSteps => {
                 return Err(MyError::new(
@@ -436,14 +795,66 @@ impl ProcessingLine {
 
         self.bw.start_task(TaskSetup::Export {
             dir_path: proj_path,
+            format,
         });
 
         Ok(())
     }
 
+    fn process_project_start_batch_msg(&mut self, format: ImgFormat) -> Result<(), MyError> {
+        if self.bw.locked().get_steps_count(MAIN_BRANCH) == 0 {
+            return Err(MyError::new(
+                "В основной цепочке проекта нет шагов обработки для пакетной обработки".to_string(),
+            ));
+        }
+
+        let input_dir: String = match Self::choose_dir("Папка с изображениями для обработки")?
+        {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        let mut output_dir: String = match Self::choose_dir("Папка для сохранения результатов")?
+        {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+
+        self.set_task_and_freeze_ui(CurrentTask::BatchProcessing, "Пакетная обработка");
+
+        output_dir.push('/');
+        let dir_name = format!("Batch results {}", Self::cur_time_str());
+        output_dir.push_str(&dir_name);
+
+        self.bw.start_task(TaskSetup::BatchProcess {
+            input_dir,
+            output_dir,
+            format,
+        });
+
+        Ok(())
+    }
+
+    fn choose_dir(title: &str) -> Result<Option<String>, MyError> {
+        let mut dlg = dialog::FileDialog::new(dialog::FileDialogType::BrowseDir);
+        dlg.set_title(title);
+
+        dlg.show();
+
+        let path_buf = dlg.filename();
+
+        match path_buf.to_str() {
+            Some("") => Ok(None),
+            Some(path) => Ok(Some(path.to_string())),
+            None => Err(MyError::new(
+                "Не получилось перевести выбранный путь в строку".to_string(),
+            )),
+        }
+    }
+
     fn process_step_op_add_step_msg(&mut self, msg: AddStep, app: app::App) -> Result<(), MyError> {
         if let Some(filter) = step_editor::create(msg, app) {
-            self.add_step_to_background_worker_and_as_widget(filter);
+            self.add_step_to_background_worker_and_as_widget(self.current_branch, filter);
         }
 
         Ok(())
@@ -451,41 +862,36 @@ impl ProcessingLine {
 
     fn process_step_op_edit_step_msg(
         &mut self,
+        branch_num: usize,
         step_num: usize,
         app: app::App,
     ) -> Result<(), MyError> {
-        let step = &mut self.steps_widgets[step_num];
-
-        let edited = self
-            .bw
-            .locked()
-            .edit_step(step_num, |filter| step_editor::edit(app, filter));
+        let edited = self.bw.locked().edit_step(branch_num, step_num, |filter| {
+            step_editor::edit(app, filter)
+        });
 
         if edited {
-            step.set_step_descr(&self.bw.locked().get_step_descr(step_num));
+            self.sync_steps_widgets(branch_num, step_num);
         }
 
         Ok(())
     }
 
-    fn process_step_op_remove_step_msg(&mut self, step_num: usize) -> Result<(), MyError> {
-        self.scroll_pack.begin();
-        self.steps_widgets[step_num].remove_self_from(&mut self.scroll_pack);
-        self.scroll_pack.end();
-
-        self.steps_widgets.remove(step_num);
-
-        for sn in step_num..self.steps_widgets.len() {
-            self.steps_widgets[sn].update_btn_emits(sn);
-        }
+    fn process_step_op_remove_step_msg(
+        &mut self,
+        branch_num: usize,
+        step_num: usize,
+    ) -> Result<(), MyError> {
+        self.bw.locked().remove_step(branch_num, step_num)?;
 
-        self.bw.locked().remove_step(step_num);
+        self.sync_steps_widgets(branch_num, step_num);
 
         Ok(())
     }
 
     fn process_step_op_reorder_step_msg(
         &mut self,
+        branch_num: usize,
         step_num: usize,
         direction: MoveStep,
     ) -> Result<(), MyError> {
@@ -498,7 +904,7 @@ impl ProcessingLine {
                 }
             }
             MoveStep::Down => {
-                if step_num < self.steps_widgets.len() - 1 {
+                if step_num < self.branches_widgets[branch_num].get_steps_count() - 1 {
                     (step_num, step_num + 1)
                 } else {
                     return Ok(());
@@ -506,23 +912,73 @@ impl ProcessingLine {
             }
         };
 
-        self.bw.locked().swap_steps(upper_num, lower_num);
+        self.bw
+            .locked()
+            .swap_steps(branch_num, upper_num, lower_num)?;
+
+        self.sync_steps_widgets(branch_num, upper_num);
+
+        Ok(())
+    }
 
-        for step in self.steps_widgets[upper_num..].iter_mut() {
-            step.clear_displayed_result();
+    fn process_step_op_edit_notes_msg(
+        &mut self,
+        branch_num: usize,
+        step_num: usize,
+    ) -> Result<(), MyError> {
+        let notes: String = self
+            .bw
+            .locked()
+            .get_step_notes(branch_num, step_num)
+            .to_string();
+
+        let center = self.get_center_pos();
+        if let Some(notes) = dialog::input(center.x, center.y, "Заметка к шагу", &notes)
+        {
+            let notes = notes.trim().to_string();
+            self.branches_widgets[branch_num]
+                .step_mut(step_num)
+                .set_step_notes(&notes);
+            self.bw.locked().set_step_notes(branch_num, step_num, notes);
         }
-        self.steps_widgets[upper_num].set_step_descr(&self.bw.locked().get_step_descr(upper_num));
-        self.steps_widgets[lower_num].set_step_descr(&self.bw.locked().get_step_descr(lower_num));
 
         Ok(())
     }
 
+    fn process_step_op_undo_msg(&mut self) -> Result<(), MyError> {
+        let first_changed_step = self.bw.locked().undo();
+
+        match first_changed_step {
+            Some((branch_num, step_num)) => {
+                self.sync_steps_widgets(branch_num, step_num);
+                Ok(())
+            }
+            None => Err(MyError::new("Нет действий для отмены".to_string())),
+        }
+    }
+
+    fn process_step_op_redo_msg(&mut self) -> Result<(), MyError> {
+        let first_changed_step = self.bw.locked().redo();
+
+        match first_changed_step {
+            Some((branch_num, step_num)) => {
+                self.sync_steps_widgets(branch_num, step_num);
+                Ok(())
+            }
+            None => Err(MyError::new("Нет действий для повтора".to_string())),
+        }
+    }
+
     fn process_proc_start_chain_msg(
         &mut self,
+        branch_num: usize,
         step_num: usize,
         process_until_end: bool,
     ) -> Result<(), MyError> {
-        let start_proc_result = self.bw.locked().check_if_can_start_processing(step_num);
+        let start_proc_result = self
+            .bw
+            .locked()
+            .check_if_can_start_processing(branch_num, step_num);
         match start_proc_result {
             StartProcResult::NoInitialImg => Err(MyError::new(
                 "Необходимо загрузить изображение для обработки".to_string(),
@@ -530,20 +986,40 @@ impl ProcessingLine {
             StartProcResult::NoPrevStepImg => Err(MyError::new(
                 "Необходим результат предыдущего шага для обработки текущего".to_string(),
             )),
+            StartProcResult::NoForkStepImg { fork_step } => Err(MyError::new(format!(
+                "Необходим результат шага {} основной цепочки, который продолжает ветвь",
+                fork_step + 1
+            ))),
+            StartProcResult::NoInputStepImg { input_num } => Err(MyError::new(format!(
+                "Необходим результат шага {}, который используется текущим шагом",
+                input_num + 1
+            ))),
             StartProcResult::CanStart => {
                 self.set_task_and_freeze_ui(
                     CurrentTask::Processing {
+                        branch_num,
                         step_num,
                         process_until_end,
                     },
                     "Общий прогресс",
                 );
 
-                for step_widget in &mut self.steps_widgets[step_num..] {
-                    step_widget.clear_displayed_result();
+                self.branches_widgets[branch_num].clear_displayed_results_from(step_num);
+
+                // the branches continuing the reprocessed steps lose their results
+                if branch_num == MAIN_BRANCH {
+                    let bw_locked = self.bw.locked();
+                    for (forked_num, branch) in self.branches_widgets.iter_mut().enumerate() {
+                        match bw_locked.get_branch_fork_step(forked_num) {
+                            Some(fork_step) if fork_step >= step_num => {
+                                branch.clear_displayed_results_from(0)
+                            }
+                            _ => {}
+                        }
+                    }
                 }
 
-                self.start_step_processing(step_num);
+                self.start_step_processing(branch_num, step_num);
 
                 Ok(())
             }
@@ -551,11 +1027,8 @@ impl ProcessingLine {
     }
 
     fn process_proc_halt_msg(&mut self) -> Result<(), MyError> {
+        // the task is finished in the message loop as soon as the halt is seen there
         self.delegator_handle.halt_task();
-        if let CurrentTask::Processing { step_num, .. } = self.current_task.as_ref().unwrap() {
-            self.steps_widgets[*step_num].display_result(None);
-        }
-        self.clear_task_and_unfreeze_ui();
         Ok(())
     }
 
@@ -576,29 +1049,53 @@ impl ProcessingLine {
                 | CurrentTask::Exporting => {
                     self.total_progress_bar.set_value(percents);
                 }
-                CurrentTask::Processing { step_num, .. } => {
-                    let total_percents = (step_num * 100 + percents) / self.steps_widgets.len();
+                CurrentTask::Processing {
+                    branch_num,
+                    step_num,
+                    ..
+                } => {
+                    let branch = &mut self.branches_widgets[branch_num];
+                    let total_percents = (step_num * 100 + percents) / branch.get_steps_count();
                     self.total_progress_bar.set_value(total_percents);
-                    self.steps_widgets[step_num].display_progress(percents);
+                    branch.step_mut(step_num).display_progress(percents);
                 }
-            },
-            TaskState::Finished { result } => {
-                match result {
-                    Ok(()) => {}
-                    Err(stop) => match stop {
-                        TaskStop::Err(err) => show_err_msg(self.get_center_pos(), err),
-                        TaskStop::Halted => {}
-                    },
+                CurrentTask::BatchProcessing => {
+                    if let Some(stage) = self.delegator_handle.get_task_stage() {
+                        let steps_count: usize =
+                            self.branches_widgets[MAIN_BRANCH].get_steps_count();
+                        self.total_progress_bar.set_label(format!(
+                            "Изображение {} из {}, шаг {} из {}",
+                            stage.num / steps_count + 1,
+                            stage.total / steps_count,
+                            stage.num % steps_count + 1,
+                            steps_count
+                        ));
+                    }
+                    self.total_progress_bar.set_value(percents);
                 }
+            },
+            TaskState::Finished { .. } => {
+                let succeeded: bool = match self.delegator_handle.get_task_result() {
+                    Ok(()) => true,
+                    Err(stop) => {
+                        match stop {
+                            TaskStop::Err(err) => show_err_msg(self.get_center_pos(), err),
+                            TaskStop::Halted => {}
+                        }
+                        false
+                    }
+                };
                 match current_task {
                     CurrentTask::Importing => self.process_import_finish(),
                     CurrentTask::Loading => self.process_project_loading_finish(),
                     CurrentTask::Processing {
+                        branch_num,
                         step_num,
                         process_until_end,
-                    } => self.process_processing_finish(step_num, process_until_end),
+                    } => self.process_processing_finish(branch_num, step_num, process_until_end),
                     CurrentTask::Saving => self.process_project_saving_finish(),
                     CurrentTask::Exporting => self.process_export_finish(),
+                    CurrentTask::BatchProcessing => self.process_batch_finish(succeeded),
                 }
             }
         }
@@ -606,7 +1103,10 @@ impl ProcessingLine {
 
     fn process_import_finish(&mut self) {
         self.clear_task_and_unfreeze_ui();
+        self.show_initial_img();
+    }
 
+    fn show_initial_img(&mut self) {
         let (init_img_descr, init_img_drawable): (String, fltk::image::RgbImage) = {
             let bw_locked = self.bw.locked();
             let init_img_descr = bw_locked.get_init_img_descr();
@@ -624,18 +1124,30 @@ impl ProcessingLine {
         show_info_msg(self.get_center_pos(), "Результаты успешно сохранены");
     }
 
-    fn process_processing_finish(&mut self, step_num: usize, process_until_end: bool) {
-        println!("finished processing");
+    fn process_batch_finish(&mut self, succeeded: bool) {
+        self.clear_task_and_unfreeze_ui();
+        if succeeded {
+            show_info_msg(self.get_center_pos(), "Пакетная обработка завершена");
+        }
+    }
 
+    fn process_processing_finish(
+        &mut self,
+        branch_num: usize,
+        step_num: usize,
+        process_until_end: bool,
+    ) {
         let bw_locked = self.bw.locked();
 
-        let drawable: Option<fltk::image::RgbImage> = bw_locked.get_step_img_drawable(step_num);
+        let drawable: Option<fltk::image::RgbImage> =
+            bw_locked.get_step_img_drawable(branch_num, step_num);
         let processing_was_halted: bool = drawable.is_none();
 
-        self.steps_widgets[step_num].display_result(drawable);
-        self.steps_widgets[step_num].set_step_descr(&bw_locked.get_step_descr(step_num));
+        let step_widget = self.branches_widgets[branch_num].step_mut(step_num);
+        step_widget.display_result(drawable);
+        step_widget.set_step_descr(&bw_locked.get_step_descr(branch_num, step_num));
 
-        let it_is_the_last_step: bool = step_num >= bw_locked.get_steps_count() - 1;
+        let it_is_the_last_step: bool = step_num >= bw_locked.get_steps_count(branch_num) - 1;
 
         drop(bw_locked);
 
@@ -645,10 +1157,11 @@ impl ProcessingLine {
         if processing_continues {
             let next_step_num: usize = step_num + 1;
             self.current_task = Some(CurrentTask::Processing {
+                branch_num,
                 step_num: next_step_num,
                 process_until_end,
             });
-            self.start_step_processing(next_step_num);
+            self.start_step_processing(branch_num, next_step_num);
         } else {
             self.clear_task_and_unfreeze_ui();
         }
@@ -661,23 +1174,18 @@ impl ProcessingLine {
 
     fn process_project_loading_finish(&mut self) {
         self.clear_task_and_unfreeze_ui();
-        let locked_bw = self.bw.locked();
+        self.current_branch = MAIN_BRANCH;
+        self.sync_branches_widgets(MAIN_BRANCH);
 
-        self.scroll_pack.set_size(self.scroll_pack.w(), 0);
-        self.scroll_pack.begin();
-        for _ in 0..locked_bw.get_steps_count() {
-            self.scroll_pack
-                .set_size(self.scroll_pack.w(), self.scroll_pack.h() + self.h());
-
-            let step_num = self.steps_widgets.len();
-
-            let mut new_step = ProcessingStep::new(self.w() / 2, self.h(), step_num, self.tx_ui);
+        if !self.bw.locked().has_initial_img() {
+            return;
+        }
 
-            new_step.set_step_descr(&locked_bw.get_step_descr(step_num));
+        self.show_initial_img();
 
-            self.steps_widgets.push(new_step);
+        if let Some(crop_area) = self.bw.locked().get_project_crop_area() {
+            self.img_presenter.set_selection_rect(crop_area);
         }
-        self.scroll_pack.end();
     }
 
     fn get_center_pos(&self) -> Pos {
@@ -687,16 +1195,22 @@ impl ProcessingLine {
         )
     }
 
-    fn start_step_processing(&mut self, step_num: usize) {
-        self.steps_widgets[step_num].display_processing_start();
+    fn start_step_processing(&mut self, branch_num: usize, step_num: usize) {
+        self.branches_widgets[branch_num]
+            .step_mut(step_num)
+            .display_processing_start();
 
-        let crop_area: Option<PixelsArea> = if step_num == 0 {
-            self.img_presenter.get_selection_rect()
-        } else {
-            self.steps_widgets[step_num - 1].get_selection_rect()
+        let fork_step: Option<usize> = self.bw.locked().get_branch_fork_step(branch_num);
+
+        // the selection is taken from the image the step processes
+        let crop_area: Option<PixelsArea> = match fork_step {
+            _ if step_num > 0 => self.branches_widgets[branch_num].get_selection_rect(step_num - 1),
+            Some(fork_step) => self.branches_widgets[MAIN_BRANCH].get_selection_rect(fork_step),
+            None => self.img_presenter.get_selection_rect(),
         };
 
         self.bw.start_task(TaskSetup::ProcessStep {
+            branch_num,
             step_num,
             crop_area,
         });
@@ -708,35 +1222,111 @@ impl ProcessingLine {
         format!("{}", current_datetime_formatter)
     }
 
-    fn add_step_to_background_worker_and_as_widget(&mut self, filter: FilterBase) {
-        self.bw.locked().add_step(filter);
+    fn add_step_to_background_worker_and_as_widget(
+        &mut self,
+        branch_num: usize,
+        filter: FilterBase,
+    ) {
+        let step_num = self.branches_widgets[branch_num].get_steps_count();
+
+        self.bw.locked().add_step(branch_num, filter);
+
+        self.sync_steps_widgets(branch_num, step_num);
+    }
+
+    fn has_steps(&self) -> bool {
+        self.branches_widgets
+            .iter()
+            .any(|branch| branch.get_steps_count() > 0)
+    }
+
+    // makes the widgets match the branches, starting from the first changed one;
+    // the widgets know the numbers of their branches, so the following ones are made anew
+    fn sync_branches_widgets(&mut self, from_branch: usize) {
+        let branches_count: usize = self.bw.locked().get_branches_count();
+
+        while self.branches_widgets.len() > from_branch {
+            let mut branch = self.branches_widgets.pop().unwrap();
+            branch.remove_self_from(self.branches_row.widget_mut());
+        }
+
+        let branch_w: i32 = self.w() / 2 / branches_count as i32;
+
+        self.branches_row.widget_mut().begin();
+        while self.branches_widgets.len() < branches_count {
+            let branch_num = self.branches_widgets.len();
+            let new_branch = ProcessingBranch::new(branch_w, self.h(), branch_num, self.tx_ui);
+            self.branches_widgets.push(new_branch);
+        }
+        self.branches_row.end();
+
+        for branch_num in from_branch..branches_count {
+            self.sync_branch_steps_widgets(branch_num, 0);
+        }
+
+        self.update_branches_descr();
+
+        self.resize(self.w(), self.h());
+    }
+
+    fn update_branches_descr(&mut self) {
+        let bw_locked = self.bw.locked();
+
+        for (branch_num, branch) in self.branches_widgets.iter_mut().enumerate() {
+            branch.set_descr(
+                &bw_locked.get_branch_descr(branch_num),
+                branch_num == self.current_branch,
+            );
+        }
+    }
+
+    // the branches continuing the main chain may lose their results with its steps
+    fn sync_steps_widgets(&mut self, branch_num: usize, from_step: usize) {
+        self.sync_branch_steps_widgets(branch_num, from_step);
 
-        self.scroll_pack.begin();
+        if branch_num == MAIN_BRANCH {
+            for forked_num in MAIN_BRANCH + 1..self.branches_widgets.len() {
+                self.sync_branch_steps_widgets(forked_num, 0);
+            }
 
-        self.scroll_pack
-            .set_size(self.scroll_pack.w(), self.scroll_pack.h() + self.h());
+            // the steps the branches continue may be renumbered
+            self.update_branches_descr();
+        }
+    }
 
-        let step_num = self.steps_widgets.len();
+    // makes the widgets match the steps, starting from the first changed one
+    fn sync_branch_steps_widgets(&mut self, branch_num: usize, from_step: usize) {
+        let bw_locked = self.bw.locked();
+        let steps_count: usize = bw_locked.get_steps_count(branch_num);
 
-        let mut new_step = ProcessingStep::new(self.w() / 2, self.h(), step_num, self.tx_ui);
+        let step_h: i32 = self.h();
+        let branch = &mut self.branches_widgets[branch_num];
+        branch.set_steps_count(steps_count, step_h);
 
-        new_step.set_step_descr(&self.bw.locked().get_step_descr(step_num));
+        for step_num in from_step..steps_count {
+            let step = branch.step_mut(step_num);
+            step.set_step_descr(&bw_locked.get_step_descr(branch_num, step_num));
+            step.set_step_notes(bw_locked.get_step_notes(branch_num, step_num));
+            step.display_result(bw_locked.get_step_img_drawable(branch_num, step_num));
+        }
 
-        self.steps_widgets.push(new_step);
+        drop(bw_locked);
 
-        self.scroll_pack.end();
+        branch.redraw();
     }
 
     fn set_task_and_freeze_ui(&mut self, task: CurrentTask, label: &str) {
         assert!(self.current_task.is_none());
         self.current_task = Some(task);
 
-        for step in self.steps_widgets.iter_mut() {
-            step.set_buttons_active(false);
+        for branch in self.branches_widgets.iter_mut() {
+            branch.set_buttons_active(false);
         }
         self.btn_project.set_active(false);
+        self.btn_edit.set_active(false);
         self.btn_import.set_active(false);
         self.btn_add_step.set_active(false);
+        self.btn_branches.set_active(false);
         self.btn_export.set_active(false);
         self.btn_halt_processing.set_active(true);
 
@@ -748,12 +1338,14 @@ impl ProcessingLine {
         assert!(self.current_task.is_some());
         self.current_task = None;
 
-        for step in self.steps_widgets.iter_mut() {
-            step.set_buttons_active(true);
+        for branch in self.branches_widgets.iter_mut() {
+            branch.set_buttons_active(true);
         }
         self.btn_project.set_active(true);
+        self.btn_edit.set_active(true);
         self.btn_import.set_active(true);
         self.btn_add_step.set_active(true);
+        self.btn_branches.set_active(true);
         self.btn_export.set_active(true);
         self.btn_halt_processing.set_active(false);
 
@@ -767,18 +1359,14 @@ impl Alignable for ProcessingLine {
 
         self.init_img_col.resize(w / 2, h);
 
-        self.processing_col.resize(w / 2, h);
-
-        self.scroll_area.set_size(w / 2, h);
-
-        self.scroll_pack
-            .set_size(w / 2 - PADDING, self.scroll_pack.h());
+        self.branches_row.resize(w / 2, h);
 
         let img_pres_y = self.btns_row.h() + self.lbl_init_img.h();
         self.img_presenter.resize(w / 2, h - img_pres_y);
 
-        for step in self.steps_widgets.iter_mut() {
-            step.resize(w / 2, h);
+        let branch_w: i32 = w / 2 / self.branches_widgets.len().max(1) as i32;
+        for branch in self.branches_widgets.iter_mut() {
+            branch.resize(branch_w, h);
         }
     }
 
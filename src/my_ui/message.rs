@@ -1,42 +1,68 @@
+use crate::img::{
+    filter::filter_option::{CombineOp, FrequencyBand, GradientOperator, MorphOp},
+    ColorSpace, ImgFormat,
+};
+
 #[derive(Debug, Copy, Clone)]
 pub enum Msg {
     Project(Project),
     StepOp(StepOp),
     Proc(Proc),
+    Branch(Branch),
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum Project {
     Import(ImportType),
     SaveProject,
+    SaveBundle,
     LoadProject,
-    Export,
+    Export(ImgFormat),
+    BatchProcess(ImgFormat),
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum StepOp {
     AddStep(AddStep),
     Edit {
+        branch_num: usize,
         step_num: usize,
     },
     Move {
+        branch_num: usize,
         step_num: usize,
         direction: MoveStep,
     },
     Delete {
+        branch_num: usize,
+        step_num: usize,
+    },
+    EditNotes {
+        branch_num: usize,
         step_num: usize,
     },
+    Undo,
+    Redo,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum Proc {
     StartStepsChain {
+        branch_num: usize,
         step_num: usize,
         process_until_end: bool,
     },
     HaltStepsChain,
 }
 
+#[derive(Debug, Copy, Clone)]
+pub enum Branch {
+    // the new branch continues the step of the main chain, the initial image if none
+    Add { fork_step: Option<usize> },
+    Select { branch_num: usize },
+    Delete { branch_num: usize },
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum ImportType {
     File,
@@ -49,13 +75,35 @@ pub enum AddStep {
     LinMean,
     LinGauss,
     Median,
+    Gamma,
+    ContrastBrightness,
+    Levels,
+    Invert,
+    Curve,
+    Combine(CombineOp),
+    UnsharpMask,
+    Bilateral,
+    NonLocalMeans,
     HistogramLocalContrast,
     CutBrightness,
     HistogramEqualizer,
+    Clahe,
     Rgb2Gray,
+    ConvertColorSpace(ColorSpace),
     NeutralizeChannel,
     ExtractChannel,
     CannyEdgeDetection,
+    Morphology(MorphOp),
+    Threshold,
+    Resize,
+    Rotate,
+    Flip,
+    AffineWarp,
+    FrequencyFilter(FrequencyBand),
+    LogSpectrum,
+    Gradient(GradientOperator),
+    Laplacian,
+    LaplacianOfGaussian,
 }
 
 #[derive(Debug, Copy, Clone)]
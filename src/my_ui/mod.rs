@@ -2,6 +2,7 @@ pub const TEXT_PADDING: i32 = 10;
 
 const PADDING: i32 = 20;
 
+mod branch;
 pub mod container;
 mod embedded_images;
 pub mod img_presenter;
@@ -8,10 +8,17 @@ use crate::{
         usual::{MyButton, MyLabel, MyMenuButton, MyProgressBar},
         Alignable,
     },
+    processing::MAIN_BRANCH,
+};
+use fltk::{
+    app::Sender,
+    group,
+    image::RgbImage,
+    prelude::{GroupExt, WidgetExt},
 };
-use fltk::{app::Sender, group, image::RgbImage, prelude::GroupExt};
 
 pub struct ProcessingStep {
+    branch_num: usize,
     step_num: usize,
     tx: Sender<Msg>,
 
@@ -20,13 +27,22 @@ pub struct ProcessingStep {
     btn_edit: MyButton,
     btn_delete: MyButton,
     btn_reorder: MyMenuButton,
+    btn_notes: MyButton,
+    // a new branch can continue only a step of the main chain
+    btn_fork: Option<MyButton>,
     label_step_name: MyLabel,
     prog_bar: MyProgressBar,
     img_presenter: MyImgPresenter,
 }
 
 impl ProcessingStep {
-    pub fn new(w: i32, h: i32, step_num: usize, tx: fltk::app::Sender<Msg>) -> Self {
+    pub fn new(
+        w: i32,
+        h: i32,
+        branch_num: usize,
+        step_num: usize,
+        tx: fltk::app::Sender<Msg>,
+    ) -> Self {
         let mut main_column = MyColumn::new(w, h);
 
         let label_step_name = MyLabel::new("", w);
@@ -38,6 +54,15 @@ impl ProcessingStep {
         let btn_delete = MyButton::with_img_and_tooltip(AssetItem::DeleteStep, "Удалить");
         let btn_reorder =
             MyMenuButton::with_img_and_tooltip(AssetItem::ReorderSteps, "Переупорядочить");
+        let btn_notes = MyButton::with_label("Заметка");
+        let btn_fork = if branch_num == MAIN_BRANCH {
+            let mut btn = MyButton::with_label("Ветвь");
+            btn.widget_mut()
+                .set_tooltip("Новая ветвь, продолжающая этот шаг");
+            Some(btn)
+        } else {
+            None
+        };
 
         btns_row.end();
 
@@ -54,9 +79,12 @@ impl ProcessingStep {
             btn_edit,
             btn_delete,
             btn_reorder,
+            btn_notes,
+            btn_fork,
             label_step_name,
             prog_bar,
             img_presenter,
+            branch_num,
             step_num,
             tx,
         };
@@ -78,11 +106,21 @@ impl ProcessingStep {
         self.label_step_name.set_text(descr);
     }
 
+    pub fn set_step_notes(&mut self, notes: &str) {
+        if notes.is_empty() {
+            self.btn_notes.widget_mut().set_tooltip("Заметка к шагу");
+        } else {
+            self.btn_notes.widget_mut().set_tooltip(notes);
+        }
+    }
+
     pub fn update_btn_emits(&mut self, step_num: usize) {
+        let branch_num = self.branch_num;
         self.btn_run.add_emit(
             "Только этот шаг",
             self.tx,
             Msg::Proc(Proc::StartStepsChain {
+                branch_num,
                 step_num,
                 process_until_end: false,
             }),
@@ -91,18 +129,45 @@ impl ProcessingStep {
             "Этот шаг и все шаги ниже",
             self.tx,
             Msg::Proc(Proc::StartStepsChain {
+                branch_num,
                 step_num,
                 process_until_end: true,
             }),
         );
-        self.btn_edit
-            .set_emit(self.tx, Msg::StepOp(StepOp::Edit { step_num }));
-        self.btn_delete
-            .set_emit(self.tx, Msg::StepOp(StepOp::Delete { step_num }));
+        self.btn_edit.set_emit(
+            self.tx,
+            Msg::StepOp(StepOp::Edit {
+                branch_num,
+                step_num,
+            }),
+        );
+        self.btn_delete.set_emit(
+            self.tx,
+            Msg::StepOp(StepOp::Delete {
+                branch_num,
+                step_num,
+            }),
+        );
+        self.btn_notes.set_emit(
+            self.tx,
+            Msg::StepOp(StepOp::EditNotes {
+                branch_num,
+                step_num,
+            }),
+        );
+        if let Some(ref mut btn_fork) = self.btn_fork {
+            btn_fork.set_emit(
+                self.tx,
+                Msg::Branch(Branch::Add {
+                    fork_step: Some(step_num),
+                }),
+            );
+        }
         self.btn_reorder.add_emit(
             "Сдвинуть вверх",
             self.tx,
             Msg::StepOp(StepOp::Move {
+                branch_num,
                 step_num,
                 direction: MoveStep::Up,
             }),
@@ -111,6 +176,7 @@ impl ProcessingStep {
             "Сдвинуть вниз",
             self.tx,
             Msg::StepOp(StepOp::Move {
+                branch_num,
                 step_num,
                 direction: MoveStep::Down,
             }),
@@ -123,6 +189,10 @@ impl ProcessingStep {
         self.btn_edit.set_active(active);
         self.btn_delete.set_active(active);
         self.btn_reorder.set_active(active);
+        self.btn_notes.set_active(active);
+        if let Some(ref mut btn_fork) = self.btn_fork {
+            btn_fork.set_active(active);
+        }
     }
 
     pub fn get_selection_rect(&self) -> Option<PixelsArea> {
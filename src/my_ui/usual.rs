@@ -416,6 +416,19 @@ impl MyMenuButton {
             .add_emit(label, Shortcut::None, menu::MenuFlag::Normal, tx, msg);
     }
 
+    pub fn add_emit_with_shortcut<TMsg>(
+        &mut self,
+        label: &str,
+        shortcut: Shortcut,
+        tx: Sender<TMsg>,
+        msg: TMsg,
+    ) where
+        TMsg: 'static + Clone + Copy + Send + Sync,
+    {
+        self.btn
+            .add_emit(label, shortcut, menu::MenuFlag::Normal, tx, msg);
+    }
+
     pub fn set_active(&mut self, active: bool) {
         if active {
             self.btn.activate();
@@ -479,6 +492,14 @@ impl MyProgressBar {
         self.set_value(0);
     }
 
+    pub fn set_label(&mut self, label: String) {
+        if self.label != label {
+            self.label = label;
+            self.bar
+                .set_label(&format!("{}: {}%", self.label, self.progress_percents));
+        }
+    }
+
     pub fn set_value(&mut self, progress_percents: usize) {
         if self.progress_percents != progress_percents {
             self.progress_percents = progress_percents;
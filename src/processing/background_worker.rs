@@ -43,8 +43,6 @@ impl BackgroundWorker {
     }
 
     pub fn start_task(&mut self, setup: TaskSetup) {
-        print!("notified ");
-
         self.locked().start_task(setup);
         self.inner.cv.notify_one();
     }
@@ -0,0 +1,40 @@
+use super::proc_step::ProcStep;
+
+pub const MAIN_BRANCH: usize = 0;
+pub const MAIN_BRANCH_NAME: &str = "Основная цепочка";
+
+pub struct Branch {
+    pub name: String,
+    // the step of the main chain the branch continues, the initial image if none;
+    // the number is shifted along with the changes of the main chain
+    pub fork_step: Option<usize>,
+    pub proc_steps: Vec<ProcStep>,
+}
+
+impl Branch {
+    pub fn new(name: String, fork_step: Option<usize>) -> Self {
+        Branch {
+            name,
+            fork_step,
+            proc_steps: Vec::new(),
+        }
+    }
+
+    pub fn main() -> Self {
+        Self::new(MAIN_BRANCH_NAME.to_string(), None)
+    }
+
+    pub fn clear_results(&mut self) {
+        for step in self.proc_steps.iter_mut() {
+            step.set_img(None);
+        }
+    }
+
+    pub fn get_description(&self, branch_num: usize) -> String {
+        match self.fork_step {
+            _ if branch_num == MAIN_BRANCH => self.name.clone(),
+            Some(step_num) => format!("{} (от шага {})", self.name, step_num + 1),
+            None => format!("{} (от исходного)", self.name),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use super::proc_step::ProcStep;
+use crate::{
+    img::{
+        filter::{filter_option::StepRef, FilterBase},
+        Img,
+    },
+    my_err::MyError,
+};
+use std::collections::VecDeque;
+
+const HISTORY_MAX_LEN: usize = 50;
+// the older records lose their results, so undoing them takes processing again
+const HISTORY_MAX_IMGS_BYTES: usize = 1 << 30;
+
+pub enum ChainChange {
+    Insert {
+        step_num: usize,
+        filter: FilterBase,
+        notes: String,
+    },
+    Remove {
+        step_num: usize,
+    },
+    Replace {
+        step_num: usize,
+        filter: FilterBase,
+    },
+    Swap {
+        step_num1: usize,
+        step_num2: usize,
+    },
+}
+
+impl ChainChange {
+    pub fn first_affected_step(&self) -> usize {
+        match self {
+            ChainChange::Insert { step_num, .. }
+            | ChainChange::Remove { step_num }
+            | ChainChange::Replace { step_num, .. } => *step_num,
+            ChainChange::Swap {
+                step_num1,
+                step_num2,
+            } => *step_num1.min(step_num2),
+        }
+    }
+
+    // the steps taking the results of other steps must stay below them
+    pub fn check_inputs(&self, proc_steps: &[ProcStep]) -> Result<(), MyError> {
+        let step_inputs = |step_num: usize| -> Vec<usize> {
+            proc_steps[step_num]
+                .filter
+                .get_inputs()
+                .into_iter()
+                .filter_map(|input| match input {
+                    StepRef::Step(input_num) if input_num < step_num => Some(input_num),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        match *self {
+            ChainChange::Insert { .. } | ChainChange::Replace { .. } => Ok(()),
+            ChainChange::Remove { step_num } => {
+                match (step_num + 1..proc_steps.len())
+                    .find(|&num| step_inputs(num).contains(&step_num))
+                {
+                    Some(user_num) => Err(MyError::new(format!(
+                        "Шаг {} нельзя удалить, его результат использует шаг {}",
+                        step_num + 1,
+                        user_num + 1
+                    ))),
+                    None => Ok(()),
+                }
+            }
+            ChainChange::Swap {
+                step_num1,
+                step_num2,
+            } => {
+                let (upper_num, lower_num) = (step_num1.min(step_num2), step_num1.max(step_num2));
+
+                // the lower step goes to the place of the upper one and the upper one goes below
+                // the steps between them
+                let moved_above = |user_num: usize, input_num: usize| {
+                    input_num == upper_num || (user_num == lower_num && input_num > upper_num)
+                };
+                let found = (upper_num + 1..=lower_num).find_map(|user_num| {
+                    step_inputs(user_num)
+                        .into_iter()
+                        .find(|&input_num| moved_above(user_num, input_num))
+                        .map(|input_num| (user_num, input_num))
+                });
+
+                match found {
+                    Some((user_num, input_num)) => Err(MyError::new(format!(
+                        "Шаг {} использует результат шага {} и должен оставаться ниже него",
+                        user_num + 1,
+                        input_num + 1
+                    ))),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    pub fn renumbering(&self) -> Renumbering {
+        match *self {
+            ChainChange::Insert { step_num, .. } => Renumbering::Inserted(step_num),
+            ChainChange::Remove { step_num } => Renumbering::Removed(step_num),
+            ChainChange::Replace { .. } => Renumbering::Same,
+            ChainChange::Swap {
+                step_num1,
+                step_num2,
+            } => Renumbering::Swapped(step_num1, step_num2),
+        }
+    }
+
+    // returns the change that reverts this one;
+    // the steps taking the results of the moved steps are renumbered as well
+    pub fn apply(self, proc_steps: &mut Vec<ProcStep>) -> ChainChange {
+        let renumbering = self.renumbering();
+
+        match self {
+            ChainChange::Insert {
+                step_num,
+                filter,
+                notes,
+            } => {
+                renumber_inputs(proc_steps, renumbering);
+                proc_steps.insert(step_num, ProcStep::new(filter, notes));
+                ChainChange::Remove { step_num }
+            }
+            ChainChange::Remove { step_num } => {
+                let step = proc_steps.remove(step_num);
+                renumber_inputs(proc_steps, renumbering);
+                ChainChange::Insert {
+                    step_num,
+                    filter: step.filter,
+                    notes: step.notes,
+                }
+            }
+            ChainChange::Replace { step_num, filter } => {
+                let prev_filter = std::mem::replace(&mut proc_steps[step_num].filter, filter);
+                ChainChange::Replace {
+                    step_num,
+                    filter: prev_filter,
+                }
+            }
+            ChainChange::Swap {
+                step_num1,
+                step_num2,
+            } => {
+                proc_steps.swap(step_num1, step_num2);
+                renumber_inputs(proc_steps, renumbering);
+                ChainChange::Swap {
+                    step_num1,
+                    step_num2,
+                }
+            }
+        }
+    }
+}
+
+// how the numbers of the steps that stay in the chain change
+#[derive(Clone, Copy)]
+pub enum Renumbering {
+    Same,
+    Inserted(usize),
+    Removed(usize),
+    Swapped(usize, usize),
+}
+
+impl Renumbering {
+    pub fn renumber(self, num: usize) -> usize {
+        match self {
+            Renumbering::Inserted(step_num) if num >= step_num => num + 1,
+            Renumbering::Removed(step_num) if num > step_num => num - 1,
+            Renumbering::Swapped(step_num1, step_num2) if num == step_num1 => step_num2,
+            Renumbering::Swapped(step_num1, step_num2) if num == step_num2 => step_num1,
+            _ => num,
+        }
+    }
+}
+
+fn renumber_inputs(proc_steps: &mut [ProcStep], renumbering: Renumbering) {
+    for step in proc_steps.iter_mut() {
+        let inputs: Vec<StepRef> = step.filter.get_inputs();
+        if !inputs.iter().any(|input| matches!(input, StepRef::Step(_))) {
+            continue;
+        }
+
+        let inputs = inputs
+            .into_iter()
+            .map(|input| match input {
+                StepRef::Step(num) => StepRef::Step(renumbering.renumber(num)),
+                StepRef::Initial => StepRef::Initial,
+            })
+            .collect();
+        step.filter.set_inputs(inputs);
+    }
+}
+
+pub struct CachedImg {
+    pub img: Option<Img>,
+    pub img_id: usize,
+}
+
+pub struct HistoryRecord {
+    pub branch_num: usize,
+    // the change that brings the chain to the state the record was made for
+    pub change: ChainChange,
+    // results of the steps starting from the first affected one in that state
+    pub cached_imgs: Vec<CachedImg>,
+    // the cached results are valid only if the step above them is still the same
+    pub upper_img_id: usize,
+}
+
+impl HistoryRecord {
+    fn imgs_bytes(&self) -> usize {
+        self.cached_imgs
+            .iter()
+            .filter_map(|cached| cached.img.as_ref())
+            .map(|img| img.w() * img.h() * img.d() * std::mem::size_of::<f64>())
+            .sum()
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo_records: VecDeque<HistoryRecord>,
+    redo_records: Vec<HistoryRecord>,
+}
+
+impl History {
+    pub fn push(&mut self, record: HistoryRecord) {
+        self.redo_records.clear();
+
+        self.undo_records.push_back(record);
+        if self.undo_records.len() > HISTORY_MAX_LEN {
+            self.undo_records.pop_front();
+        }
+        self.drop_old_imgs(HISTORY_MAX_IMGS_BYTES);
+    }
+
+    pub fn pop_undo(&mut self) -> Option<HistoryRecord> {
+        self.undo_records.pop_back()
+    }
+
+    pub fn push_undo(&mut self, record: HistoryRecord) {
+        self.undo_records.push_back(record);
+        self.drop_old_imgs(HISTORY_MAX_IMGS_BYTES);
+    }
+
+    pub fn pop_redo(&mut self) -> Option<HistoryRecord> {
+        self.redo_records.pop()
+    }
+
+    pub fn push_redo(&mut self, record: HistoryRecord) {
+        self.redo_records.push(record);
+        self.drop_old_imgs(HISTORY_MAX_IMGS_BYTES);
+    }
+
+    // the oldest undo records lose their results first, then the farthest redo ones
+    pub fn drop_old_imgs(&mut self, max_bytes: usize) {
+        let mut total_bytes: usize = self
+            .undo_records
+            .iter()
+            .chain(self.redo_records.iter())
+            .map(HistoryRecord::imgs_bytes)
+            .sum();
+
+        for record in self
+            .undo_records
+            .iter_mut()
+            .chain(self.redo_records.iter_mut())
+        {
+            if total_bytes <= max_bytes {
+                break;
+            }
+            total_bytes -= record.imgs_bytes();
+            record.cached_imgs.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_records.clear();
+        self.redo_records.clear();
+    }
+}
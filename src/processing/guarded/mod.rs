@@ -1,21 +1,37 @@
-use core::panic;
-
-use super::ExecutorHandle;
+use super::{
+    project_file::{ProjectBranch, ProjectFile, ProjectStep},
+    ExecutorHandle,
+};
 use crate::{
-    img::{filter::FilterBase, Img, PixelsArea},
+    img::{
+        filter::{filter_option::StepRef, FilterBase},
+        Img, ImgFormat, PixelsArea,
+    },
     my_err::MyError,
-    processing::task_info_channel::{TaskState, TaskStop},
+    processing::task_info_channel::TaskStop,
 };
+use branch::Branch;
 use fltk::image::RgbImage;
-use proc_step::ProcStep;
+use history::{CachedImg, ChainChange, History, HistoryRecord, Renumbering};
+use proc_step::{next_img_id, ProcStep};
+use std::path::{Path, PathBuf};
 
+mod branch;
+mod history;
 mod proc_step;
 
+pub use branch::MAIN_BRANCH;
+
 pub struct Guarded {
     executor_handle: ExecutorHandle,
     task_setup: Option<TaskSetup>,
     initial_img: Option<Img>,
-    proc_steps: Vec<ProcStep>,
+    initial_img_id: usize,
+    initial_img_path: Option<String>,
+    project_crop_area: Option<PixelsArea>,
+    // the main chain goes first
+    branches: Vec<Branch>,
+    history: History,
 }
 
 impl Guarded {
@@ -24,7 +40,11 @@ impl Guarded {
             executor_handle,
             task_setup: None,
             initial_img: None,
-            proc_steps: Vec::new(),
+            initial_img_id: next_img_id(),
+            initial_img_path: None,
+            project_crop_area: None,
+            branches: vec![Branch::main()],
+            history: History::default(),
         }
     }
 
@@ -33,44 +53,58 @@ impl Guarded {
     }
 
     pub fn do_task_and_save_result(&mut self) {
-        print!("started ");
-
         let task_setup: TaskSetup = self.task_setup.take().expect("No task was set up!");
 
         let result: Result<(), TaskStop> = match &task_setup {
             TaskSetup::ProcessStep {
+                branch_num,
                 step_num,
                 crop_area,
             } => Self::process_step(
                 &mut self.executor_handle,
                 &self.initial_img,
-                &mut self.proc_steps,
+                &mut self.branches,
+                *branch_num,
                 *step_num,
                 *crop_area,
             ),
-            TaskSetup::Export { ref dir_path } => {
-                Self::export_results(&mut self.executor_handle, &self.proc_steps, dir_path)
-            }
+            TaskSetup::Export {
+                ref dir_path,
+                format,
+            } => Self::export_results(&mut self.executor_handle, &self.branches, dir_path, *format),
             TaskSetup::Import { file_path } => {
-                Self::import(&mut self.executor_handle, &mut self.initial_img, file_path)
-            }
-            TaskSetup::SaveProject { file_path } => {
-                Self::save_project(&mut self.executor_handle, &self.proc_steps, file_path)
+                let result =
+                    Self::import(&mut self.executor_handle, &mut self.initial_img, file_path);
+                self.initial_img_id = next_img_id();
+                if result.is_ok() {
+                    self.initial_img_path = Some(file_path.clone());
+                }
+                result
             }
+            TaskSetup::SaveProject {
+                file_path,
+                crop_area,
+                with_results,
+            } => self.save_project(file_path, *crop_area, *with_results),
             TaskSetup::LoadProject { file_path } => {
-                Self::load_project(&mut self.executor_handle, &mut self.proc_steps, file_path)
+                self.history.clear();
+                self.load_project(file_path)
             }
+            // the branches are for comparing, a folder is processed by the main chain
+            TaskSetup::BatchProcess {
+                input_dir,
+                output_dir,
+                format,
+            } => Self::batch_process(
+                &mut self.executor_handle,
+                &self.branches[MAIN_BRANCH].proc_steps,
+                input_dir,
+                output_dir,
+                *format,
+            ),
         };
 
-        println!("result {:?}", result);
-
-        match self.executor_handle.get_task_state() {
-            TaskState::Empty => panic!("No task detected"),
-            TaskState::InProgress { .. } => panic!("Task wasn't completed "),
-            TaskState::Finished { .. } => {}
-        }
-
-        println!("{:?}", self.task_setup);
+        self.executor_handle.finish_task(result);
     }
 
     pub fn start_task(&mut self, setup: TaskSetup) {
@@ -80,8 +114,10 @@ impl Guarded {
 
     pub fn set_initial_img(&mut self, img: Img) {
         self.initial_img = Some(img);
-        for step in self.proc_steps.iter_mut() {
-            step.img = None;
+        self.initial_img_id = next_img_id();
+        self.initial_img_path = None;
+        for branch in self.branches.iter_mut() {
+            branch.clear_results();
         }
     }
 
@@ -101,67 +137,319 @@ impl Guarded {
         self.initial_img.as_ref().unwrap().get_description()
     }
 
-    pub fn add_step(&mut self, filter: FilterBase) {
-        self.proc_steps.push(ProcStep { img: None, filter });
+    // returns the number of the new branch
+    pub fn add_branch(&mut self, name: String, fork_step: Option<usize>) -> usize {
+        self.branches.push(Branch::new(name, fork_step));
+
+        // undoing could remove the step the branch continues
+        self.history.clear();
+
+        self.branches.len() - 1
+    }
+
+    pub fn remove_branch(&mut self, branch_num: usize) {
+        assert_ne!(branch_num, MAIN_BRANCH);
+        self.branches.remove(branch_num);
+
+        // the records keep the numbers of the branches, which have changed
+        self.history.clear();
+    }
+
+    pub fn get_branches_count(&self) -> usize {
+        self.branches.len()
+    }
+
+    pub fn get_branch_descr(&self, branch_num: usize) -> String {
+        self.branches[branch_num].get_description(branch_num)
+    }
+
+    pub fn get_branch_fork_step(&self, branch_num: usize) -> Option<usize> {
+        self.branches[branch_num].fork_step
+    }
+
+    pub fn add_step(&mut self, branch_num: usize, filter: FilterBase) {
+        let step_num = self.branches[branch_num].proc_steps.len();
+        self.change_chain(
+            branch_num,
+            ChainChange::Insert {
+                step_num,
+                filter,
+                notes: String::new(),
+            },
+        );
     }
 
     pub fn edit_step(
         &mut self,
+        branch_num: usize,
         step_num: usize,
         mut action: impl FnMut(&mut FilterBase) -> bool,
     ) -> bool {
-        action(&mut self.proc_steps[step_num].filter)
+        let mut filter = self.branches[branch_num].proc_steps[step_num]
+            .filter
+            .get_copy();
+
+        let edited = action(&mut filter);
+        if edited {
+            self.change_chain(branch_num, ChainChange::Replace { step_num, filter });
+        }
+
+        edited
+    }
+
+    pub fn remove_step(&mut self, branch_num: usize, step_num: usize) -> Result<(), MyError> {
+        self.checked_change_chain(branch_num, ChainChange::Remove { step_num })
+    }
+
+    pub fn swap_steps(
+        &mut self,
+        branch_num: usize,
+        step_num1: usize,
+        step_num2: usize,
+    ) -> Result<(), MyError> {
+        self.checked_change_chain(
+            branch_num,
+            ChainChange::Swap {
+                step_num1,
+                step_num2,
+            },
+        )
+    }
+
+    // removes all the branches and steps
+    pub fn clear_steps(&mut self) {
+        self.branches = vec![Branch::main()];
+        self.history.clear();
+    }
+
+    // returns the branch and the number of the first step that was changed
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        let record = self.history.pop_undo()?;
+        let (reverting_record, first_step) = self.revert(record);
+        self.history.push_redo(reverting_record);
+        Some(first_step)
+    }
+
+    // returns the branch and the number of the first step that was changed
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        let record = self.history.pop_redo()?;
+        let (reverting_record, first_step) = self.revert(record);
+        self.history.push_undo(reverting_record);
+        Some(first_step)
+    }
+
+    fn checked_change_chain(
+        &mut self,
+        branch_num: usize,
+        change: ChainChange,
+    ) -> Result<(), MyError> {
+        change.check_inputs(&self.branches[branch_num].proc_steps)?;
+
+        if let (MAIN_BRANCH, ChainChange::Remove { step_num }) = (branch_num, &change) {
+            let forked = self.branches[MAIN_BRANCH + 1..]
+                .iter()
+                .find(|branch| branch.fork_step == Some(*step_num));
+            if let Some(branch) = forked {
+                return Err(MyError::new(format!(
+                    "Шаг {} нельзя удалить, его продолжает ветвь '{}'",
+                    step_num + 1,
+                    branch.name
+                )));
+            }
+        }
+
+        self.change_chain(branch_num, change);
+        Ok(())
+    }
+
+    fn change_chain(&mut self, branch_num: usize, change: ChainChange) {
+        let first_step = change.first_affected_step();
+        let upper_img_id = self.upper_img_id(branch_num, first_step);
+        let cached_imgs = self.take_imgs_from(branch_num, first_step);
+
+        self.renumber_fork_steps(branch_num, change.renumbering());
+        let reverting_change = change.apply(&mut self.branches[branch_num].proc_steps);
+
+        self.history.push(HistoryRecord {
+            branch_num,
+            change: reverting_change,
+            cached_imgs,
+            upper_img_id,
+        });
+    }
+
+    fn revert(&mut self, record: HistoryRecord) -> (HistoryRecord, (usize, usize)) {
+        let branch_num = record.branch_num;
+        let first_step = record.change.first_affected_step();
+        let upper_img_id = self.upper_img_id(branch_num, first_step);
+        let cached_imgs = self.take_imgs_from(branch_num, first_step);
+
+        self.renumber_fork_steps(branch_num, record.change.renumbering());
+        let proc_steps = &mut self.branches[branch_num].proc_steps;
+        let reverting_change = record.change.apply(proc_steps);
+
+        // the results are still valid only if the image they were made from hasn't changed
+        if upper_img_id == record.upper_img_id {
+            for (step, cached) in proc_steps[first_step..].iter_mut().zip(record.cached_imgs) {
+                step.img = cached.img;
+                step.img_id = cached.img_id;
+            }
+        }
+
+        let reverting_record = HistoryRecord {
+            branch_num,
+            change: reverting_change,
+            cached_imgs,
+            upper_img_id,
+        };
+
+        (reverting_record, (branch_num, first_step))
+    }
+
+    // the branches follow the steps of the main chain they continue
+    fn renumber_fork_steps(&mut self, branch_num: usize, renumbering: Renumbering) {
+        if branch_num != MAIN_BRANCH {
+            return;
+        }
+
+        for branch in self.branches[MAIN_BRANCH + 1..].iter_mut() {
+            branch.fork_step = branch
+                .fork_step
+                .map(|fork_step| renumbering.renumber(fork_step));
+        }
+    }
+
+    fn upper_img_id(&self, branch_num: usize, step_num: usize) -> usize {
+        let branch = &self.branches[branch_num];
+        if step_num > 0 {
+            return branch.proc_steps[step_num - 1].img_id;
+        }
+
+        match branch.fork_step {
+            // the step the branch continues may be not loaded yet
+            Some(fork_step) => self.branches[MAIN_BRANCH]
+                .proc_steps
+                .get(fork_step)
+                .map_or(0, |step| step.img_id),
+            None => self.initial_img_id,
+        }
+    }
+
+    fn take_imgs_from(&mut self, branch_num: usize, step_num: usize) -> Vec<CachedImg> {
+        let mut cached_imgs = Vec::<CachedImg>::new();
+
+        for step in self.branches[branch_num].proc_steps[step_num..].iter_mut() {
+            cached_imgs.push(CachedImg {
+                img: step.img.take(),
+                img_id: step.img_id,
+            });
+            step.set_img(None);
+        }
+
+        if branch_num == MAIN_BRANCH {
+            clear_forked_branches(&mut self.branches, step_num);
+        }
+
+        cached_imgs
     }
 
-    pub fn remove_step(&mut self, step_num: usize) {
-        self.proc_steps.remove(step_num);
+    pub fn set_step_notes(&mut self, branch_num: usize, step_num: usize, notes: String) {
+        self.branches[branch_num].proc_steps[step_num].notes = notes;
     }
 
-    pub fn swap_steps(&mut self, step_num1: usize, step_num2: usize) {
-        self.proc_steps.swap(step_num1, step_num2);
+    pub fn get_step_notes(&self, branch_num: usize, step_num: usize) -> &str {
+        &self.branches[branch_num].proc_steps[step_num].notes
     }
 
-    pub fn get_steps_count(&self) -> usize {
-        self.proc_steps.len()
+    pub fn get_project_crop_area(&self) -> Option<PixelsArea> {
+        self.project_crop_area
     }
 
-    pub fn get_step_img(&self, step_num: usize) -> &Img {
-        self.proc_steps[step_num].img.as_ref().unwrap()
+    pub fn get_steps_count(&self, branch_num: usize) -> usize {
+        self.branches[branch_num].proc_steps.len()
     }
 
-    pub fn check_if_can_start_processing(&self, step_num: usize) -> StartProcResult {
+    pub fn get_step_img(&self, branch_num: usize, step_num: usize) -> &Img {
+        self.branches[branch_num].proc_steps[step_num]
+            .img
+            .as_ref()
+            .unwrap()
+    }
+
+    pub fn check_if_can_start_processing(
+        &self,
+        branch_num: usize,
+        step_num: usize,
+    ) -> StartProcResult {
+        let branch = &self.branches[branch_num];
+
+        let input_without_img = branch.proc_steps[step_num]
+            .filter
+            .get_inputs()
+            .into_iter()
+            .find_map(|input| match input {
+                // the wrong numbers are reported by the processing itself
+                StepRef::Step(input_num)
+                    if input_num < step_num && branch.proc_steps[input_num].img.is_none() =>
+                {
+                    Some(input_num)
+                }
+                _ => None,
+            });
+
+        let fork_step_without_img = match branch.fork_step {
+            Some(fork_step) if step_num == 0 => {
+                let fork_img = self.branches[MAIN_BRANCH]
+                    .proc_steps
+                    .get(fork_step)
+                    .and_then(|step| step.img.as_ref());
+                fork_img.map_or(Some(fork_step), |_| None)
+            }
+            _ => None,
+        };
+
         if self.initial_img.is_none() {
             StartProcResult::NoInitialImg
-        } else if step_num > 0 && self.proc_steps[step_num - 1].img.is_none() {
+        } else if step_num > 0 && branch.proc_steps[step_num - 1].img.is_none() {
             StartProcResult::NoPrevStepImg
+        } else if let Some(fork_step) = fork_step_without_img {
+            StartProcResult::NoForkStepImg { fork_step }
+        } else if let Some(input_num) = input_without_img {
+            StartProcResult::NoInputStepImg { input_num }
         } else {
             StartProcResult::CanStart
         }
     }
 
-    pub fn get_step_descr(&self, step_num: usize) -> String {
-        self.proc_steps[step_num].get_description()
+    pub fn get_step_descr(&self, branch_num: usize, step_num: usize) -> String {
+        self.branches[branch_num].proc_steps[step_num].get_description()
     }
 
-    pub fn get_step_img_drawable(&self, step_num: usize) -> Option<RgbImage> {
-        match self.proc_steps[step_num].img {
+    pub fn get_step_img_drawable(&self, branch_num: usize, step_num: usize) -> Option<RgbImage> {
+        match self.branches[branch_num].proc_steps[step_num].img {
             Some(ref img) => Some(img.get_drawable_copy()),
             None => None,
         }
     }
 
-    pub fn get_filter_params_as_str(&self, step_num: usize) -> Option<String> {
-        self.proc_steps[step_num].filter.params_to_string()
+    pub fn get_filter_params_as_str(&self, branch_num: usize, step_num: usize) -> Option<String> {
+        self.branches[branch_num].proc_steps[step_num]
+            .filter
+            .params_to_string()
     }
 
-    pub fn get_filter_save_name(&self, step_num: usize) -> String {
-        self.proc_steps[step_num].filter.get_save_name()
+    pub fn get_filter_save_name(&self, branch_num: usize, step_num: usize) -> String {
+        self.branches[branch_num].proc_steps[step_num]
+            .filter
+            .get_save_name()
     }
 
     pub fn check_if_can_export(&self) -> StartResultsSavingResult {
-        if self.proc_steps.len() == 0 {
+        let mut all_steps = self.branches.iter().flat_map(|b| b.proc_steps.iter());
+
+        if self.branches.iter().all(|b| b.proc_steps.is_empty()) {
             StartResultsSavingResult::NoSteps
-        } else if self.proc_steps.iter().any(|s| s.img.is_none()) {
+        } else if all_steps.any(|s| s.img.is_none()) {
             StartResultsSavingResult::NotAllStepsHaveResult
         } else {
             StartResultsSavingResult::CanStart
@@ -171,61 +459,120 @@ impl Guarded {
     fn process_step(
         executor_handle: &mut ExecutorHandle,
         initial_img: &Option<Img>,
-        proc_steps: &mut Vec<ProcStep>,
+        branches: &mut Vec<Branch>,
+        branch_num: usize,
         step_num: usize,
         crop_area: Option<PixelsArea>,
     ) -> Result<(), TaskStop> {
-        for step in &mut proc_steps[step_num + 1..] {
+        for step in &mut branches[branch_num].proc_steps[step_num + 1..] {
             if let Some(_) = step.img {
-                step.img = None;
+                step.set_img(None);
             }
         }
 
-        let mut img_to_process: &Img = if step_num == 0 {
-            initial_img.as_ref().unwrap()
-        } else {
-            proc_steps[step_num - 1].img.as_ref().unwrap()
-        };
-
-        let cropped_copy: Img;
-        if let Some(crop_area) = crop_area {
-            cropped_copy = img_to_process.get_cropped_copy(crop_area);
-            img_to_process = &cropped_copy;
+        if branch_num == MAIN_BRANCH {
+            clear_forked_branches(branches, step_num);
         }
 
+        let branch = &branches[branch_num];
+        let proc_steps = &branch.proc_steps;
+
+        let initial_img: &Img = initial_img.as_ref().unwrap();
+        let img_to_process: &Img = match branch.fork_step {
+            _ if step_num > 0 => proc_steps[step_num - 1].img.as_ref().unwrap(),
+            Some(fork_step) => branches[MAIN_BRANCH].proc_steps[fork_step]
+                .img
+                .as_ref()
+                .unwrap(),
+            None => initial_img,
+        };
+
         let step = &proc_steps[step_num];
+        let inputs: Vec<&Img> = collect_inputs(&step.filter, step_num, initial_img, |num| {
+            proc_steps[num].img.as_ref()
+        })?;
+
+        // steps may change the image size, so the selection can be left from a bigger image;
+        // the other inputs of the same size are cropped the same way
+        let crop_area = crop_area.filter(|area| area.is_inside_of(&img_to_process.get_area()));
+        let crop = |img: &Img| -> Option<Img> {
+            match crop_area {
+                Some(area) if img.get_area() == img_to_process.get_area() => {
+                    Some(img.get_cropped_copy(area))
+                }
+                _ => None,
+            }
+        };
 
-        executor_handle.reset(step.filter.get_steps_num(&img_to_process));
+        let cropped_img: Option<Img> = crop(img_to_process);
+        let cropped_inputs: Vec<Option<Img>> = inputs.iter().map(|img| crop(img)).collect();
 
-        let img_result = match step.filter.process(&img_to_process, executor_handle) {
-            Ok(img) => Some(img),
-            Err(_halted) => None,
-        };
+        let img_to_process: &Img = cropped_img.as_ref().unwrap_or(img_to_process);
+        let inputs: Vec<&Img> = inputs
+            .iter()
+            .zip(cropped_inputs.iter())
+            .map(|(img, cropped)| cropped.as_ref().unwrap_or(img))
+            .collect();
 
-        proc_steps[step_num].img = img_result;
+        executor_handle.reset(step.filter.get_steps_num(img_to_process));
 
-        Ok(())
+        // the errors are shown, the halted processing just leaves no result
+        let (img_result, result) =
+            match step
+                .filter
+                .process_with_inputs(img_to_process, &inputs, executor_handle)
+            {
+                Ok(img) => (Some(img), Ok(())),
+                Err(TaskStop::Halted) => (None, Ok(())),
+                Err(err) => (None, Err(err)),
+            };
+
+        branches[branch_num].proc_steps[step_num].set_img(img_result);
+
+        result
     }
 
     fn export_results(
         executor_handle: &mut ExecutorHandle,
-        proc_steps: &Vec<ProcStep>,
+        branches: &[Branch],
         dir_path: &str,
+        format: ImgFormat,
     ) -> Result<(), TaskStop> {
-        executor_handle.reset(1 + proc_steps.len());
+        let steps_count: usize = branches.iter().map(|b| b.proc_steps.len()).sum();
+        executor_handle.reset(1 + steps_count);
 
         std::fs::create_dir(&dir_path)?;
 
         executor_handle.complete_action()?;
 
-        for step_num in 0..proc_steps.len() {
-            let mut file_path = String::from(dir_path);
-            file_path.push_str(&format!("/{}.jpg", step_num + 1));
-
-            let step = &proc_steps[step_num];
-            step.img.as_ref().unwrap().try_save(&file_path)?;
+        for (branch_num, branch) in branches.iter().enumerate() {
+            if branch.proc_steps.is_empty() {
+                continue;
+            }
 
-            executor_handle.complete_action()?;
+            // the results of the main chain are in the folder itself, of the branches - in
+            // the folders inside it
+            let branch_dir_path: String = if branch_num == MAIN_BRANCH {
+                dir_path.to_string()
+            } else {
+                let path = format!(
+                    "{}/{} {}",
+                    dir_path,
+                    branch_num,
+                    safe_file_name(&branch.name)
+                );
+                std::fs::create_dir(&path)?;
+                path
+            };
+
+            for (step_num, step) in branch.proc_steps.iter().enumerate() {
+                let mut file_path = branch_dir_path.clone();
+                file_path.push_str(&format!("/{}.{}", step_num + 1, format.extension()));
+
+                step.img.as_ref().unwrap().try_save_as(&file_path, format)?;
+
+                executor_handle.complete_action()?;
+            }
         }
 
         Ok(())
@@ -238,27 +585,10 @@ impl Guarded {
     ) -> Result<(), TaskStop> {
         executor_handle.reset(2);
 
-        println!("loadeding...");
-        let sh_im = fltk::image::SharedImage::load(file_path)?;
-        println!("loaded");
+        let img = load_img(file_path)?;
 
         executor_handle.complete_action()?;
 
-        use fltk::prelude::ImageExt;
-        if sh_im.w() < 0 {
-            return Err(MyError::new("Ширина загруженного изображения < 0".to_string()).into());
-        }
-        if sh_im.h() < 0 {
-            return Err(MyError::new("Высота загруженного изображения < 0".to_string()).into());
-        }
-
-        let img = Img::from_pixels(
-            sh_im.w() as usize,
-            sh_im.h() as usize,
-            sh_im.depth(),
-            sh_im.to_rgb_data(),
-        );
-
         initial_img.replace(img);
 
         executor_handle.complete_action()?;
@@ -266,113 +596,369 @@ impl Guarded {
         Ok(())
     }
 
-    fn save_project(
+    fn batch_process(
         executor_handle: &mut ExecutorHandle,
-        proc_steps: &Vec<ProcStep>,
-        file_path: &str,
+        proc_steps: &[ProcStep],
+        input_dir: &str,
+        output_dir: &str,
+        format: ImgFormat,
     ) -> Result<(), TaskStop> {
-        executor_handle.reset(1 + proc_steps.len() + 1);
+        assert!(!proc_steps.is_empty());
+
+        let imgs_paths: Vec<PathBuf> = list_imgs_in_dir(input_dir)?;
+        if imgs_paths.is_empty() {
+            return Err(MyError::new(format!(
+                "В папке '{}' нет изображений для обработки",
+                input_dir
+            ))
+            .into());
+        }
+
+        let results_paths: Vec<PathBuf> = results_paths(output_dir, &imgs_paths, format)?;
+
+        executor_handle.reset_staged(imgs_paths.len() * proc_steps.len());
+
+        std::fs::create_dir_all(output_dir)?;
+
+        // the unreadable images are skipped, so they don't stop processing of the others
+        let mut failed_imgs = Vec::<String>::new();
+
+        for (img_num, (img_path, result_path)) in
+            imgs_paths.iter().zip(results_paths.iter()).enumerate()
+        {
+            let initial_img: Img = match load_img(&img_path.to_string_lossy()) {
+                Ok(img) => img,
+                Err(err) => {
+                    failed_imgs.push(format!("'{}': {}", img_path.display(), err));
+                    continue;
+                }
+            };
+            let mut results = Vec::<Img>::with_capacity(proc_steps.len());
+
+            for (step_num, step) in proc_steps.iter().enumerate() {
+                let img: &Img = results.last().unwrap_or(&initial_img);
+                let inputs =
+                    collect_inputs(&step.filter, step_num, &initial_img, |num| results.get(num))?;
+
+                executor_handle.start_stage(
+                    img_num * proc_steps.len() + step_num,
+                    step.filter.get_steps_num(img),
+                )?;
+
+                let result = step
+                    .filter
+                    .process_with_inputs(img, &inputs, executor_handle)?;
+                results.push(result);
+            }
 
-        let mut file = std::fs::File::create(file_path)?;
+            results
+                .last()
+                .unwrap()
+                .try_save_as(&result_path.to_string_lossy(), format)?;
+        }
 
-        executor_handle.complete_action()?;
+        if !failed_imgs.is_empty() {
+            return Err(MyError::new(format!(
+                "Не удалось загрузить изображения:\n{}",
+                failed_imgs.join("\n")
+            ))
+            .into());
+        }
 
-        let mut file_content = String::new();
+        Ok(())
+    }
 
-        for step_num in 0..proc_steps.len() {
-            let filter_save_name: String = proc_steps[step_num].filter.get_save_name();
+    fn save_project(
+        &mut self,
+        file_path: &str,
+        crop_area: Option<PixelsArea>,
+        with_results: bool,
+    ) -> Result<(), TaskStop> {
+        let steps_count: usize = self.branches.iter().map(|b| b.proc_steps.len()).sum();
+        self.executor_handle.reset(steps_count + 1);
+
+        // the images are cloned only for a bundle
+        let mut project = ProjectFile {
+            initial_img_path: self.initial_img_path.clone(),
+            crop_area,
+            steps: Vec::new(),
+            branches: Vec::with_capacity(self.branches.len() - 1),
+            initial_img: if with_results {
+                self.initial_img.clone()
+            } else {
+                None
+            },
+        };
 
-            file_content.push_str(&filter_save_name);
-            file_content.push_str("\n");
+        for (branch_num, branch) in self.branches.iter().enumerate() {
+            let mut steps = Vec::<ProjectStep>::with_capacity(branch.proc_steps.len());
 
-            if let Some(params_str) = proc_steps[step_num].filter.params_to_string() {
-                file_content.push_str(&params_str);
+            for step in branch.proc_steps.iter() {
+                steps.push(ProjectStep {
+                    filter: step.filter.get_copy(),
+                    notes: step.notes.clone(),
+                    result: if with_results { step.img.clone() } else { None },
+                });
+
+                self.executor_handle.complete_action()?;
             }
-            file_content.push_str("\n");
 
-            if step_num < proc_steps.len() - 1 {
-                file_content.push_str(FILTER_SAVE_SEPARATOR);
-                file_content.push_str("\n");
+            if branch_num == MAIN_BRANCH {
+                project.steps = steps;
+            } else {
+                project.branches.push(ProjectBranch {
+                    name: branch.name.clone(),
+                    from_step: branch.fork_step,
+                    steps,
+                });
             }
+        }
+
+        project.try_save(file_path)?;
 
-            executor_handle.complete_action()?;
+        self.executor_handle.complete_action()?;
+
+        Ok(())
+    }
+
+    fn load_project(&mut self, file_path: &str) -> Result<(), TaskStop> {
+        self.executor_handle.reset(1 + 1 + 1);
+
+        self.branches = vec![Branch::main()];
+        self.project_crop_area = None;
+
+        self.executor_handle.complete_action()?;
+
+        let project = ProjectFile::try_load(file_path)?;
+
+        self.executor_handle.complete_action()?;
+
+        self.project_crop_area = project.crop_area;
+
+        let mut cached_results = Vec::<Vec<Option<Img>>>::with_capacity(1 + project.branches.len());
+
+        let mut main_results = Vec::<Option<Img>>::with_capacity(project.steps.len());
+        for step in project.steps {
+            self.branches[MAIN_BRANCH]
+                .proc_steps
+                .push(ProcStep::new(step.filter, step.notes));
+            main_results.push(step.result);
+        }
+        cached_results.push(main_results);
+
+        for project_branch in project.branches {
+            let mut branch = Branch::new(project_branch.name, project_branch.from_step);
+            let mut branch_results = Vec::<Option<Img>>::with_capacity(project_branch.steps.len());
+            for step in project_branch.steps {
+                branch
+                    .proc_steps
+                    .push(ProcStep::new(step.filter, step.notes));
+                branch_results.push(step.result);
+            }
+            self.branches.push(branch);
+            cached_results.push(branch_results);
         }
 
-        use std::io::Write;
-        file.write_all(&file_content.as_bytes())?;
-        file.sync_all()?;
+        match project.initial_img {
+            // a bundle keeps its own copy of the initial image, so the results stay valid
+            // even if the original file was changed since then
+            Some(img) => {
+                self.set_initial_img(img);
+                for (branch, results) in self.branches.iter_mut().zip(cached_results) {
+                    for (step, result) in branch.proc_steps.iter_mut().zip(results) {
+                        step.set_img(result);
+                    }
+                }
+            }
+            None => {
+                if let Some(ref img_path) = project.initial_img_path {
+                    let img = load_img(img_path).map_err(|err| {
+                        MyError::new(format!(
+                            "Не удалось загрузить исходное изображение проекта: {}",
+                            err.get_message()
+                        ))
+                    })?;
+                    self.set_initial_img(img);
+                }
+            }
+        }
+        self.initial_img_path = project.initial_img_path;
 
-        executor_handle.complete_action()?;
+        self.executor_handle.complete_action()?;
 
         Ok(())
     }
+}
 
-    fn load_project(
-        executor_handle: &mut ExecutorHandle,
-        proc_steps: &mut Vec<ProcStep>,
-        file_path: &str,
-    ) -> Result<(), TaskStop> {
-        executor_handle.reset(1 + 1 + 1);
+// the results of the branches that continue the changed steps of the main chain are outdated
+fn clear_forked_branches(branches: &mut [Branch], from_step: usize) {
+    for branch in branches[MAIN_BRANCH + 1..].iter_mut() {
+        if matches!(branch.fork_step, Some(fork_step) if fork_step >= from_step) {
+            branch.clear_results();
+        }
+    }
+}
 
-        proc_steps.clear();
+fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || " -_.".contains(ch) {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
 
-        executor_handle.complete_action()?;
+// the formats the decoder doesn't know (GIF, ...) are still loaded by FLTK in the UI
+pub fn load_img(file_path: &str) -> Result<Img, MyError> {
+    Img::try_load(file_path).or_else(|err| load_shared_img(file_path).ok_or(err))
+}
 
-        let mut file = std::fs::File::open(file_path)?;
+fn load_shared_img(file_path: &str) -> Option<Img> {
+    use fltk::prelude::ImageExt;
 
-        let mut file_content = String::new();
-        use std::io::Read;
-        file.read_to_string(&mut file_content)?;
+    let sh_im = fltk::image::SharedImage::load(file_path).ok()?;
+    if sh_im.w() < 0 || sh_im.h() < 0 {
+        return None;
+    }
 
-        executor_handle.complete_action()?;
+    Some(Img::from_pixels(
+        sh_im.w() as usize,
+        sh_im.h() as usize,
+        sh_im.depth(),
+        sh_im.to_rgb_data(),
+    ))
+}
 
-        let mut filters_iter =
-            crate::utils::TextBlocksIter::new(&file_content, FILTER_SAVE_SEPARATOR);
+// the images the filter of the step takes besides the result of the previous step,
+// a step can take only the initial image and the results of the steps before it
+pub fn collect_inputs<'img>(
+    filter: &FilterBase,
+    step_num: usize,
+    initial_img: &'img Img,
+    step_img: impl Fn(usize) -> Option<&'img Img>,
+) -> Result<Vec<&'img Img>, MyError> {
+    filter
+        .get_inputs()
+        .into_iter()
+        .map(|input| match input {
+            StepRef::Initial => Ok(initial_img),
+            StepRef::Step(input_num) if input_num >= step_num => Err(MyError::new(format!(
+                "Шаг {} может использовать только исходное изображение и результаты шагов до него, а не шага {}",
+                step_num + 1,
+                input_num + 1
+            ))),
+            StepRef::Step(input_num) => step_img(input_num).ok_or_else(|| {
+                MyError::new(format!(
+                    "Для шага {} нужен результат шага {}",
+                    step_num + 1,
+                    input_num + 1
+                ))
+            }),
+        })
+        .collect()
+}
+
+pub fn result_file_path(output_dir: &str, img_path: &str, format: ImgFormat) -> PathBuf {
+    let file_name = match Path::new(img_path).file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => "result".to_string(),
+    };
+    Path::new(output_dir).join(format!("{}.{}", file_name, format.extension()))
+}
+
+// the images differing only by the extension would overwrite each other's results
+fn results_paths(
+    output_dir: &str,
+    imgs_paths: &[PathBuf],
+    format: ImgFormat,
+) -> Result<Vec<PathBuf>, MyError> {
+    let mut results_paths = Vec::<PathBuf>::with_capacity(imgs_paths.len());
+
+    for img_path in imgs_paths.iter() {
+        let result_path = result_file_path(output_dir, &img_path.to_string_lossy(), format);
+
+        if let Some(prev_num) = results_paths.iter().position(|path| *path == result_path) {
+            return Err(MyError::new(format!(
+                "Результаты изображений '{}' и '{}' сохранились бы в один файл '{}'",
+                imgs_paths[prev_num].display(),
+                img_path.display(),
+                result_path.display()
+            )));
+        }
+
+        results_paths.push(result_path);
+    }
 
-        proc_steps.reserve(filters_iter.len());
+    Ok(results_paths)
+}
 
-        for filter_str in filters_iter.iter() {
-            let mut lines_iter = crate::utils::LinesIter::new(filter_str);
-            let filter_name = lines_iter.next_or_empty().to_string();
-            let filter_content = lines_iter.all_left(true);
+fn list_imgs_in_dir(dir_path: &str) -> Result<Vec<PathBuf>, MyError> {
+    let mut imgs_paths = Vec::<PathBuf>::new();
 
-            use crate::img::filter::try_parce_filter;
-            let filter = try_parce_filter(&filter_name, &filter_content)?;
-            proc_steps.push(ProcStep { img: None, filter });
+    for entry in std::fs::read_dir(dir_path)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
         }
 
-        executor_handle.complete_action()?;
+        let is_img = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => IMG_EXTENSIONS
+                .iter()
+                .any(|img_ext| img_ext.eq_ignore_ascii_case(ext)),
+            None => false,
+        };
 
-        Ok(())
+        if is_img {
+            imgs_paths.push(path);
+        }
     }
+
+    imgs_paths.sort();
+
+    Ok(imgs_paths)
 }
 
-const FILTER_SAVE_SEPARATOR: &'static str = "||";
-pub const PROJECT_EXT: &'static str = "ps";
+const IMG_EXTENSIONS: [&str; 9] = [
+    "png", "jpg", "jpeg", "bmp", "gif", "pbm", "pgm", "ppm", "pnm",
+];
 
 #[derive(Debug)]
 pub enum TaskSetup {
     ProcessStep {
+        branch_num: usize,
         step_num: usize,
         crop_area: Option<PixelsArea>,
     },
     Export {
         dir_path: String,
+        format: ImgFormat,
     },
     Import {
         file_path: String,
     },
     SaveProject {
         file_path: String,
+        crop_area: Option<PixelsArea>,
+        with_results: bool,
     },
     LoadProject {
         file_path: String,
     },
+    BatchProcess {
+        input_dir: String,
+        output_dir: String,
+        format: ImgFormat,
+    },
 }
 
 pub enum StartProcResult {
     NoInitialImg,
     NoPrevStepImg,
+    NoForkStepImg { fork_step: usize },
+    NoInputStepImg { input_num: usize },
     CanStart,
 }
 pub enum StartResultsSavingResult {
@@ -380,3 +966,409 @@ pub enum StartResultsSavingResult {
     NotAllStepsHaveResult,
     CanStart,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Guarded, StartProcResult, MAIN_BRANCH};
+    use crate::{
+        img::{
+            filter::{
+                arithmetic::Combine,
+                color_channel::{PointTransform, Rgb2Gray},
+                filter_trait::StringFromTo,
+                linear::LinearMean,
+                FilterBase,
+            },
+            Img,
+        },
+        processing::create_task_info_channel,
+    };
+    use fltk::enums::ColorDepth;
+
+    fn process(
+        guarded: &mut Guarded,
+        branch_num: usize,
+        step_num: usize,
+    ) -> Result<(), crate::processing::TaskStop> {
+        let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+        Guarded::process_step(
+            &mut executor_handle,
+            &guarded.initial_img,
+            &mut guarded.branches,
+            branch_num,
+            step_num,
+            None,
+        )
+    }
+
+    fn guarded_with_results() -> Guarded {
+        let (executor_handle, _) = create_task_info_channel();
+        let mut guarded = Guarded::new(executor_handle);
+
+        guarded.set_initial_img(Img::empty_with_size(2, 2, ColorDepth::Rgb8));
+        guarded.add_step(MAIN_BRANCH, Box::new(Rgb2Gray::default()) as FilterBase);
+        guarded.add_step(MAIN_BRANCH, Box::new(LinearMean::default()) as FilterBase);
+
+        for step in guarded.branches[MAIN_BRANCH].proc_steps.iter_mut() {
+            step.set_img(Some(Img::empty_with_size(2, 2, ColorDepth::L8)));
+        }
+
+        guarded
+    }
+
+    #[test]
+    fn undo_redo_restores_chain_and_results() {
+        let mut guarded = guarded_with_results();
+
+        guarded.swap_steps(MAIN_BRANCH, 0, 1).unwrap();
+        assert_eq!(guarded.get_filter_save_name(MAIN_BRANCH, 0), "LinearMean");
+        assert!(guarded.branches[MAIN_BRANCH]
+            .proc_steps
+            .iter()
+            .all(|s| s.img.is_none()));
+
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 0)));
+        assert_eq!(guarded.get_filter_save_name(MAIN_BRANCH, 0), "Rgb2Gray");
+        assert!(guarded.branches[MAIN_BRANCH]
+            .proc_steps
+            .iter()
+            .all(|s| s.img.is_some()));
+
+        assert_eq!(guarded.redo(), Some((MAIN_BRANCH, 0)));
+        assert_eq!(guarded.get_filter_save_name(MAIN_BRANCH, 0), "LinearMean");
+        assert_eq!(guarded.redo(), None);
+
+        guarded.remove_step(MAIN_BRANCH, 1).unwrap();
+        assert_eq!(guarded.get_steps_count(MAIN_BRANCH), 1);
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 1)));
+        assert_eq!(guarded.get_steps_count(MAIN_BRANCH), 2);
+        assert_eq!(guarded.get_filter_save_name(MAIN_BRANCH, 1), "Rgb2Gray");
+    }
+
+    #[test]
+    fn old_records_lose_results_over_budget() {
+        let mut guarded = guarded_with_results();
+        guarded.swap_steps(MAIN_BRANCH, 0, 1).unwrap();
+
+        for step in guarded.branches[MAIN_BRANCH].proc_steps.iter_mut() {
+            step.set_img(Some(Img::empty_with_size(2, 2, ColorDepth::L8)));
+        }
+        guarded.swap_steps(MAIN_BRANCH, 0, 1).unwrap();
+
+        // each record keeps two 2x2 gray images
+        guarded
+            .history
+            .drop_old_imgs(2 * 2 * 2 * std::mem::size_of::<f64>());
+
+        let has_results = |guarded: &Guarded| {
+            guarded.branches[MAIN_BRANCH]
+                .proc_steps
+                .iter()
+                .all(|s| s.img.is_some())
+        };
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 0)));
+        assert!(has_results(&guarded));
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 0)));
+        assert!(guarded.branches[MAIN_BRANCH]
+            .proc_steps
+            .iter()
+            .all(|s| s.img.is_none()));
+    }
+
+    #[test]
+    fn outdated_results_are_not_restored() {
+        let mut guarded = guarded_with_results();
+
+        guarded.remove_step(MAIN_BRANCH, 1).unwrap();
+        guarded.branches[MAIN_BRANCH].proc_steps[0].set_img(Some(Img::empty_with_size(
+            2,
+            2,
+            ColorDepth::L8,
+        )));
+
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 1)));
+        assert!(guarded.branches[MAIN_BRANCH].proc_steps[0].img.is_some());
+        assert!(guarded.branches[MAIN_BRANCH].proc_steps[1].img.is_none());
+    }
+
+    #[test]
+    fn step_takes_result_of_earlier_step() {
+        let (executor_handle, _) = create_task_info_channel();
+        let mut guarded = Guarded::new(executor_handle);
+
+        let mut initial_img = Img::empty_with_size(3, 2, ColorDepth::L8);
+        for (ind, val) in initial_img
+            .layer_mut(0)
+            .matrix_mut()
+            .vals_mut()
+            .iter_mut()
+            .enumerate()
+        {
+            *val = ind as f64 * 10_f64;
+        }
+        guarded.set_initial_img(initial_img);
+
+        let mut combine = Combine::default();
+        combine
+            .try_set_from_string("Operation: difference\nWith: step 1\nMapping: clamped")
+            .unwrap();
+        guarded.add_step(
+            MAIN_BRANCH,
+            Box::new(PointTransform::invert()) as FilterBase,
+        );
+        guarded.add_step(
+            MAIN_BRANCH,
+            Box::new(PointTransform::invert()) as FilterBase,
+        );
+        guarded.add_step(MAIN_BRANCH, Box::new(combine) as FilterBase);
+
+        let process =
+            |guarded: &mut Guarded, step_num: usize| process(guarded, MAIN_BRANCH, step_num);
+
+        for step_num in 0..3 {
+            assert!(matches!(
+                guarded.check_if_can_start_processing(MAIN_BRANCH, step_num),
+                StartProcResult::CanStart
+            ));
+            process(&mut guarded, step_num).unwrap();
+        }
+
+        // |x - (255 - x)|
+        let res = guarded.get_step_img(MAIN_BRANCH, 2).layer(0).matrix();
+        for (ind, val) in res.vals().iter().enumerate() {
+            assert!((val - (ind as f64 * 20_f64 - 255_f64).abs()).abs() < 1e-9);
+        }
+
+        guarded.branches[MAIN_BRANCH].proc_steps[0].set_img(None);
+        assert!(matches!(
+            guarded.check_if_can_start_processing(MAIN_BRANCH, 2),
+            StartProcResult::NoInputStepImg { input_num: 0 }
+        ));
+
+        // only the steps before can be used
+        guarded.edit_step(MAIN_BRANCH, 2, |filter| {
+            filter
+                .try_set_from_string("Operation: add\nWith: step 3\nMapping: clamped")
+                .is_ok()
+        });
+        process(&mut guarded, 0).unwrap();
+        process(&mut guarded, 1).unwrap();
+        assert!(process(&mut guarded, 2).is_err());
+    }
+
+    #[test]
+    fn moving_steps_keeps_inputs_of_combine() {
+        let (executor_handle, _) = create_task_info_channel();
+        let mut guarded = Guarded::new(executor_handle);
+
+        let mut combine = Combine::default();
+        combine
+            .try_set_from_string("Operation: add\nWith: step 2\nMapping: clamped")
+            .unwrap();
+        for _ in 0..3 {
+            guarded.add_step(
+                MAIN_BRANCH,
+                Box::new(PointTransform::invert()) as FilterBase,
+            );
+        }
+        guarded.add_step(MAIN_BRANCH, Box::new(combine) as FilterBase);
+
+        let with_line = |guarded: &Guarded, step_num: usize| -> String {
+            let params = guarded
+                .get_filter_params_as_str(MAIN_BRANCH, step_num)
+                .unwrap();
+            params.lines().nth(1).unwrap().to_string()
+        };
+
+        guarded.remove_step(MAIN_BRANCH, 0).unwrap();
+        assert_eq!(with_line(&guarded, 2), "With: step 1");
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 0)));
+        assert_eq!(with_line(&guarded, 3), "With: step 2");
+
+        // the taken result can't be removed or moved below the step taking it
+        assert!(guarded.remove_step(MAIN_BRANCH, 1).is_err());
+        assert!(guarded.swap_steps(MAIN_BRANCH, 1, 2).is_ok());
+        assert_eq!(with_line(&guarded, 3), "With: step 3");
+        assert!(guarded.swap_steps(MAIN_BRANCH, 2, 3).is_err());
+        assert_eq!(guarded.get_steps_count(MAIN_BRANCH), 4);
+
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 1)));
+        assert_eq!(with_line(&guarded, 3), "With: step 2");
+        assert_eq!(guarded.redo(), Some((MAIN_BRANCH, 1)));
+        assert_eq!(with_line(&guarded, 3), "With: step 3");
+    }
+
+    #[test]
+    fn branch_continues_step_of_main_chain() {
+        let (executor_handle, _) = create_task_info_channel();
+        let mut guarded = Guarded::new(executor_handle);
+
+        let mut initial_img = Img::empty_with_size(2, 2, ColorDepth::L8);
+        initial_img.layer_mut(0).matrix_mut()[0] = 100_f64;
+        guarded.set_initial_img(initial_img);
+
+        guarded.add_step(
+            MAIN_BRANCH,
+            Box::new(PointTransform::invert()) as FilterBase,
+        );
+        guarded.add_step(
+            MAIN_BRANCH,
+            Box::new(PointTransform::invert()) as FilterBase,
+        );
+
+        let from_initial = guarded.add_branch("a".to_string(), None);
+        let from_step = guarded.add_branch("b".to_string(), Some(0));
+        guarded.add_step(
+            from_initial,
+            Box::new(PointTransform::invert()) as FilterBase,
+        );
+        guarded.add_step(from_step, Box::new(PointTransform::invert()) as FilterBase);
+        assert_eq!(guarded.get_branch_descr(from_step), "b (от шага 1)");
+
+        assert!(matches!(
+            guarded.check_if_can_start_processing(from_step, 0),
+            StartProcResult::NoForkStepImg { fork_step: 0 }
+        ));
+
+        process(&mut guarded, MAIN_BRANCH, 0).unwrap();
+        process(&mut guarded, from_initial, 0).unwrap();
+        process(&mut guarded, from_step, 0).unwrap();
+
+        let val = |guarded: &Guarded, branch_num: usize| {
+            guarded.get_step_img(branch_num, 0).layer(0).matrix()[0]
+        };
+        let has_result = |guarded: &Guarded, branch_num: usize| {
+            guarded.branches[branch_num].proc_steps[0].img.is_some()
+        };
+        assert!((val(&guarded, from_initial) - 155_f64).abs() < 1e-9);
+        assert!((val(&guarded, from_step) - 100_f64).abs() < 1e-9);
+
+        // only the branch that continues the reprocessed step is outdated
+        process(&mut guarded, MAIN_BRANCH, 0).unwrap();
+        assert!(has_result(&guarded, from_initial));
+        assert!(!has_result(&guarded, from_step));
+
+        process(&mut guarded, from_step, 0).unwrap();
+        guarded.remove_step(MAIN_BRANCH, 1).unwrap();
+        assert!(has_result(&guarded, from_step));
+        assert!(guarded.remove_step(MAIN_BRANCH, 0).is_err());
+        assert!(has_result(&guarded, from_step));
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 1)));
+
+        guarded.remove_branch(from_initial);
+        assert_eq!(guarded.get_branches_count(), 2);
+        assert_eq!(guarded.get_branch_fork_step(1), Some(0));
+        assert_eq!(guarded.undo(), None);
+    }
+
+    #[test]
+    fn fork_step_follows_main_chain_changes() {
+        let (executor_handle, _) = create_task_info_channel();
+        let mut guarded = Guarded::new(executor_handle);
+
+        for _ in 0..3 {
+            guarded.add_step(
+                MAIN_BRANCH,
+                Box::new(PointTransform::invert()) as FilterBase,
+            );
+        }
+        let branch_num = guarded.add_branch("b".to_string(), Some(1));
+
+        // the branch was added after these changes, undoing them could remove its step
+        assert_eq!(guarded.undo(), None);
+
+        guarded.add_step(
+            MAIN_BRANCH,
+            Box::new(PointTransform::invert()) as FilterBase,
+        );
+        assert_eq!(guarded.get_branch_fork_step(branch_num), Some(1));
+        guarded.swap_steps(MAIN_BRANCH, 1, 2).unwrap();
+        assert_eq!(guarded.get_branch_fork_step(branch_num), Some(2));
+        guarded.remove_step(MAIN_BRANCH, 0).unwrap();
+        assert_eq!(guarded.get_branch_fork_step(branch_num), Some(1));
+        assert_eq!(guarded.get_branch_descr(branch_num), "b (от шага 2)");
+
+        let err = guarded.remove_step(MAIN_BRANCH, 1).err().unwrap();
+        assert_eq!(
+            err.get_message(),
+            "Шаг 2 нельзя удалить, его продолжает ветвь 'b'"
+        );
+
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 0)));
+        assert_eq!(guarded.get_branch_fork_step(branch_num), Some(2));
+        assert_eq!(guarded.undo(), Some((MAIN_BRANCH, 1)));
+        assert_eq!(guarded.get_branch_fork_step(branch_num), Some(1));
+        assert_eq!(guarded.redo(), Some((MAIN_BRANCH, 1)));
+        assert_eq!(guarded.get_branch_fork_step(branch_num), Some(2));
+
+        // the changes of the branch itself don't move it
+        guarded.add_step(branch_num, Box::new(PointTransform::invert()) as FilterBase);
+        guarded.add_step(branch_num, Box::new(PointTransform::invert()) as FilterBase);
+        guarded.remove_step(branch_num, 0).unwrap();
+        assert_eq!(guarded.get_branch_fork_step(branch_num), Some(2));
+    }
+
+    #[test]
+    fn results_paths_keep_format_and_dont_collide() {
+        use super::{results_paths, ImgFormat};
+        use std::path::PathBuf;
+
+        let imgs_paths = vec![PathBuf::from("in/a.bmp"), PathBuf::from("in/b.png")];
+        let paths = results_paths("out", &imgs_paths, ImgFormat::Png).unwrap();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("out/a.png"), PathBuf::from("out/b.png")]
+        );
+
+        let imgs_paths = vec![PathBuf::from("in/a.bmp"), PathBuf::from("in/a.png")];
+        let err = results_paths("out", &imgs_paths, ImgFormat::Jpeg)
+            .err()
+            .unwrap();
+        assert!(err.get_message().contains("'in/a.bmp' и 'in/a.png'"));
+    }
+
+    #[test]
+    fn batch_skips_unreadable_imgs() {
+        use super::{proc_step::ProcStep, ImgFormat};
+
+        let dir = std::env::temp_dir().join("image_processing_batch_test");
+        let input_dir = dir.join("in");
+        let output_dir = dir.join("out");
+        std::fs::create_dir_all(&input_dir).unwrap();
+
+        let img = Img::empty_with_size(3, 2, ColorDepth::L8);
+        for name in ["a.png", "c.png"] {
+            let path = input_dir.join(name);
+            img.try_save_as(&path.to_string_lossy(), ImgFormat::Png)
+                .unwrap();
+        }
+        std::fs::write(input_dir.join("b.png"), b"not an image").unwrap();
+
+        let proc_steps = vec![ProcStep::new(
+            Box::new(LinearMean::default()) as FilterBase,
+            String::new(),
+        )];
+        let (mut executor_handle, _delegator_handle) = create_task_info_channel();
+        let res = Guarded::batch_process(
+            &mut executor_handle,
+            &proc_steps,
+            &input_dir.to_string_lossy(),
+            &output_dir.to_string_lossy(),
+            ImgFormat::Png,
+        );
+
+        match res {
+            Err(crate::processing::TaskStop::Err(err)) => {
+                let msg = err.get_message();
+                assert!(msg.contains("b.png"));
+                assert!(!msg.contains("a.png") && !msg.contains("c.png"));
+            }
+            _ => panic!("the unreadable image must be reported"),
+        }
+        assert!(output_dir.join("a.png").is_file());
+        assert!(!output_dir.join("b.png").exists());
+        assert!(output_dir.join("c.png").is_file());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,35 @@
 use crate::img::{filter::FilterBase, Img};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_IMG_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub fn next_img_id() -> usize {
+    NEXT_IMG_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 pub struct ProcStep {
     pub img: Option<Img>,
+    // changes every time the img changes, so cached results can be checked for validity
+    pub img_id: usize,
     pub filter: FilterBase,
+    pub notes: String,
 }
 
 impl ProcStep {
+    pub fn new(filter: FilterBase, notes: String) -> Self {
+        ProcStep {
+            img: None,
+            img_id: next_img_id(),
+            filter,
+            notes,
+        }
+    }
+
+    pub fn set_img(&mut self, img: Option<Img>) {
+        self.img = img;
+        self.img_id = next_img_id();
+    }
+
     pub fn get_description(&self) -> String {
         let filter_descr = self.filter.get_description();
 
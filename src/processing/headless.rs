@@ -0,0 +1,138 @@
+use super::{
+    create_task_info_channel,
+    guarded::{collect_inputs, result_file_path},
+    project_file::ProjectFile,
+    DelegatorHandle, ExecutorHandle, TaskState, TaskStop,
+};
+use crate::{
+    img::{filter::FilterBase, Img, ImgFormat},
+    my_err::MyError,
+};
+use std::{thread, time::Duration};
+
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn run_chain(
+    project_path: &str,
+    img_path: Option<&str>,
+    output_dir: &str,
+    format: ImgFormat,
+) -> Result<(), MyError> {
+    let project = ProjectFile::try_load(project_path)?;
+
+    // the branches are for comparing in the UI, the result is made by the main chain
+    let filters: Vec<FilterBase> = project.steps.into_iter().map(|s| s.filter).collect();
+    if filters.is_empty() {
+        return Err(MyError::new(format!(
+            "В проекте '{}' нет шагов обработки",
+            project_path
+        )));
+    }
+
+    let img_path: String = match (img_path, project.initial_img_path) {
+        (Some(path), _) => path.to_string(),
+        (None, Some(path)) => path,
+        (None, None) => {
+            return Err(MyError::new(format!(
+                "В проекте '{}' не указано исходное изображение",
+                project_path
+            )));
+        }
+    };
+
+    // only the formats of the decoder, the run doesn't use the UI toolkit
+    let mut img: Img = Img::try_load(&img_path)?;
+
+    if let Some(crop_area) = project.crop_area {
+        if !crop_area.is_inside_of(&img.get_area()) {
+            return Err(MyError::new(format!(
+                "Область обрезки из проекта не помещается в изображение '{}'",
+                img_path
+            )));
+        }
+        img = img.get_cropped_copy(crop_area);
+    }
+
+    let (mut executor_handle, delegator_handle) = create_task_info_channel();
+
+    // the steps may use the results of any steps before them
+    let mut results = Vec::<Img>::with_capacity(filters.len());
+
+    for (step_num, filter) in filters.iter().enumerate() {
+        let step_label = format!(
+            "Шаг {}/{} ({})",
+            step_num + 1,
+            filters.len(),
+            filter.get_description()
+        );
+
+        let inputs = collect_inputs(filter, step_num, &img, |num| results.get(num))?;
+
+        let result = process_step(
+            filter,
+            results.last().unwrap_or(&img),
+            &inputs,
+            &step_label,
+            &mut executor_handle,
+            &delegator_handle,
+        )?;
+        results.push(result);
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+
+    let result_path = result_file_path(output_dir, &img_path, format);
+
+    results
+        .last()
+        .unwrap()
+        .try_save_as(&result_path.to_string_lossy(), format)?;
+
+    eprintln!("Результат сохранен в '{}'", result_path.display());
+
+    Ok(())
+}
+
+fn process_step(
+    filter: &FilterBase,
+    img: &Img,
+    inputs: &[&Img],
+    step_label: &str,
+    executor_handle: &mut ExecutorHandle,
+    delegator_handle: &DelegatorHandle,
+) -> Result<Img, MyError> {
+    executor_handle.reset(filter.get_steps_num(img));
+
+    let filter_copy: FilterBase = filter.get_copy();
+    let worker_executor_handle: &mut ExecutorHandle = executor_handle;
+
+    let result: Result<Img, TaskStop> = thread::scope(|s| {
+        let worker =
+            s.spawn(move || filter_copy.process_with_inputs(img, inputs, worker_executor_handle));
+
+        while !worker.is_finished() {
+            report_progress(step_label, delegator_handle);
+            thread::sleep(PROGRESS_POLL_INTERVAL);
+        }
+
+        worker.join().expect("Processing thread panicked")
+    });
+
+    report_progress(step_label, delegator_handle);
+    eprintln!();
+
+    executor_handle.finish_task(result.as_ref().map(|_| ()).map_err(|stop| stop.clone()));
+    let _ = delegator_handle.get_task_result();
+
+    match result {
+        Ok(img) => Ok(img),
+        Err(TaskStop::Err(err)) => Err(err),
+        Err(TaskStop::Halted) => Err(MyError::new(format!("{}: обработка прервана", step_label))),
+    }
+}
+
+fn report_progress(step_label: &str, delegator_handle: &DelegatorHandle) {
+    if let TaskState::InProgress { percents } = delegator_handle.get_task_state() {
+        eprint!("\r{}: {}%", step_label, percents);
+    }
+}
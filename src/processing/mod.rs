@@ -1,5 +1,7 @@
 mod background_worker;
 mod guarded;
+mod headless;
+mod project_file;
 mod task_info_channel;
 
 #[cfg(test)]
@@ -9,7 +11,9 @@ pub use background_worker::BackgroundWorker;
 pub use guarded::StartProcResult;
 pub use guarded::StartResultsSavingResult;
 pub use guarded::TaskSetup;
-pub use guarded::PROJECT_EXT;
+pub use guarded::MAIN_BRANCH;
+pub use headless::run_chain;
+pub use project_file::{LEGACY_PROJECT_EXT, PROJECT_EXT};
 pub use task_info_channel::{
-    create_task_info_channel, DelegatorHandle, ExecutorHandle, TaskState, TaskStop,
+    create_task_info_channel, DelegatorHandle, ExecutorHandle, TaskStage, TaskState, TaskStop,
 };
@@ -0,0 +1,834 @@
+use crate::{
+    img::{
+        filter::{try_parce_filter, FilterBase},
+        Img, ImgFormat, PixelPos, PixelsArea,
+    },
+    my_err::MyError,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Project file, version 2 (TOML):
+//
+//   version = 2
+//   initial_img_path = "images/cells.png"   # optional
+//
+//   [crop_area]                              # optional, pixels, inclusive
+//   top = 10
+//   left = 20
+//   bottom = 110
+//   right = 220
+//
+//   [[steps]]                                # one table per step, in order
+//   filter = "LinearMean"                    # save name of the filter
+//   params = """                             # same text as in the step editor
+//   3 x 3
+//   Ext: near"""
+//   notes = "убрать шум"                     # optional
+//   result = "name.cache/step_1.ipraw"       # optional, cached result of the step
+//
+//   [[branches]]                             # optional, one table per extra branch
+//   name = "медианный"
+//   from_step = 1                            # optional, the step of the main chain the
+//                                            # branch continues, the initial image if absent
+//   [[branches.steps]]                       # same as the steps of the main chain
+//   filter = "MedianFilter"
+//   result = "name.cache/branch_1_step_1.ipraw"
+//
+// Version 1 is the same but without branches.
+//
+// A project saved as a bundle also has `initial_img_cache` at the top level, the
+// initial image and the results are kept in the "<project name>.cache" folder
+// next to the project file, paths are relative to the project file.
+//
+// Files of the old free-text format (blocks separated by "||") are read as well
+// and are saved in the new format next time.
+
+pub const PROJECT_EXT: &str = "ipproj";
+pub const LEGACY_PROJECT_EXT: &str = "ps";
+pub const PROJECT_VERSION: u32 = 2;
+
+const LEGACY_FILTER_SEPARATOR: &str = "||";
+
+pub struct ProjectStep {
+    pub filter: FilterBase,
+    pub notes: String,
+    // saved and loaded only with a bundle
+    pub result: Option<Img>,
+}
+
+pub struct ProjectBranch {
+    pub name: String,
+    // number of the step of the main chain, the initial image if none
+    pub from_step: Option<usize>,
+    pub steps: Vec<ProjectStep>,
+}
+
+#[derive(Default)]
+pub struct ProjectFile {
+    pub initial_img_path: Option<String>,
+    pub crop_area: Option<PixelsArea>,
+    pub steps: Vec<ProjectStep>,
+    pub branches: Vec<ProjectBranch>,
+    // saved and loaded only with a bundle
+    pub initial_img: Option<Img>,
+}
+
+#[derive(Default)]
+struct CachePaths {
+    initial_img: Option<String>,
+    results: Vec<Option<String>>,
+    steps_lines: Vec<usize>,
+    branches_results: Vec<Vec<Option<String>>>,
+    branches_steps_lines: Vec<usize>,
+}
+
+impl ProjectFile {
+    pub fn try_load(file_path: &str) -> Result<Self, MyError> {
+        let text = std::fs::read_to_string(file_path)?;
+
+        let project_dir: &Path = Path::new(file_path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+
+        Self::parse(&text)
+            .and_then(|(mut project, cache_paths)| {
+                project.load_cache(project_dir, cache_paths)?;
+                Ok(project)
+            })
+            .map_err(|err| {
+                MyError::new(format!(
+                    "Не удалось загрузить проект '{}': {}",
+                    file_path,
+                    err.get_message()
+                ))
+            })
+    }
+
+    // the initial image and the results are saved too if there are any
+    pub fn try_save(&self, file_path: &str) -> Result<(), MyError> {
+        let cache_dir_name: Option<String> = if self.has_cache() {
+            let path = Path::new(file_path);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let dir_name = format!("{}.cache", stem);
+
+            let dir_path = path.with_file_name(&dir_name);
+            std::fs::create_dir_all(&dir_path)?;
+            self.save_cache(&dir_path)?;
+
+            Some(dir_name)
+        } else {
+            None
+        };
+
+        let text = self.to_content_text(cache_dir_name.as_deref())?;
+
+        use std::io::Write;
+        let mut file = std::fs::File::create(file_path)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+
+    fn has_cache(&self) -> bool {
+        self.initial_img.is_some() || self.all_steps().any(|(_, _, step)| step.result.is_some())
+    }
+
+    // the steps of the main chain go first, the branches are numbered from 1
+    fn all_steps(&self) -> impl Iterator<Item = (usize, usize, &ProjectStep)> {
+        let main_steps = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(step_num, step)| (0, step_num, step));
+
+        let branches_steps = self
+            .branches
+            .iter()
+            .enumerate()
+            .flat_map(|(branch_ind, branch)| {
+                branch
+                    .steps
+                    .iter()
+                    .enumerate()
+                    .map(move |(step_num, step)| (branch_ind + 1, step_num, step))
+            });
+
+        main_steps.chain(branches_steps)
+    }
+
+    fn save_cache(&self, dir_path: &Path) -> Result<(), MyError> {
+        let mut saved_names = Vec::<String>::new();
+        let mut save = |name: String, img: &Img| -> Result<(), MyError> {
+            img.try_save_as(&dir_path.join(&name).to_string_lossy(), ImgFormat::Raw)?;
+            saved_names.push(name);
+            Ok(())
+        };
+
+        if let Some(ref img) = self.initial_img {
+            save(initial_img_cache_name(), img)?;
+        }
+        for (branch_num, step_num, step) in self.all_steps() {
+            if let Some(ref img) = step.result {
+                save(result_cache_name(branch_num, step_num), img)?;
+            }
+        }
+
+        // the results of the previous save the project doesn't have anymore,
+        // including the ones of the removed steps and branches
+        for entry in std::fs::read_dir(dir_path)? {
+            let path = entry?.path();
+            let is_cache = ImgFormat::from_path(&path.to_string_lossy()) == Some(ImgFormat::Raw);
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            let is_saved = saved_names.iter().any(|saved| saved == name);
+            if is_cache && !is_saved && path.is_file() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_cache(&mut self, project_dir: &Path, cache_paths: CachePaths) -> Result<(), MyError> {
+        let load = |rel_path: &str| -> Result<Img, MyError> {
+            Img::try_load(&project_dir.join(rel_path).to_string_lossy())
+        };
+
+        if let Some(ref path) = cache_paths.initial_img {
+            self.initial_img = Some(load(path)?);
+        }
+
+        for (step_num, path) in cache_paths.results.iter().enumerate() {
+            if let Some(path) = path {
+                let img = load(path).map_err(|err| {
+                    step_error(
+                        step_num,
+                        cache_paths.steps_lines.get(step_num).copied(),
+                        err,
+                    )
+                })?;
+                self.steps[step_num].result = Some(img);
+            }
+        }
+
+        let mut lines_iter = cache_paths.branches_steps_lines.iter();
+        for (branch, paths) in self
+            .branches
+            .iter_mut()
+            .zip(cache_paths.branches_results.iter())
+        {
+            for (step_num, path) in paths.iter().enumerate() {
+                let line: Option<usize> = lines_iter.next().copied();
+                if let Some(path) = path {
+                    let img = load(path).map_err(|err| {
+                        branch_error(&branch.name, step_error(step_num, line, err))
+                    })?;
+                    branch.steps[step_num].result = Some(img);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse(text: &str) -> Result<(Self, CachePaths), MyError> {
+        if is_legacy_text(text) {
+            Ok((Self::try_from_legacy_text(text)?, CachePaths::default()))
+        } else {
+            Self::try_from_toml_text(text)
+        }
+    }
+
+    fn to_content_text(&self, cache_dir_name: Option<&str>) -> Result<String, MyError> {
+        let cache_path = |file_name: String, has_img: bool| -> Option<String> {
+            match cache_dir_name {
+                Some(dir_name) if has_img => Some(format!("{}/{}", dir_name, file_name)),
+                _ => None,
+            }
+        };
+
+        // such a project could not be opened again
+        for branch in self.branches.iter() {
+            match branch.from_step {
+                Some(step_num) if step_num >= self.steps.len() => {
+                    return Err(branch_error(
+                        &branch.name,
+                        MyError::new(format!("в основной цепочке нет шага {}", step_num + 1)),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        let content = FileContent {
+            version: PROJECT_VERSION,
+            initial_img_path: self.initial_img_path.clone(),
+            initial_img_cache: cache_path(initial_img_cache_name(), self.initial_img.is_some()),
+            crop_area: self.crop_area.map(CropAreaContent::from),
+            steps: steps_content(&self.steps, 0, &cache_path),
+            branches: self
+                .branches
+                .iter()
+                .enumerate()
+                .map(|(branch_ind, branch)| BranchContent {
+                    name: branch.name.clone(),
+                    from_step: branch.from_step.map(|step_num| step_num + 1),
+                    steps: steps_content(&branch.steps, branch_ind + 1, &cache_path),
+                })
+                .collect(),
+        };
+
+        toml::to_string_pretty(&content)
+            .map_err(|err| MyError::new(format!("Не удалось записать проект: {}", err)))
+    }
+
+    fn try_from_toml_text(text: &str) -> Result<(Self, CachePaths), MyError> {
+        let content: FileContent = toml::from_str(text).map_err(|err| {
+            let line_info = match err.line_col() {
+                Some((line, _col)) => format!("строка {}: ", line + 1),
+                None => String::new(),
+            };
+            MyError::new(format!("{}{}", line_info, err))
+        })?;
+
+        if content.version == 0 || content.version > PROJECT_VERSION {
+            return Err(MyError::new(format!(
+                "версия формата {} не поддерживается, поддерживаются версии 1..{}",
+                content.version, PROJECT_VERSION
+            )));
+        }
+
+        let crop_area: Option<PixelsArea> = match content.crop_area {
+            Some(area) => Some(area.try_into_area()?),
+            None => None,
+        };
+
+        let mut cache_paths = CachePaths {
+            initial_img: content.initial_img_cache,
+            results: Vec::with_capacity(content.steps.len()),
+            steps_lines: lines_of_tables(text, "[[steps]]"),
+            branches_results: Vec::with_capacity(content.branches.len()),
+            branches_steps_lines: lines_of_tables(text, "[[branches.steps]]"),
+        };
+
+        let mut steps = Vec::<ProjectStep>::with_capacity(content.steps.len());
+        for (step_num, step) in content.steps.into_iter().enumerate() {
+            let filter = try_parce_filter(&step.filter, &step.params).map_err(|err| {
+                step_error(
+                    step_num,
+                    cache_paths.steps_lines.get(step_num).copied(),
+                    err,
+                )
+            })?;
+
+            steps.push(ProjectStep {
+                filter,
+                notes: step.notes,
+                result: None,
+            });
+            cache_paths.results.push(step.result);
+        }
+
+        let mut branches = Vec::<ProjectBranch>::with_capacity(content.branches.len());
+        let mut lines_iter = cache_paths.branches_steps_lines.iter();
+        for (branch_ind, branch) in content.branches.into_iter().enumerate() {
+            let BranchContent {
+                name,
+                from_step,
+                steps: branch_steps_content,
+            } = branch;
+
+            if name.trim().is_empty() {
+                return Err(MyError::new(format!(
+                    "у ветви {} нет имени",
+                    branch_ind + 1
+                )));
+            }
+
+            let fork_step: Option<usize> = match from_step {
+                Some(step_num) if step_num == 0 || step_num > steps.len() => {
+                    return Err(branch_error(
+                        &name,
+                        MyError::new(format!("в основной цепочке нет шага {}", step_num)),
+                    ));
+                }
+                Some(step_num) => Some(step_num - 1),
+                None => None,
+            };
+
+            let mut branch_steps = Vec::<ProjectStep>::with_capacity(branch_steps_content.len());
+            let mut branch_results =
+                Vec::<Option<String>>::with_capacity(branch_steps_content.len());
+            for (step_num, step) in branch_steps_content.into_iter().enumerate() {
+                let line: Option<usize> = lines_iter.next().copied();
+                let filter = try_parce_filter(&step.filter, &step.params)
+                    .map_err(|err| branch_error(&name, step_error(step_num, line, err)))?;
+
+                branch_steps.push(ProjectStep {
+                    filter,
+                    notes: step.notes,
+                    result: None,
+                });
+                branch_results.push(step.result);
+            }
+
+            branches.push(ProjectBranch {
+                name,
+                from_step: fork_step,
+                steps: branch_steps,
+            });
+            cache_paths.branches_results.push(branch_results);
+        }
+
+        let project = ProjectFile {
+            initial_img_path: content.initial_img_path,
+            crop_area,
+            steps,
+            branches,
+            initial_img: None,
+        };
+
+        Ok((project, cache_paths))
+    }
+
+    fn try_from_legacy_text(text: &str) -> Result<Self, MyError> {
+        let mut steps = Vec::<ProjectStep>::new();
+
+        let mut block_start: usize = 0;
+        for block in text.split(LEGACY_FILTER_SEPARATOR) {
+            let block_offset = block_start + (block.len() - block.trim_start().len());
+            block_start += block.len() + LEGACY_FILTER_SEPARATOR.len();
+
+            let block = block.trim();
+            if block.is_empty() {
+                continue;
+            }
+
+            let mut lines_iter = crate::utils::LinesIter::new(block);
+            let filter_name = lines_iter.next_or_empty().to_string();
+            let filter_content = lines_iter.all_left(true);
+
+            let line = text[..block_offset].matches('\n').count() + 1;
+
+            let filter = try_parce_filter(&filter_name, &filter_content)
+                .map_err(|err| step_error(steps.len(), Some(line), err))?;
+
+            steps.push(ProjectStep {
+                filter,
+                notes: String::new(),
+                result: None,
+            });
+        }
+
+        Ok(ProjectFile {
+            steps,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileContent {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    initial_img_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    initial_img_cache: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crop_area: Option<CropAreaContent>,
+    #[serde(default)]
+    steps: Vec<StepContent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    branches: Vec<BranchContent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BranchContent {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from_step: Option<usize>,
+    #[serde(default)]
+    steps: Vec<StepContent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StepContent {
+    filter: String,
+    #[serde(default)]
+    params: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    notes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CropAreaContent {
+    top: usize,
+    left: usize,
+    bottom: usize,
+    right: usize,
+}
+
+impl CropAreaContent {
+    fn try_into_area(self) -> Result<PixelsArea, MyError> {
+        if self.top > self.bottom || self.left > self.right {
+            return Err(MyError::new(
+                "в области обрезки верх должен быть не ниже низа, а левый край не правее правого"
+                    .to_string(),
+            ));
+        }
+
+        Ok(PixelsArea::new(
+            PixelPos::new(self.top, self.left),
+            PixelPos::new(self.bottom, self.right),
+        ))
+    }
+}
+
+impl From<PixelsArea> for CropAreaContent {
+    fn from(area: PixelsArea) -> Self {
+        CropAreaContent {
+            top: area.top_left().row,
+            left: area.top_left().col,
+            bottom: area.bottom_right().row,
+            right: area.bottom_right().col,
+        }
+    }
+}
+
+// the old format is not TOML, and the new one always has the version key
+fn is_legacy_text(text: &str) -> bool {
+    match toml::from_str::<toml::Value>(text) {
+        Ok(toml::Value::Table(table)) => !table.contains_key("version"),
+        _ => true,
+    }
+}
+
+fn initial_img_cache_name() -> String {
+    format!("initial.{}", ImgFormat::Raw.extension())
+}
+
+fn result_cache_name(branch_num: usize, step_num: usize) -> String {
+    if branch_num == 0 {
+        format!("step_{}.{}", step_num + 1, ImgFormat::Raw.extension())
+    } else {
+        format!(
+            "branch_{}_step_{}.{}",
+            branch_num,
+            step_num + 1,
+            ImgFormat::Raw.extension()
+        )
+    }
+}
+
+fn steps_content(
+    steps: &[ProjectStep],
+    branch_num: usize,
+    cache_path: &impl Fn(String, bool) -> Option<String>,
+) -> Vec<StepContent> {
+    steps
+        .iter()
+        .enumerate()
+        .map(|(step_num, step)| StepContent {
+            filter: step.filter.get_save_name(),
+            params: step.filter.params_to_string().unwrap_or_default(),
+            notes: step.notes.clone(),
+            result: cache_path(
+                result_cache_name(branch_num, step_num),
+                step.result.is_some(),
+            ),
+        })
+        .collect()
+}
+
+fn lines_of_tables(text: &str, header: &str) -> Vec<usize> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim() == header)
+        .map(|(line_num, _)| line_num + 1)
+        .collect()
+}
+
+fn step_error(step_num: usize, line: Option<usize>, err: MyError) -> MyError {
+    match line {
+        Some(line) => MyError::new(format!(
+            "шаг {} (строка {}): {}",
+            step_num + 1,
+            line,
+            err.get_message()
+        )),
+        None => MyError::new(format!("шаг {}: {}", step_num + 1, err.get_message())),
+    }
+}
+
+fn branch_error(name: &str, err: MyError) -> MyError {
+    MyError::new(format!("ветвь '{}', {}", name, err.get_message()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MyError, ProjectFile};
+    use crate::img::{PixelPos, PixelsArea};
+
+    fn from_text(text: &str) -> Result<ProjectFile, MyError> {
+        ProjectFile::parse(text).map(|(project, _cache_paths)| project)
+    }
+
+    #[test]
+    fn toml_roundtrip() {
+        let text = "version = 1\n\
+            initial_img_path = \"a b/img.png\"\n\
+            \n\
+            [crop_area]\n\
+            top = 1\n\
+            left = 2\n\
+            bottom = 3\n\
+            right = 4\n\
+            \n\
+            [[steps]]\n\
+            filter = \"Rgb2Gray\"\n\
+            \n\
+            [[steps]]\n\
+            filter = \"LinearMean\"\n\
+            params = \"\"\"\n\
+            5 x 5\n\
+            Ext: near\"\"\"\n\
+            notes = \"a || b\"\n";
+
+        let project = from_text(text).unwrap();
+        assert_eq!(project.initial_img_path.as_deref(), Some("a b/img.png"));
+        assert_eq!(
+            project.crop_area,
+            Some(PixelsArea::new(PixelPos::new(1, 2), PixelPos::new(3, 4)))
+        );
+        assert_eq!(project.steps.len(), 2);
+        assert_eq!(
+            project.steps[1].filter.get_description(),
+            "Линейный фильтр (усредняющий) 5x5"
+        );
+        assert_eq!(project.steps[1].notes, "a || b");
+
+        let saved = project.to_content_text(None).unwrap();
+        let reloaded = from_text(&saved).unwrap();
+        assert_eq!(reloaded.crop_area, project.crop_area);
+        assert_eq!(reloaded.steps.len(), 2);
+        assert_eq!(
+            reloaded.steps[1].filter.params_to_string(),
+            project.steps[1].filter.params_to_string()
+        );
+        assert_eq!(reloaded.steps[1].notes, "a || b");
+    }
+
+    #[test]
+    fn bundle_restores_cached_imgs() {
+        use super::{ProjectBranch, ProjectStep};
+        use crate::img::{
+            filter::{color_channel::EqualizeHist, FilterBase},
+            Img,
+        };
+        use fltk::enums::ColorDepth;
+
+        let mut project = from_text(
+            "version = 1\n[[steps]]\nfilter = \"Rgb2Gray\"\n[[steps]]\nfilter = \"EqualizeHist\"\n",
+        )
+        .unwrap();
+
+        let mut initial_img = Img::empty_with_size(3, 2, ColorDepth::Rgb8);
+        initial_img.layer_mut(1).matrix_mut()[4] = 0.5;
+        project.initial_img = Some(initial_img);
+        project.steps[0].result = Some(Img::empty_with_size(3, 2, ColorDepth::L8));
+        project.branches.push(ProjectBranch {
+            name: "b".to_string(),
+            from_step: Some(0),
+            steps: vec![ProjectStep {
+                filter: Box::new(EqualizeHist::default()) as FilterBase,
+                notes: String::new(),
+                result: Some(Img::empty_with_size(2, 2, ColorDepth::L8)),
+            }],
+        });
+
+        let dir = std::env::temp_dir().join("image_processing_bundle_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.ipproj");
+        project.try_save(path.to_str().unwrap()).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("bundle.cache/step_1.ipraw"));
+        assert!(!text.contains("/step_2"));
+        assert!(text.contains("bundle.cache/branch_1_step_1.ipraw"));
+
+        let loaded = ProjectFile::try_load(path.to_str().unwrap()).unwrap();
+        let loaded_initial = loaded.initial_img.as_ref().unwrap();
+        assert_eq!(loaded_initial.layer(1).matrix()[4], 0.5);
+        assert_eq!(loaded.steps[0].result.as_ref().unwrap().w(), 3);
+        assert!(loaded.steps[1].result.is_none());
+        let branch_result = loaded.branches[0].steps[0].result.as_ref().unwrap();
+        assert_eq!(branch_result.w(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bundle_removes_stale_cached_imgs() {
+        use super::{ProjectBranch, ProjectStep};
+        use crate::img::{
+            filter::{color_channel::EqualizeHist, FilterBase},
+            Img,
+        };
+        use fltk::enums::ColorDepth;
+
+        let mut project = from_text(
+            "version = 1\n[[steps]]\nfilter = \"Rgb2Gray\"\n[[steps]]\nfilter = \"EqualizeHist\"\n",
+        )
+        .unwrap();
+        project.steps[0].result = Some(Img::empty_with_size(3, 2, ColorDepth::L8));
+        project.steps[1].result = Some(Img::empty_with_size(3, 2, ColorDepth::L8));
+        project.branches.push(ProjectBranch {
+            name: "b".to_string(),
+            from_step: Some(0),
+            steps: vec![ProjectStep {
+                filter: Box::new(EqualizeHist::default()) as FilterBase,
+                notes: String::new(),
+                result: Some(Img::empty_with_size(2, 2, ColorDepth::L8)),
+            }],
+        });
+
+        let dir = std::env::temp_dir().join("image_processing_stale_cache_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.ipproj");
+        project.try_save(path.to_str().unwrap()).unwrap();
+
+        let cache_dir = dir.join("bundle.cache");
+        assert!(cache_dir.join("step_2.ipraw").is_file());
+        assert!(cache_dir.join("branch_1_step_1.ipraw").is_file());
+
+        project.steps.pop();
+        project.branches.clear();
+        project.try_save(path.to_str().unwrap()).unwrap();
+
+        assert!(cache_dir.join("step_1.ipraw").is_file());
+        assert!(!cache_dir.join("step_2.ipraw").exists());
+        assert!(!cache_dir.join("branch_1_step_1.ipraw").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn branches_roundtrip() {
+        let text = "version = 2\n\
+            [[steps]]\n\
+            filter = \"Rgb2Gray\"\n\
+            \n\
+            [[branches]]\n\
+            name = \"medians\"\n\
+            from_step = 1\n\
+            \n\
+            [[branches.steps]]\n\
+            filter = \"EqualizeHist\"\n\
+            \n\
+            [[branches]]\n\
+            name = \"from initial\"\n";
+
+        let project = from_text(text).unwrap();
+        assert_eq!(project.branches.len(), 2);
+        assert_eq!(project.branches[0].name, "medians");
+        assert_eq!(project.branches[0].from_step, Some(0));
+        assert_eq!(
+            project.branches[0].steps[0].filter.get_save_name(),
+            "EqualizeHist"
+        );
+        assert_eq!(project.branches[1].from_step, None);
+        assert!(project.branches[1].steps.is_empty());
+
+        let saved = project.to_content_text(Some("p.cache")).unwrap();
+        let reloaded = from_text(&saved).unwrap();
+        assert_eq!(reloaded.steps.len(), 1);
+        assert_eq!(reloaded.branches.len(), 2);
+        assert_eq!(reloaded.branches[0].from_step, Some(0));
+        assert_eq!(reloaded.branches[1].name, "from initial");
+
+        let err = from_text(
+            "version = 2\n[[branches]]\nname = \"b\"\n[[branches.steps]]\nfilter = \"Nope\"\n",
+        )
+        .err()
+        .unwrap();
+        assert!(err.get_message().starts_with("ветвь 'b', шаг 1 (строка 4)"));
+
+        let err = from_text("version = 2\n[[branches]]\nname = \"b\"\nfrom_step = 1\n")
+            .err()
+            .unwrap();
+        assert!(err.get_message().starts_with("ветвь 'b'"));
+
+        let mut project = project;
+        project.branches[0].from_step = Some(1);
+        let err = project.to_content_text(None).err().unwrap();
+        assert_eq!(
+            err.get_message(),
+            "ветвь 'medians', в основной цепочке нет шага 2"
+        );
+
+        assert!(from_text("version = 1\n[[steps]]\nfilter = \"Rgb2Gray\"\n").is_ok());
+    }
+
+    #[test]
+    fn legacy_format_is_migrated() {
+        let text = "Rgb2Gray\n\n||\nEqualizeHist\n\n";
+
+        let project = from_text(text).unwrap();
+        assert!(project.initial_img_path.is_none());
+        assert_eq!(project.steps.len(), 2);
+        assert_eq!(project.steps[1].filter.get_save_name(), "EqualizeHist");
+
+        assert!(project
+            .to_content_text(None)
+            .unwrap()
+            .starts_with("version = 2"));
+    }
+
+    #[test]
+    fn format_doesnt_depend_on_keys_order() {
+        let text = "# edited by hand\n\
+            initial_img_path = \"img.png\"\n\
+            version = 2\n\
+            \n\
+            [crop_area]\n\
+            top = 1\n\
+            left = 2\n\
+            bottom = 3\n\
+            right = 4\n\
+            \n\
+            [[steps]]\n\
+            filter = \"Rgb2Gray\"\n";
+
+        let project = from_text(text).unwrap();
+        assert_eq!(project.initial_img_path.as_deref(), Some("img.png"));
+        assert_eq!(
+            project.crop_area,
+            Some(PixelsArea::new(PixelPos::new(1, 2), PixelPos::new(3, 4)))
+        );
+        assert_eq!(project.steps.len(), 1);
+    }
+
+    #[test]
+    fn errors_point_to_step_and_line() {
+        let text =
+            "version = 1\n\n[[steps]]\nfilter = \"Rgb2Gray\"\n\n[[steps]]\nfilter = \"Nope\"\n";
+        let err = from_text(text).err().unwrap();
+        assert!(err.get_message().starts_with("шаг 2 (строка 6)"));
+
+        let legacy = "Rgb2Gray\n\n||\n\nNope\n";
+        let err = from_text(legacy).err().unwrap();
+        assert!(err.get_message().starts_with("шаг 2 (строка 5)"));
+
+        let err = from_text("version = 1\nsteps = 5\n").err().unwrap();
+        assert!(err.get_message().starts_with("строка"));
+
+        let err = from_text("version = 7\n").err().unwrap();
+        assert!(err.get_message().contains('7'));
+    }
+}
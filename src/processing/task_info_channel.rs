@@ -9,31 +9,67 @@ use crate::my_err::MyError;
 
 pub fn create_task_info_channel() -> (ExecutorHandle, DelegatorHandle) {
     let inner = Arc::new(Mutex::new(TaskState::Empty));
-    (ExecutorHandle::new(&inner), DelegatorHandle::new(&inner))
+    let stage = Arc::new(Mutex::new(None));
+    (
+        ExecutorHandle::new(&inner, &stage),
+        DelegatorHandle::new(&inner, &stage),
+    )
 }
 
 pub struct ExecutorHandle {
-    actions_completed: usize,
-    actions_total: usize,
+    progress: Arc<Mutex<Progress>>,
     inner: Arc<Mutex<TaskState>>,
+    stage: Arc<Mutex<Option<TaskStage>>>,
 }
 
 impl ExecutorHandle {
-    fn new(inner: &Arc<Mutex<TaskState>>) -> Self {
+    fn new(inner: &Arc<Mutex<TaskState>>, stage: &Arc<Mutex<Option<TaskStage>>>) -> Self {
         ExecutorHandle {
-            actions_completed: 0,
-            actions_total: 0,
+            progress: Arc::new(Mutex::new(Progress {
+                actions_completed: 0,
+                actions_total: 0,
+                stage_num: 0,
+                stages_total: 1,
+            })),
             inner: Arc::clone(inner),
+            stage: Arc::clone(stage),
+        }
+    }
+
+    // a handle for a worker thread, it reports actions to the same task
+    pub fn share(&self) -> Self {
+        ExecutorHandle {
+            progress: Arc::clone(&self.progress),
+            inner: Arc::clone(&self.inner),
+            stage: Arc::clone(&self.stage),
         }
     }
 
     pub fn reset(&mut self, actions_total: usize) {
+        self.start_task(1, actions_total);
+        *self.stage.lock().unwrap() = None;
+    }
+
+    pub fn reset_staged(&mut self, stages_total: usize) {
+        assert!(stages_total > 0);
+        self.start_task(stages_total, 0);
+        *self.stage.lock().unwrap() = Some(TaskStage {
+            num: 0,
+            total: stages_total,
+            percents: 0,
+        });
+    }
+
+    fn start_task(&mut self, stages_total: usize, actions_total: usize) {
         let mut guard = self.inner.lock().unwrap();
         let state: &mut TaskState = guard.deref_mut();
 
-        print!("reset ");
-        self.actions_total = actions_total;
-        self.actions_completed = 0;
+        *self.progress.lock().unwrap() = Progress {
+            actions_completed: 0,
+            actions_total,
+            stage_num: 0,
+            stages_total,
+        };
 
         match state {
             TaskState::Empty => *state = TaskState::InProgress { percents: 0 },
@@ -44,6 +80,38 @@ impl ExecutorHandle {
         drop(guard);
     }
 
+    pub fn start_stage(&mut self, stage_num: usize, actions_total: usize) -> Result<(), TaskStop> {
+        let mut guard = self.inner.lock().unwrap();
+        let state: &mut TaskState = guard.deref_mut();
+
+        let result: Result<(), TaskStop> = match state {
+            // the task was halted and its result was already taken by the delegator
+            TaskState::Empty => Err(TaskStop::Halted),
+            TaskState::InProgress { .. } => {
+                let mut progress = self.progress.lock().unwrap();
+
+                assert!(stage_num < progress.stages_total);
+                assert!(stage_num >= progress.stage_num);
+
+                progress.stage_num = stage_num;
+                progress.actions_total = actions_total;
+                progress.actions_completed = 0;
+
+                *state = TaskState::InProgress {
+                    percents: progress.count_percents(),
+                };
+                self.update_stage(&progress);
+
+                Ok(())
+            }
+            TaskState::Finished { result } => result.clone(),
+        };
+
+        drop(guard);
+
+        result
+    }
+
     pub fn get_task_state(&self) -> TaskState {
         self.inner.lock().unwrap().deref().clone()
     }
@@ -53,14 +121,19 @@ impl ExecutorHandle {
         let state: &mut TaskState = guard.deref_mut();
 
         let result: Result<(), TaskStop> = match state {
-            TaskState::Empty => panic!("Task is empty!"),
+            // the task was halted and its result was already taken by the delegator
+            TaskState::Empty => Err(TaskStop::Halted),
             TaskState::InProgress { .. } => {
-                assert!(self.actions_completed < self.actions_total);
+                let mut progress = self.progress.lock().unwrap();
 
-                self.actions_completed += 1;
+                assert!(progress.actions_completed < progress.actions_total);
 
-                let percents: usize = self.actions_completed * 100 / self.actions_total;
-                *state = TaskState::InProgress { percents };
+                progress.actions_completed += 1;
+
+                *state = TaskState::InProgress {
+                    percents: progress.count_percents(),
+                };
+                self.update_stage(&progress);
 
                 Ok(())
             }
@@ -81,32 +154,64 @@ impl ExecutorHandle {
                     self.assert_all_actions_completed();
                     assert_eq!(*percents, 100);
                 }
+                *state = TaskState::Finished { result };
             }
-            TaskState::Empty | TaskState::Finished { .. } => unreachable!(),
+            // the task was halted by the delegator and its result may be already taken
+            TaskState::Empty | TaskState::Finished { .. } => {}
+        }
+        drop(guard);
+    }
+
+    fn update_stage(&self, progress: &Progress) {
+        let mut guard = self.stage.lock().unwrap();
+        if let Some(stage) = guard.deref_mut() {
+            stage.num = progress.stage_num;
+            stage.percents = progress.stage_percents();
         }
-        *state = TaskState::Finished { result };
         drop(guard);
     }
 
     pub fn assert_all_actions_completed(&self) {
-        if self.actions_completed != self.actions_total {
+        let progress = self.progress.lock().unwrap();
+        if progress.actions_completed != progress.actions_total {
             panic!(
                 "not all acions completed: {} of {}",
-                self.actions_completed, self.actions_total
+                progress.actions_completed, progress.actions_total
             );
         }
     }
 }
 
+struct Progress {
+    actions_completed: usize,
+    actions_total: usize,
+    stage_num: usize,
+    stages_total: usize,
+}
+
+impl Progress {
+    fn stage_percents(&self) -> usize {
+        (self.actions_completed * 100)
+            .checked_div(self.actions_total)
+            .unwrap_or(100)
+    }
+
+    fn count_percents(&self) -> usize {
+        (self.stage_num * 100 + self.stage_percents()) / self.stages_total
+    }
+}
+
 #[derive(Debug)]
 pub struct DelegatorHandle {
     inner: Arc<Mutex<TaskState>>,
+    stage: Arc<Mutex<Option<TaskStage>>>,
 }
 
 impl DelegatorHandle {
-    fn new(inner: &Arc<Mutex<TaskState>>) -> Self {
+    fn new(inner: &Arc<Mutex<TaskState>>, stage: &Arc<Mutex<Option<TaskStage>>>) -> Self {
         DelegatorHandle {
             inner: Arc::clone(inner),
+            stage: Arc::clone(stage),
         }
     }
 
@@ -124,6 +229,10 @@ impl DelegatorHandle {
         guard.deref().clone()
     }
 
+    pub fn get_task_stage(&self) -> Option<TaskStage> {
+        *self.stage.lock().unwrap()
+    }
+
     pub fn clear_task(&self) {
         let mut guard = self.inner.lock().unwrap();
         let state: &mut TaskState = guard.deref_mut();
@@ -140,6 +249,13 @@ impl DelegatorHandle {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct TaskStage {
+    pub num: usize,
+    pub total: usize,
+    pub percents: usize,
+}
+
 #[derive(Clone, Debug)]
 pub enum TaskState {
     Empty,
@@ -183,3 +299,131 @@ impl From<FltkError> for TaskStop {
         TaskStop::Err(err.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{create_task_info_channel, TaskState, TaskStop};
+
+    #[test]
+    fn staged_progress() {
+        let (mut ex, del) = create_task_info_channel();
+
+        ex.reset_staged(4);
+
+        let check_percents = |p: usize| {
+            if let TaskState::InProgress { percents } = del.get_task_state() {
+                assert_eq!(percents, p);
+            } else {
+                panic!("State is not 'InProgress': {:?}", del.get_task_state());
+            }
+        };
+
+        check_percents(0);
+
+        ex.start_stage(0, 2).unwrap();
+        ex.complete_action().unwrap();
+        check_percents(50 / 4);
+        assert_eq!(del.get_task_stage().unwrap().percents, 50);
+        ex.complete_action().unwrap();
+        check_percents(100 / 4);
+
+        ex.start_stage(1, 0).unwrap();
+        check_percents(200 / 4);
+        assert_eq!(del.get_task_stage().unwrap().num, 1);
+
+        ex.start_stage(3, 1).unwrap();
+        check_percents(300 / 4);
+        ex.complete_action().unwrap();
+        check_percents(100);
+
+        let stage = del.get_task_stage().unwrap();
+        assert_eq!(stage.num, 3);
+        assert_eq!(stage.total, 4);
+        assert_eq!(stage.percents, 100);
+
+        ex.finish_task(Ok(()));
+        assert!(del.get_task_result().is_ok());
+    }
+
+    #[test]
+    fn staged_progress_halt() {
+        let (mut ex, del) = create_task_info_channel();
+
+        ex.reset_staged(2);
+        ex.start_stage(0, 3).unwrap();
+        ex.complete_action().unwrap();
+
+        del.halt_task();
+
+        assert!(matches!(ex.complete_action(), Err(TaskStop::Halted)));
+        assert!(matches!(ex.start_stage(1, 3), Err(TaskStop::Halted)));
+
+        ex.finish_task(Err(TaskStop::Halted));
+        assert!(matches!(del.get_task_result(), Err(TaskStop::Halted)));
+    }
+
+    #[test]
+    fn shared_handles_report_to_one_task() {
+        let (mut ex, del) = create_task_info_channel();
+
+        ex.reset(8);
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut worker = ex.share();
+                std::thread::spawn(move || {
+                    worker.complete_action().unwrap();
+                    worker.complete_action().unwrap();
+                })
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+
+        assert!(matches!(
+            del.get_task_state(),
+            TaskState::InProgress { percents: 100 }
+        ));
+
+        del.halt_task();
+        assert!(matches!(
+            ex.share().complete_action(),
+            Err(TaskStop::Halted)
+        ));
+
+        ex.finish_task(Err(TaskStop::Halted));
+        assert!(matches!(del.get_task_result(), Err(TaskStop::Halted)));
+    }
+
+    #[test]
+    fn finish_task_after_halted_result_was_taken() {
+        let (mut ex, del) = create_task_info_channel();
+
+        ex.reset(2);
+        ex.complete_action().unwrap();
+
+        del.halt_task();
+        assert!(matches!(del.get_task_result(), Err(TaskStop::Halted)));
+
+        ex.finish_task(Ok(()));
+        assert!(matches!(del.get_task_state(), TaskState::Empty));
+    }
+
+    #[test]
+    fn actions_after_halted_result_was_taken() {
+        let (mut ex, del) = create_task_info_channel();
+
+        ex.reset_staged(2);
+        ex.start_stage(0, 2).unwrap();
+
+        del.halt_task();
+        assert!(matches!(del.get_task_result(), Err(TaskStop::Halted)));
+
+        assert!(matches!(ex.complete_action(), Err(TaskStop::Halted)));
+        assert!(matches!(ex.start_stage(1, 2), Err(TaskStop::Halted)));
+
+        ex.finish_task(Err(TaskStop::Halted));
+        assert!(matches!(del.get_task_state(), TaskState::Empty));
+    }
+}
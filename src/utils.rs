@@ -466,6 +466,10 @@ impl ScalableRect {
         (pos - self.top_left).div_f(self.scale)
     }
 
+    pub fn pixel_to_self(&self, pos: Pos) -> Pos {
+        pos.mul_f(self.scale) + self.top_left
+    }
+
     pub fn stretch_self_to_area(&mut self, area: RectArea) {
         // ------------------------ fit by scale -------------------------------
         self.scale = self.get_scale_to_fit(area.size());